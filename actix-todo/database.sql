@@ -3,7 +3,8 @@ drop table if exists todo_list;
 
 create table todo_list (
     id serial primary key,
-    title varchar(150)
+    title varchar(150),
+    search tsvector
 );
 
 create table todo_item (
@@ -11,9 +12,28 @@ create table todo_item (
     title varchar(150) not null,
     checked boolean not null default false,
     list_id integer not null,
+    -- lexicographic key, compared byte-wise (see src/position.rs)
+    position varchar(255) collate "C" not null,
+    search tsvector,
+    due_date date,
+    -- RRULE-style rule, e.g. FREQ=WEEKLY;BYDAY=MO (see src/recurrence.rs)
+    recurrence varchar(255),
     foreign key (list_id) references todo_list(id)
 );
 
+-- unique, so two writers racing into the same gap fail instead of storing
+-- keys that `key_between` can no longer split
+create unique index todo_item_list_position on todo_item (list_id, position);
+
+-- full-text search (see GET /search)
+create trigger todo_list_search_update before insert or update on todo_list
+    for each row execute procedure tsvector_update_trigger(search, 'pg_catalog.english', title);
+create trigger todo_item_search_update before insert or update on todo_item
+    for each row execute procedure tsvector_update_trigger(search, 'pg_catalog.english', title);
+
+create index todo_list_search on todo_list using gin (search);
+create index todo_item_search on todo_item using gin (search);
+
 insert into todo_list (title) values ('List 1'), ('List 2');
-insert into todo_item (title, list_id) 
-    values ('Connect to database', 1), ('Do queries', 1);
\ No newline at end of file
+insert into todo_item (title, list_id, position)
+    values ('Connect to database', 1, 'V'), ('Do queries', 1, 'k');
\ No newline at end of file
@@ -7,16 +7,126 @@ pub struct ServerConfig {
     pub port: i32,
 }
 
+/// Cross-origin access for browser clients, e.g. `CORS.ENABLED=true`.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct CorsConfig {
+    pub enabled: bool,
+    /// Comma-separated origins such as `https://app.example.com`, or `*`.
+    /// `*` can't be combined with `allow_credentials`.
+    pub allowed_origins: String,
+    /// Comma-separated HTTP methods.
+    pub allowed_methods: String,
+    pub allow_credentials: bool,
+    /// Seconds browsers may cache a preflight response.
+    pub max_age: usize,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        CorsConfig {
+            enabled: false,
+            allowed_origins: String::new(),
+            allowed_methods: "GET,POST,PUT,DELETE".to_string(),
+            allow_credentials: false,
+            max_age: 3600,
+        }
+    }
+}
+
+impl CorsConfig {
+    /// Refuses `*` origins together with credentials: actix-cors would echo
+    /// back any origin and let every site make credentialed requests.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let any_origin = self.allowed_origins.split(',').any(|origin| origin.trim() == "*");
+        if self.enabled && self.allow_credentials && any_origin {
+            return Err(ConfigError::Message(
+                "CORS.ALLOWED_ORIGINS=* cannot be used with CORS.ALLOW_CREDENTIALS=true; \
+                 list the allowed origins instead"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Standard security response headers, e.g. `SECURITY.HSTS=true`.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct SecurityConfig {
+    pub headers: bool,
+    /// Only turn this on when the server is reached over HTTPS.
+    pub hsts: bool,
+    pub hsts_max_age: u32,
+    /// Sent with HTML responses.
+    pub content_security_policy: String,
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        SecurityConfig {
+            headers: true,
+            hsts: false,
+            hsts_max_age: 31_536_000,
+            content_security_policy: "default-src 'self'; frame-ancestors 'none'".to_string(),
+        }
+    }
+}
+
+/// gzip/brotli/zstd response compression, e.g. `COMPRESSION.ENABLED=false`.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig { enabled: true }
+    }
+}
+
 #[derive(Deserialize)]
 pub struct Config {
     pub server: ServerConfig,
     pub pg: deadpool_postgres::Config,
+    #[serde(default)]
+    pub cors: CorsConfig,
+    #[serde(default)]
+    pub security: SecurityConfig,
+    #[serde(default)]
+    pub compression: CompressionConfig,
 }
 
 impl Config {
   pub fn from_env() -> Result<Self, ConfigError> {
       let mut cfg = config::Config::new();
       cfg.merge(config::Environment::new())?;
-      cfg.try_into()
+      let config: Config = cfg.try_into()?;
+      config.cors.validate()?;
+      Ok(config)
   }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_any_origin_with_credentials() {
+        let mut cors = CorsConfig {
+            enabled: true,
+            allowed_origins: "https://app.example.com, *".to_string(),
+            allow_credentials: true,
+            ..CorsConfig::default()
+        };
+        assert!(cors.validate().is_err());
+
+        cors.allow_credentials = false;
+        assert!(cors.validate().is_ok());
+
+        cors.allowed_origins = "https://app.example.com".to_string();
+        cors.allow_credentials = true;
+        assert!(cors.validate().is_ok());
+    }
+}
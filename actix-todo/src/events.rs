@@ -0,0 +1,30 @@
+use crate::models::{ItemChange, ItemChanged, TodoItem};
+use tokio::sync::broadcast;
+
+// Subscribers that fall this far behind miss events rather than holding up
+// the handlers that publish them.
+const CAPACITY: usize = 256;
+
+/// Fan-out of item changes to GraphQL subscriptions.
+#[derive(Clone)]
+pub struct ItemEvents {
+    sender: broadcast::Sender<ItemChanged>,
+}
+
+impl Default for ItemEvents {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        ItemEvents { sender }
+    }
+}
+
+impl ItemEvents {
+    pub fn publish(&self, change: ItemChange, item: &TodoItem) {
+        // Sending only fails when nobody is subscribed.
+        let _ = self.sender.send(ItemChanged { change, item: item.clone() });
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ItemChanged> {
+        self.sender.subscribe()
+    }
+}
@@ -0,0 +1,303 @@
+use crate::db;
+use crate::events::ItemEvents;
+use crate::models::{CheckedItem, CreateItem, ItemChange, ItemChanged, MoveItem, TodoItem, TodoList};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::{web, HttpRequest, HttpResponse};
+use async_graphql::dataloader::{DataLoader, Loader};
+use async_graphql::futures_util::{stream, Stream, StreamExt};
+use async_graphql::http::{GraphiQLSource, WebSocket, WebSocketProtocols, WsMessage};
+use async_graphql::{ComplexObject, Context, Object, Result, Schema, Subscription};
+use chrono::NaiveDate;
+use deadpool_postgres::{Client, Pool};
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+
+pub type TodoSchema = Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
+
+// GraphiQL is served from unpkg, which the API's own policy doesn't allow.
+const PLAYGROUND_CSP: &str = "default-src 'self'; \
+    script-src 'self' 'unsafe-inline' https://unpkg.com; \
+    style-src 'self' 'unsafe-inline' https://unpkg.com; \
+    img-src 'self' data: https://graphql.org; \
+    connect-src 'self' ws: wss:; \
+    frame-ancestors 'none'";
+
+pub fn schema(pool: Pool, events: ItemEvents) -> TodoSchema {
+    Schema::build(QueryRoot, MutationRoot, SubscriptionRoot)
+        .data(pool)
+        .data(events)
+        .finish()
+}
+
+async fn client(ctx: &Context<'_>) -> Result<Client> {
+    Ok(ctx.data::<Pool>()?.get().await?)
+}
+
+/// Batches `TodoList.items` lookups so a page of lists costs one query
+/// against `todo_item` instead of one per list.
+pub struct ItemsByList {
+    pool: Pool,
+}
+
+impl Loader<i32> for ItemsByList {
+    type Value = Vec<TodoItem>;
+    type Error = Arc<io::Error>;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Self::Value>, Self::Error> {
+        let client = self
+            .pool
+            .get()
+            .await
+            .map_err(|err| Arc::new(io::Error::other(err.to_string())))?;
+        let items = db::get_items_for_lists(&client, keys).await.map_err(Arc::new)?;
+
+        let mut by_list: HashMap<i32, Vec<TodoItem>> =
+            keys.iter().map(|id| (*id, Vec::new())).collect();
+        for item in items {
+            by_list.entry(item.list_id).or_default().push(item);
+        }
+        Ok(by_list)
+    }
+}
+
+async fn list_items(ctx: &Context<'_>, list_id: i32) -> Result<Vec<TodoItem>> {
+    let loader = ctx.data::<DataLoader<ItemsByList>>()?;
+    Ok(loader.load_one(list_id).await?.unwrap_or_default())
+}
+
+#[ComplexObject]
+impl TodoList {
+    /// Items in position order.
+    async fn items(&self, ctx: &Context<'_>) -> Result<Vec<TodoItem>> {
+        list_items(ctx, self.id).await
+    }
+
+    async fn item_count(&self, ctx: &Context<'_>) -> Result<usize> {
+        Ok(list_items(ctx, self.id).await?.len())
+    }
+
+    async fn checked_count(&self, ctx: &Context<'_>) -> Result<usize> {
+        Ok(list_items(ctx, self.id).await?.iter().filter(|item| item.checked).count())
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    async fn lists(&self, ctx: &Context<'_>) -> Result<Vec<TodoList>> {
+        Ok(db::get_todos(&client(ctx).await?).await?)
+    }
+
+    async fn list(&self, ctx: &Context<'_>, id: i32) -> Result<Option<TodoList>> {
+        Ok(db::get_todo(&client(ctx).await?, id).await?)
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    async fn create_item(
+        &self,
+        ctx: &Context<'_>,
+        list_id: i32,
+        title: String,
+        due_date: Option<NaiveDate>,
+        recurrence: Option<String>,
+    ) -> Result<TodoItem> {
+        let item = CreateItem { title, due_date, recurrence };
+        let item = db::create_item(&mut client(ctx).await?, list_id, &item).await?;
+        ctx.data::<ItemEvents>()?.publish(ItemChange::Created, &item);
+        Ok(item)
+    }
+
+    async fn check_item(&self, ctx: &Context<'_>, list_id: i32, id: i32) -> Result<CheckedItem> {
+        let checked = db::check_item(&mut client(ctx).await?, list_id, id).await?;
+        let events = ctx.data::<ItemEvents>()?;
+        events.publish(ItemChange::Checked, &checked.item);
+        if let Some(next) = &checked.next {
+            events.publish(ItemChange::Created, next);
+        }
+        Ok(checked)
+    }
+
+    /// See `POST /todos/{list_id}/items/{id}/move` for how the neighbours work.
+    async fn move_item(
+        &self,
+        ctx: &Context<'_>,
+        list_id: i32,
+        id: i32,
+        before: Option<i32>,
+        after: Option<i32>,
+    ) -> Result<TodoItem> {
+        let neighbours = MoveItem { before, after };
+        let item = db::move_item(&mut client(ctx).await?, list_id, id, &neighbours).await?;
+        ctx.data::<ItemEvents>()?.publish(ItemChange::Moved, &item);
+        Ok(item)
+    }
+}
+
+pub struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    /// Item changes, optionally limited to one list.
+    async fn item_changed(
+        &self,
+        ctx: &Context<'_>,
+        list_id: Option<i32>,
+    ) -> Result<impl Stream<Item = ItemChanged>> {
+        let receiver = ctx.data::<ItemEvents>()?.subscribe();
+
+        Ok(stream::unfold(receiver, move |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) if list_id.is_none_or(|id| id == event.item.list_id) => {
+                        return Some((event, receiver))
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        }))
+    }
+}
+
+pub async fn graphql(
+    schema: web::Data<TodoSchema>,
+    pool: web::Data<Pool>,
+    request: web::Json<async_graphql::Request>,
+) -> HttpResponse {
+    // A fresh loader per request, so its cache never serves stale items.
+    let loader = DataLoader::new(ItemsByList { pool: pool.get_ref().clone() }, actix_web::rt::spawn);
+    let response = schema.execute(request.into_inner().data(loader)).await;
+
+    HttpResponse::Ok().json(response)
+}
+
+pub async fn graphiql() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .insert_header((header::CONTENT_SECURITY_POLICY, PLAYGROUND_CSP))
+        .body(
+            GraphiQLSource::build()
+                .endpoint("/graphql")
+                .subscription_endpoint("/graphql/ws")
+                .finish(),
+        )
+}
+
+/// Subscriptions over WebSocket, speaking `graphql-transport-ws` or the older
+/// `graphql-ws` protocol, whichever the client asks for.
+pub async fn graphql_ws(
+    schema: web::Data<TodoSchema>,
+    req: HttpRequest,
+    body: web::Payload,
+) -> actix_web::Result<HttpResponse> {
+    let protocol = req
+        .headers()
+        .get(header::SEC_WEBSOCKET_PROTOCOL)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|protocols| {
+            protocols
+                .split(',')
+                .find_map(|protocol| protocol.trim().parse::<WebSocketProtocols>().ok())
+        })
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("Unsupported WebSocket subprotocol"))?;
+
+    let (mut response, mut session, messages) = actix_ws::handle(&req, body)?;
+    response.headers_mut().insert(
+        header::SEC_WEBSOCKET_PROTOCOL,
+        HeaderValue::from_static(protocol.sec_websocket_protocol()),
+    );
+
+    // Only text frames carry the GraphQL protocol; a close frame ends it.
+    let messages = messages
+        .aggregate_continuations()
+        .take_while(|message| {
+            let open = !matches!(message, Ok(actix_ws::AggregatedMessage::Close(_)) | Err(_));
+            async move { open }
+        })
+        .filter_map(|message| async move {
+            match message {
+                Ok(actix_ws::AggregatedMessage::Text(text)) => Some(text.into_bytes()),
+                _ => None,
+            }
+        });
+
+    let schema = schema.get_ref().clone();
+    actix_web::rt::spawn(async move {
+        let mut replies = Box::pin(WebSocket::new(schema, messages, protocol));
+
+        while let Some(reply) = replies.next().await {
+            match reply {
+                WsMessage::Text(text) => {
+                    if session.text(text).await.is_err() {
+                        return;
+                    }
+                }
+                WsMessage::Close(code, description) => {
+                    let reason = actix_ws::CloseReason {
+                        code: code.into(),
+                        description: Some(description),
+                    };
+                    let _ = session.close(Some(reason)).await;
+                    return;
+                }
+            }
+        }
+
+        let _ = session.close(None).await;
+    });
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_postgres::NoTls;
+
+    fn item(id: i32, list_id: i32) -> TodoItem {
+        TodoItem {
+            id,
+            list_id,
+            title: format!("Item {}", id),
+            checked: false,
+            position: "V".to_string(),
+            due_date: None,
+            recurrence: None,
+        }
+    }
+
+    #[actix_web::test]
+    async fn item_changes_are_filtered_by_list() {
+        // Never connects: the subscription only reads from the event channel.
+        let mut config = deadpool_postgres::Config::new();
+        config.dbname = Some("todos".to_string());
+        let pool = config.create_pool(NoTls).unwrap();
+        let events = ItemEvents::default();
+        let schema = schema(pool, events.clone());
+
+        let mut changes = schema.execute_stream(
+            "subscription { itemChanged(listId: 2) { change item { id listId } } }",
+        );
+        // Let the subscription start listening before publishing.
+        let first = changes.next();
+        actix_web::rt::spawn(async move {
+            actix_web::rt::task::yield_now().await;
+            events.publish(ItemChange::Created, &item(1, 1));
+            events.publish(ItemChange::Moved, &item(2, 2));
+        });
+
+        let response = first.await.unwrap();
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            async_graphql::value!({ "itemChanged": { "change": "MOVED", "item": { "id": 2, "listId": 2 } } }),
+            response.data
+        );
+    }
+}
@@ -1,13 +1,23 @@
-use crate::models::Status;
+use crate::models::{CreateItem, ItemChange, MoveItem, SearchQuery, Status};
 use crate::db;
+use crate::events::ItemEvents;
 use actix_web::{ web, Responder, HttpResponse};
 use deadpool_postgres::{ Pool, Client};
+use std::io;
 
 // #[get("/")]
 // async fn hello() -> impl Responder {
 //     HttpResponse::Ok().body("Hello world!")
 // }
 
+fn error_response(err: io::Error) -> HttpResponse {
+  match err.kind() {
+    io::ErrorKind::NotFound => HttpResponse::NotFound().body(err.to_string()),
+    io::ErrorKind::InvalidInput => HttpResponse::BadRequest().body(err.to_string()),
+    _ => HttpResponse::InternalServerError().into(),
+  }
+}
+
 pub async fn status() -> impl Responder {
   HttpResponse::Ok().json(Status {status: "Ok".to_string()})
 }
@@ -20,4 +30,82 @@ pub async fn get_todos(db_pool: web::Data<Pool>) -> impl Responder {
     Ok(todos) => HttpResponse::Ok().json(todos),
     Err(_) => HttpResponse::InternalServerError().into()
   }
-}
\ No newline at end of file
+}
+
+pub async fn get_items(db_pool: web::Data<Pool>, path: web::Path<(i32,)>) -> impl Responder {
+  let client: Client = db_pool.get().await.expect("Error connecting to the database");
+  let result = db::get_items(&client, path.0).await;
+
+  match result {
+    Ok(items) => HttpResponse::Ok().json(items),
+    Err(err) => error_response(err)
+  }
+}
+
+pub async fn create_item(
+  db_pool: web::Data<Pool>,
+  events: web::Data<ItemEvents>,
+  path: web::Path<(i32,)>,
+  json: web::Json<CreateItem>,
+) -> impl Responder {
+  let mut client: Client = db_pool.get().await.expect("Error connecting to the database");
+  let result = db::create_item(&mut client, path.0, &json).await;
+
+  match result {
+    Ok(item) => {
+      events.publish(ItemChange::Created, &item);
+      HttpResponse::Created().json(item)
+    }
+    Err(err) => error_response(err)
+  }
+}
+
+pub async fn check_item(
+  db_pool: web::Data<Pool>,
+  events: web::Data<ItemEvents>,
+  path: web::Path<(i32, i32)>,
+) -> impl Responder {
+  let mut client: Client = db_pool.get().await.expect("Error connecting to the database");
+  let (list_id, item_id) = path.into_inner();
+  let result = db::check_item(&mut client, list_id, item_id).await;
+
+  match result {
+    Ok(checked) => {
+      events.publish(ItemChange::Checked, &checked.item);
+      if let Some(next) = &checked.next {
+        events.publish(ItemChange::Created, next);
+      }
+      HttpResponse::Ok().json(checked)
+    }
+    Err(err) => error_response(err)
+  }
+}
+
+pub async fn move_item(
+  db_pool: web::Data<Pool>,
+  events: web::Data<ItemEvents>,
+  path: web::Path<(i32, i32)>,
+  json: web::Json<MoveItem>,
+) -> impl Responder {
+  let mut client: Client = db_pool.get().await.expect("Error connecting to the database");
+  let (list_id, item_id) = path.into_inner();
+  let result = db::move_item(&mut client, list_id, item_id, &json).await;
+
+  match result {
+    Ok(item) => {
+      events.publish(ItemChange::Moved, &item);
+      HttpResponse::Ok().json(item)
+    }
+    Err(err) => error_response(err)
+  }
+}
+
+pub async fn search_todos(db_pool: web::Data<Pool>, query: web::Query<SearchQuery>) -> impl Responder {
+  let client: Client = db_pool.get().await.expect("Error connecting to the database");
+  let result = db::search_todos(&client, &query.q).await;
+
+  match result {
+    Ok(results) => HttpResponse::Ok().json(results),
+    Err(err) => error_response(err)
+  }
+}
@@ -2,13 +2,19 @@ mod config;
 mod models;
 mod handler;
 mod db;
+mod events;
+mod graphql;
+mod middleware;
+mod position;
+mod recurrence;
+mod search;
 use crate::handler::status;
 
-use actix_web::{ web::{self, Data}, App, HttpServer};
+use actix_web::{ middleware::{from_fn, Compress, Condition}, web::{self, Data}, App, HttpServer};
 use std::io;
 use dotenv::dotenv;
 use tokio_postgres::NoTls;
-use handler::get_todos;
+use handler::{check_item, create_item, get_items, get_todos, move_item, search_todos};
 
 #[actix_web::main]
 async fn main() -> io::Result<()> {
@@ -20,14 +26,34 @@ async fn main() -> io::Result<()> {
     let config = crate::config::Config::from_env().unwrap();
 
     let pool = config.pg.create_pool(NoTls).unwrap();
+    let cors = config.cors.clone();
+    let security = config.security.clone();
+    let compression = config.compression.clone();
+    let events = events::ItemEvents::default();
+    let schema = graphql::schema(pool.clone(), events.clone());
 
     println!("Starting server at http://{}:{}", config.server.host, config.server.port);
 
     HttpServer::new(move || {
         App::new()
             .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(security.clone()))
+            .app_data(Data::new(events.clone()))
+            .app_data(Data::new(schema.clone()))
+            .wrap(Condition::new(compression.enabled, Compress::default()))
+            .wrap(Condition::new(security.headers, from_fn(middleware::content_security_policy)))
+            .wrap(Condition::new(security.headers, middleware::security_headers(&security)))
+            .wrap(Condition::new(cors.enabled, middleware::cors(&cors)))
             .route("/", web::get().to(status))  // .service(hello)
             .route("/todos{_:/?}",web::get().to(get_todos))
+            .route("/todos/{list_id}/items", web::get().to(get_items))
+            .route("/todos/{list_id}/items", web::post().to(create_item))
+            .route("/todos/{list_id}/items/{id}/check", web::post().to(check_item))
+            .route("/todos/{list_id}/items/{id}/move", web::post().to(move_item))
+            .route("/search", web::get().to(search_todos))
+            .route("/graphql", web::post().to(graphql::graphql))
+            .route("/graphql", web::get().to(graphql::graphiql))
+            .route("/graphql/ws", web::get().to(graphql::graphql_ws))
     })
     .bind(format!("{}:{}", config.server.host, config.server.port))?
     .run()
@@ -0,0 +1,141 @@
+use crate::config::{CorsConfig, SecurityConfig};
+use actix_cors::Cors;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::middleware::{DefaultHeaders, Next};
+use actix_web::{web, Error};
+
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',').map(str::trim).filter(|value| !value.is_empty())
+}
+
+pub fn cors(config: &CorsConfig) -> Cors {
+    let mut cors = Cors::default()
+        .allowed_methods(split_list(&config.allowed_methods))
+        .allowed_headers(vec![header::ACCEPT, header::AUTHORIZATION, header::CONTENT_TYPE])
+        .max_age(config.max_age);
+
+    for origin in split_list(&config.allowed_origins) {
+        cors = if origin == "*" {
+            cors.allow_any_origin()
+        } else {
+            cors.allowed_origin(origin)
+        };
+    }
+
+    if config.allow_credentials {
+        cors = cors.supports_credentials();
+    }
+
+    cors
+}
+
+pub fn security_headers(config: &SecurityConfig) -> DefaultHeaders {
+    let headers = DefaultHeaders::new()
+        .add((header::X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .add((header::X_FRAME_OPTIONS, "DENY"))
+        .add((header::REFERRER_POLICY, "no-referrer"));
+
+    if config.hsts {
+        headers.add((
+            header::STRICT_TRANSPORT_SECURITY,
+            format!("max-age={}; includeSubDomains", config.hsts_max_age),
+        ))
+    } else {
+        headers
+    }
+}
+
+/// Adds the configured Content-Security-Policy to HTML responses. JSON
+/// responses are left alone since the policy means nothing to them.
+pub async fn content_security_policy(
+    config: web::Data<SecurityConfig>,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let mut res = next.call(req).await?;
+
+    let is_html = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+
+    if is_html && !res.headers().contains_key(header::CONTENT_SECURITY_POLICY) {
+        if let Ok(policy) = HeaderValue::from_str(&config.content_security_policy) {
+            res.headers_mut().insert(header::CONTENT_SECURITY_POLICY, policy);
+        }
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::{test, App, HttpResponse};
+
+    #[actix_web::test]
+    async fn policy_only_on_html() {
+        let config = SecurityConfig::default();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(config.clone()))
+                .wrap(from_fn(content_security_policy))
+                .wrap(security_headers(&config))
+                .route("/page", web::get().to(|| async {
+                    HttpResponse::Ok().content_type("text/html; charset=utf-8").body("<p>hi</p>")
+                }))
+                .route("/json", web::get().to(|| async { HttpResponse::Ok().json(1) })),
+        )
+        .await;
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/page").to_request()).await;
+        assert_eq!(
+            config.content_security_policy.as_str(),
+            res.headers().get(header::CONTENT_SECURITY_POLICY).unwrap()
+        );
+        assert_eq!("nosniff", res.headers().get(header::X_CONTENT_TYPE_OPTIONS).unwrap());
+        assert!(!res.headers().contains_key(header::STRICT_TRANSPORT_SECURITY));
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/json").to_request()).await;
+        assert!(!res.headers().contains_key(header::CONTENT_SECURITY_POLICY));
+        assert_eq!("nosniff", res.headers().get(header::X_CONTENT_TYPE_OPTIONS).unwrap());
+    }
+
+    #[actix_web::test]
+    async fn cors_allows_configured_origin() {
+        let config = CorsConfig {
+            enabled: true,
+            allowed_origins: "https://app.example.com, http://localhost:3000".to_string(),
+            allow_credentials: true,
+            ..CorsConfig::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .wrap(cors(&config))
+                .route("/todos", web::get().to(|| async { HttpResponse::Ok().json(1) })),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/todos")
+            .insert_header((header::ORIGIN, "https://app.example.com"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(
+            "https://app.example.com",
+            res.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap()
+        );
+        assert_eq!("true", res.headers().get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS).unwrap());
+
+        let req = test::TestRequest::get()
+            .uri("/todos")
+            .insert_header((header::ORIGIN, "https://evil.example.com"))
+            .to_request();
+        let res = test::try_call_service(&app, req).await;
+        assert!(res.map_or(true, |res| !res.headers().contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)));
+    }
+}
@@ -1,3 +1,5 @@
+use async_graphql::{Enum, SimpleObject};
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use tokio_pg_mapper_derive::PostgresMapper;
 
@@ -6,18 +8,81 @@ pub struct Status {
   pub status : String
 }
 
-#[derive(Serialize, Deserialize, PostgresMapper)]
+#[derive(Clone, Serialize, Deserialize, PostgresMapper, SimpleObject)]
 #[pg_mapper(table = "todo_item")]
 pub struct TodoItem {
     pub id: i32,
     pub list_id: i32,
     pub title: String,
     pub checked: bool,
+    pub position: String,
+    pub due_date: Option<NaiveDate>,
+    pub recurrence: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, PostgresMapper)]
+/// Body of `POST /todos/{list_id}/items`. New items go to the end of the list.
+#[derive(Deserialize)]
+pub struct CreateItem {
+    pub title: String,
+    pub due_date: Option<NaiveDate>,
+    pub recurrence: Option<String>,
+}
+
+/// Response of checking off an item. `next` is the following occurrence of a
+/// recurring item, created right below it.
+#[derive(Serialize, SimpleObject)]
+pub struct CheckedItem {
+    pub item: TodoItem,
+    pub next: Option<TodoItem>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Enum)]
+pub enum ItemChange {
+    Created,
+    Checked,
+    Moved,
+}
+
+/// Sent to `itemChanged` subscribers whenever an item is created, checked or
+/// moved, through REST or GraphQL.
+#[derive(Clone, SimpleObject)]
+pub struct ItemChanged {
+    pub change: ItemChange,
+    pub item: TodoItem,
+}
+
+#[derive(Debug, Serialize, Deserialize, PostgresMapper, SimpleObject)]
 #[pg_mapper(table = "todo_list")]
+#[graphql(complex)]
 pub struct TodoList {
     pub id: i32,
     pub title: String,
 }
+
+/// Neighbours for `POST /todos/{list_id}/items/{id}/move`.
+///
+/// `before` is the item that should end up just above the moved item and
+/// `after` the one just below it. Either may be left out, in which case the
+/// other side is looked up from the current order.
+#[derive(Deserialize)]
+pub struct MoveItem {
+    pub before: Option<i32>,
+    pub after: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+}
+
+/// A list or item matching a search. `snippet` is the title as HTML, escaped,
+/// with the matching parts wrapped in `<mark>` tags.
+#[derive(Debug, Serialize)]
+pub struct SearchResult {
+    pub kind: String,
+    pub id: i32,
+    pub list_id: i32,
+    pub title: String,
+    pub snippet: String,
+    pub rank: f32,
+}
@@ -0,0 +1,131 @@
+// Lexicographic position keys for ordering todo items.
+//
+// A key is a base-62 fraction written without the leading "0.", so "V" sits
+// halfway between the empty start and the end of the range. Digits are in
+// ASCII order, which means comparing keys as plain strings (with the "C"
+// collation in Postgres) gives the item order. A new key can always be found
+// between two neighbours, so moving an item only rewrites that one row.
+
+const DIGITS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const BASE: usize = DIGITS.len();
+
+fn digit_value(digit: u8) -> Option<usize> {
+    DIGITS.iter().position(|&d| d == digit)
+}
+
+/// Checks that a key only uses the position alphabet and has no trailing zero,
+/// which would make two different strings describe the same place.
+pub fn is_valid(key: &str) -> bool {
+    !key.is_empty() && !key.ends_with('0') && key.bytes().all(|b| digit_value(b).is_some())
+}
+
+/// Returns a key that sorts strictly between `before` and `after`.
+///
+/// `None` stands for the start or the end of the list. Both keys must be valid
+/// and `before` must sort before `after`.
+pub fn key_between(before: Option<&str>, after: Option<&str>) -> Result<String, String> {
+    for key in [before, after].into_iter().flatten() {
+        if !is_valid(key) {
+            return Err(format!("invalid position key '{}'", key));
+        }
+    }
+    if let (Some(a), Some(b)) = (before, after) {
+        if a >= b {
+            return Err(format!("position '{}' is not before '{}'", a, b));
+        }
+    }
+
+    Ok(midpoint(before.unwrap_or("").as_bytes(), after.map(str::as_bytes)))
+}
+
+// `a` is a fraction (the empty string is zero) and `b` a larger fraction, or
+// `None` for one. Neither has trailing zeros.
+fn midpoint(a: &[u8], b: Option<&[u8]>) -> String {
+    if let Some(b) = b {
+        // Keep the shared prefix, treating `a` as padded with zeros.
+        let n = b
+            .iter()
+            .enumerate()
+            .take_while(|(i, &digit)| a.get(*i).copied().unwrap_or(DIGITS[0]) == digit)
+            .count();
+        if n > 0 {
+            let rest = midpoint(a.get(n..).unwrap_or(&[]), Some(&b[n..]));
+            return String::from_utf8_lossy(&b[..n]).into_owned() + &rest;
+        }
+    }
+
+    let digit_a = a.first().map_or(0, |&d| digit_value(d).unwrap());
+    let digit_b = b.map_or(BASE, |b| digit_value(b[0]).unwrap());
+
+    if digit_b - digit_a > 1 {
+        let mid = (digit_a + digit_b) / 2;
+        return (DIGITS[mid] as char).to_string();
+    }
+
+    match b {
+        // `b` continues after its first digit, so that digit alone is enough.
+        Some(b) if b.len() > 1 => (b[0] as char).to_string(),
+        // The first digits are adjacent: keep `a`'s and split the remainder.
+        _ => {
+            let rest = midpoint(a.get(1..).unwrap_or(&[]), None);
+            (DIGITS[digit_a] as char).to_string() + &rest
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_key_is_in_the_middle() {
+        assert_eq!("V", key_between(None, None).unwrap());
+    }
+
+    #[test]
+    fn appending_and_prepending() {
+        assert_eq!("k", key_between(Some("V"), None).unwrap());
+        assert_eq!("F", key_between(None, Some("V")).unwrap());
+    }
+
+    #[test]
+    fn adjacent_digits_grow_the_key() {
+        let key = key_between(Some("a"), Some("b")).unwrap();
+        assert_eq!("aV", key);
+        assert!("a" < key.as_str() && key.as_str() < "b");
+    }
+
+    #[test]
+    fn shared_prefix_is_kept() {
+        let key = key_between(Some("aV"), Some("aW")).unwrap();
+        assert!("aV" < key.as_str() && key.as_str() < "aW");
+        assert!(key.starts_with("aV"));
+    }
+
+    #[test]
+    fn repeated_inserts_stay_ordered() {
+        let mut low = key_between(None, None).unwrap();
+        let high = key_between(Some(&low), None).unwrap();
+        for _ in 0..50 {
+            let mid = key_between(Some(&low), Some(&high)).unwrap();
+            assert!(low < mid && mid < high);
+            assert!(is_valid(&mid));
+            low = mid;
+        }
+        let mut high = high;
+        for _ in 0..50 {
+            let mid = key_between(None, Some(&high)).unwrap();
+            assert!(mid < high);
+            assert!(is_valid(&mid));
+            high = mid;
+        }
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(key_between(Some("b"), Some("a")).is_err());
+        assert!(key_between(Some("a"), Some("a")).is_err());
+        assert!(key_between(Some("a0"), None).is_err());
+        assert!(key_between(None, Some("a-b")).is_err());
+    }
+}
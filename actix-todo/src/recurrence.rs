@@ -0,0 +1,343 @@
+// RRULE-style recurrence for todo items.
+//
+// Only the part of RFC 5545 that chores need is supported:
+//
+//     FREQ=DAILY|WEEKLY|MONTHLY[;INTERVAL=n][;BYDAY=MO,TU,...]
+//
+// Weeks start on Monday. Monthly rules keep the day of the month and skip
+// months that don't have it (a rule due on the 31st is next due on the next
+// 31st), like RFC 5545 does, instead of drifting to the 28th.
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    pub interval: u32,
+    /// Weekdays the item falls on, kept sorted from Monday. Empty means the
+    /// weekday of the current due date is kept.
+    pub weekdays: Vec<Weekday>,
+}
+
+/// The largest INTERVAL accepted. Larger ones are surely typos, and would
+/// step past the dates chrono can represent.
+pub const MAX_INTERVAL: u32 = 1000;
+
+const WEEKDAY_CODES: [(&str, Weekday); 7] = [
+    ("MO", Weekday::Mon),
+    ("TU", Weekday::Tue),
+    ("WE", Weekday::Wed),
+    ("TH", Weekday::Thu),
+    ("FR", Weekday::Fri),
+    ("SA", Weekday::Sat),
+    ("SU", Weekday::Sun),
+];
+
+impl FromStr for Recurrence {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let rule = rule.trim();
+        let rule = rule.strip_prefix("RRULE:").unwrap_or(rule);
+
+        let mut frequency = None;
+        let mut interval = 1;
+        let mut weekdays = Vec::new();
+
+        for part in rule.split(';').filter(|part| !part.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", part))?;
+
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        _ => return Err(format!("unsupported FREQ '{}'", value)),
+                    })
+                }
+                "INTERVAL" => {
+                    interval = value
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|interval| (1..=MAX_INTERVAL).contains(interval))
+                        .ok_or_else(|| {
+                            format!("INTERVAL must be a number from 1 to {}, got '{}'", MAX_INTERVAL, value)
+                        })?
+                }
+                "BYDAY" => {
+                    for code in value.split(',') {
+                        let weekday = WEEKDAY_CODES
+                            .iter()
+                            .find(|(name, _)| name.eq_ignore_ascii_case(code))
+                            .map(|(_, weekday)| *weekday)
+                            .ok_or_else(|| format!("unknown weekday '{}'", code))?;
+                        if !weekdays.contains(&weekday) {
+                            weekdays.push(weekday);
+                        }
+                    }
+                }
+                _ => return Err(format!("unsupported rule part '{}'", key)),
+            }
+        }
+
+        weekdays.sort_by_key(|weekday| weekday.num_days_from_monday());
+
+        Ok(Recurrence {
+            frequency: frequency.ok_or("FREQ is required")?,
+            interval,
+            weekdays,
+        })
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let frequency = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+        };
+        write!(f, "FREQ={}", frequency)?;
+
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+
+        if !self.weekdays.is_empty() {
+            let codes: Vec<&str> = self
+                .weekdays
+                .iter()
+                .map(|weekday| WEEKDAY_CODES[weekday.num_days_from_monday() as usize].0)
+                .collect();
+            write!(f, ";BYDAY={}", codes.join(","))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn add_months(date: NaiveDate, months: u32, day: u32) -> Option<NaiveDate> {
+    let month0 = date.month0().checked_add(months)?;
+    let year = date.year().checked_add((month0 / 12) as i32)?;
+    NaiveDate::from_ymd_opt(year, month0 % 12 + 1, day)
+}
+
+fn add_days(date: NaiveDate, days: i64) -> Option<NaiveDate> {
+    date.checked_add_signed(Duration::try_days(days)?)
+}
+
+fn first_of_month(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap()
+}
+
+// `None` for the first days chrono knows, whose Monday is before them.
+fn week_start(date: NaiveDate) -> Option<NaiveDate> {
+    date.checked_sub_signed(Duration::try_days(date.weekday().num_days_from_monday() as i64)?)
+}
+
+impl Recurrence {
+    fn on_weekday(&self, date: NaiveDate) -> bool {
+        self.weekdays.is_empty() || self.weekdays.contains(&date.weekday())
+    }
+
+    /// Returns the first occurrence strictly after `due`, treating `due` as an
+    /// occurrence of the rule. Returns `None` when the rule can never fire
+    /// again, e.g. every 7 days but only on Tuesdays starting from a Monday,
+    /// or when the next occurrence is past the last date chrono knows.
+    pub fn next_after(&self, due: NaiveDate) -> Option<NaiveDate> {
+        let interval = self.interval as i64;
+        match self.frequency {
+            Frequency::Daily => {
+                // The weekday repeats after at most seven steps.
+                (1..=7)
+                    .map_while(|n| add_days(due, interval * n))
+                    .find(|date| self.on_weekday(*date))
+            }
+            Frequency::Weekly if self.weekdays.is_empty() => add_days(due, interval * 7),
+            Frequency::Weekly => {
+                let later_this_week = (1..7)
+                    .map_while(|n| add_days(due, n))
+                    .take_while(|date| week_start(*date) == week_start(due))
+                    .find(|date| self.on_weekday(*date));
+
+                later_this_week.or_else(|| {
+                    // Counted from the next Monday, which exists even when
+                    // the week's start doesn't.
+                    let next_monday = 7 - due.weekday().num_days_from_monday() as i64;
+                    let week = add_days(due, next_monday + (interval - 1) * 7)?;
+                    (0..7)
+                        .map_while(|n| add_days(week, n))
+                        .find(|date| self.on_weekday(*date))
+                })
+            }
+            Frequency::Monthly if self.weekdays.is_empty() => {
+                // A day that exists in some month comes back within four years.
+                (1..=48)
+                    .filter_map(|n| add_months(due, self.interval.checked_mul(n)?, due.day()))
+                    .next()
+            }
+            Frequency::Monthly => {
+                let later_this_month = (1..31)
+                    .map_while(|n| add_days(due, n))
+                    .take_while(|date| date.month() == due.month())
+                    .find(|date| self.on_weekday(*date));
+
+                later_this_month.or_else(|| {
+                    let month = add_months(first_of_month(due), self.interval, 1)?;
+                    (0..7)
+                        .map_while(|n| add_days(month, n))
+                        .find(|date| self.on_weekday(*date))
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn rule(rule: &str) -> Recurrence {
+        rule.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_and_formats_rules() {
+        let parsed = rule("RRULE:freq=weekly;interval=2;byday=fr,MO,FR");
+        assert_eq!(Frequency::Weekly, parsed.frequency);
+        assert_eq!(2, parsed.interval);
+        assert_eq!(vec![Weekday::Mon, Weekday::Fri], parsed.weekdays);
+        assert_eq!("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR", parsed.to_string());
+        assert_eq!("FREQ=DAILY", rule("FREQ=DAILY;INTERVAL=1").to_string());
+    }
+
+    #[test]
+    fn rejects_bad_rules() {
+        assert!("INTERVAL=2".parse::<Recurrence>().is_err());
+        assert!("FREQ=YEARLY".parse::<Recurrence>().is_err());
+        assert!("FREQ=DAILY;INTERVAL=0".parse::<Recurrence>().is_err());
+        assert!("FREQ=WEEKLY;INTERVAL=4294967295".parse::<Recurrence>().is_err());
+        assert!("FREQ=WEEKLY;INTERVAL=1001".parse::<Recurrence>().is_err());
+        assert!("FREQ=DAILY;BYDAY=XX".parse::<Recurrence>().is_err());
+        assert!("FREQ=DAILY;COUNT=3".parse::<Recurrence>().is_err());
+        assert!("FREQ".parse::<Recurrence>().is_err());
+    }
+
+    #[test]
+    fn daily() {
+        assert_eq!(Some(date(2024, 3, 1)), rule("FREQ=DAILY").next_after(date(2024, 2, 29)));
+        assert_eq!(
+            Some(date(2025, 1, 2)),
+            rule("FREQ=DAILY;INTERVAL=3").next_after(date(2024, 12, 30))
+        );
+    }
+
+    #[test]
+    fn daily_on_weekdays_skips_the_weekend() {
+        let workdays = rule("FREQ=DAILY;BYDAY=MO,TU,WE,TH,FR");
+        // Friday 2024-04-12 -> Monday 2024-04-15
+        assert_eq!(Some(date(2024, 4, 15)), workdays.next_after(date(2024, 4, 12)));
+        assert_eq!(Some(date(2024, 4, 11)), workdays.next_after(date(2024, 4, 10)));
+    }
+
+    #[test]
+    fn daily_rule_that_never_fires_again() {
+        // Every 7 days from a Monday only ever lands on Mondays.
+        let never = rule("FREQ=DAILY;INTERVAL=7;BYDAY=TU");
+        assert_eq!(None, never.next_after(date(2024, 4, 8)));
+    }
+
+    #[test]
+    fn weekly_keeps_the_weekday() {
+        assert_eq!(Some(date(2024, 4, 15)), rule("FREQ=WEEKLY").next_after(date(2024, 4, 8)));
+        assert_eq!(
+            Some(date(2024, 4, 22)),
+            rule("FREQ=WEEKLY;INTERVAL=2").next_after(date(2024, 4, 8))
+        );
+    }
+
+    #[test]
+    fn weekly_walks_the_weekday_set() {
+        let mon_wed = rule("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE");
+        // Monday -> Wednesday of the same week
+        assert_eq!(Some(date(2024, 4, 10)), mon_wed.next_after(date(2024, 4, 8)));
+        // Wednesday -> Monday two weeks after the week's start
+        assert_eq!(Some(date(2024, 4, 22)), mon_wed.next_after(date(2024, 4, 10)));
+        // Sunday ends the week
+        assert_eq!(Some(date(2024, 4, 22)), mon_wed.next_after(date(2024, 4, 14)));
+    }
+
+    #[test]
+    fn weekly_across_the_year_end() {
+        let friday = rule("FREQ=WEEKLY;BYDAY=FR");
+        assert_eq!(Some(date(2025, 1, 3)), friday.next_after(date(2024, 12, 27)));
+    }
+
+    #[test]
+    fn monthly_keeps_the_day_of_month() {
+        assert_eq!(Some(date(2024, 5, 15)), rule("FREQ=MONTHLY").next_after(date(2024, 4, 15)));
+        assert_eq!(
+            Some(date(2025, 2, 15)),
+            rule("FREQ=MONTHLY;INTERVAL=3").next_after(date(2024, 11, 15))
+        );
+    }
+
+    #[test]
+    fn monthly_skips_months_without_the_day() {
+        let monthly = rule("FREQ=MONTHLY");
+        assert_eq!(Some(date(2024, 3, 31)), monthly.next_after(date(2024, 1, 31)));
+        assert_eq!(Some(date(2024, 5, 31)), monthly.next_after(date(2024, 3, 31)));
+        // Leap day comes back four years later on a yearly-ish interval.
+        let yearly = rule("FREQ=MONTHLY;INTERVAL=12");
+        assert_eq!(Some(date(2028, 2, 29)), yearly.next_after(date(2024, 2, 29)));
+    }
+
+    #[test]
+    fn huge_intervals_run_out_of_dates_instead_of_panicking() {
+        let last_monday = date(2024, 4, 29);
+        let near_the_end = NaiveDate::MAX - Duration::days(3);
+        for frequency in ["DAILY", "WEEKLY", "MONTHLY"] {
+            for byday in ["", ";BYDAY=MO"] {
+                let huge = Recurrence {
+                    interval: u32::MAX,
+                    ..rule(&format!("FREQ={}{}", frequency, byday))
+                };
+                assert_eq!(None, huge.next_after(last_monday), "{}", huge);
+                rule(&format!("FREQ={};INTERVAL={}{}", frequency, MAX_INTERVAL, byday)).next_after(near_the_end);
+            }
+        }
+    }
+
+    #[test]
+    fn weekly_from_the_first_week_chrono_knows() {
+        let every_day = rule("FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR,SA,SU");
+        for n in 0..7 {
+            let due = NaiveDate::MIN + Duration::days(n);
+            assert_eq!(Some(due + Duration::days(1)), every_day.next_after(due));
+        }
+    }
+
+    #[test]
+    fn monthly_on_weekdays() {
+        let mondays = rule("FREQ=MONTHLY;INTERVAL=2;BYDAY=MO");
+        assert_eq!(Some(date(2024, 4, 15)), mondays.next_after(date(2024, 4, 8)));
+        // Last Monday of April -> first Monday of June
+        assert_eq!(Some(date(2024, 6, 3)), mondays.next_after(date(2024, 4, 29)));
+    }
+}
@@ -0,0 +1,101 @@
+// Search across todo lists and items.
+//
+// Postgres does the real work through the `search` tsvector columns (see
+// `db::search_todos`). This module builds the prefix query handed to
+// `to_tsquery`, the `ILIKE` patterns used instead when every term is a stop
+// word ("to do" has no lexemes left), and the HTML snippets.
+
+pub const HIGHLIGHT_START: &str = "<mark>";
+pub const HIGHLIGHT_STOP: &str = "</mark>";
+
+// ts_headline marks matches with these instead of the HTML tags, so the
+// title can be escaped before the tags go in.
+const START_SEL: char = '\u{1}';
+const STOP_SEL: char = '\u{2}';
+
+/// The `ts_headline` options matching [`snippet_html`].
+pub fn headline_options() -> String {
+    format!("StartSel={}, StopSel={}, HighlightAll=true", START_SEL, STOP_SEL)
+}
+
+/// Splits the user's input into search terms, dropping anything that isn't a
+/// letter or digit so it can't be read as tsquery syntax.
+pub fn terms(query: &str) -> Vec<String> {
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Builds a `to_tsquery` expression where every term has to match, each as a
+/// prefix, so "conn data" finds "Connect to database".
+pub fn prefix_tsquery(query: &str) -> Option<String> {
+    let terms = terms(query);
+    if terms.is_empty() {
+        return None;
+    }
+
+    Some(
+        terms
+            .iter()
+            .map(|term| format!("{}:*", term))
+            .collect::<Vec<_>>()
+            .join(" & "),
+    )
+}
+
+/// `ILIKE` patterns matching titles that contain every term. Terms are only
+/// letters and digits, so nothing in them needs escaping.
+pub fn like_patterns(query: &str) -> Vec<String> {
+    terms(query).iter().map(|term| format!("%{}%", term)).collect()
+}
+
+/// Turns a `ts_headline` of a title into HTML: the title escaped, with the
+/// matches in `<mark>` tags.
+pub fn snippet_html(headline: &str) -> String {
+    let mut html = String::with_capacity(headline.len());
+    for c in headline.chars() {
+        match c {
+            START_SEL => html.push_str(HIGHLIGHT_START),
+            STOP_SEL => html.push_str(HIGHLIGHT_STOP),
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_prefix_query() {
+        assert_eq!(Some("conn:* & data:*".to_string()), prefix_tsquery("Conn data"));
+        assert_eq!(Some("it:* & s:*".to_string()), prefix_tsquery("it's"));
+        assert_eq!(None, prefix_tsquery(" & | !"));
+    }
+
+    #[test]
+    fn builds_like_patterns() {
+        assert_eq!(vec!["%to%", "%do%"], like_patterns("To do!"));
+        assert_eq!(vec!["%100%"], like_patterns("100%_"));
+    }
+
+    #[test]
+    fn escapes_snippets_around_the_marks() {
+        assert_eq!(
+            "<mark>Conn</mark>ect to <mark>data</mark>base",
+            snippet_html("\u{1}Conn\u{2}ect to \u{1}data\u{2}base")
+        );
+        assert_eq!(
+            "&lt;img src=x onerror=&quot;alert(1)&quot;&gt; &amp; <mark>milk</mark>",
+            snippet_html("<img src=x onerror=\"alert(1)\"> & \u{1}milk\u{2}")
+        );
+    }
+}
@@ -0,0 +1,5 @@
+fn main() {
+    // The migrations are embedded in the library, which has to be rebuilt
+    // when they change.
+    println!("cargo:rerun-if-changed=migrations");
+}
@@ -0,0 +1,13 @@
+DROP TABLE posts_tags;
+DROP TABLE tags;
+
+DROP TRIGGER IF EXISTS set_updated_at ON posts;
+
+ALTER TABLE posts
+  DROP COLUMN slug,
+  DROP COLUMN author_id,
+  DROP COLUMN created_at,
+  DROP COLUMN updated_at,
+  DROP COLUMN published_at;
+
+DROP TABLE authors;
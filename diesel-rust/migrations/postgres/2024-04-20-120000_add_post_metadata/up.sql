@@ -0,0 +1,37 @@
+CREATE TABLE authors (
+  id SERIAL PRIMARY KEY,
+  name VARCHAR NOT NULL,
+  email VARCHAR NOT NULL UNIQUE,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE posts
+  ADD COLUMN slug VARCHAR,
+  ADD COLUMN author_id INTEGER REFERENCES authors (id) ON DELETE SET NULL,
+  ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  ADD COLUMN updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  ADD COLUMN published_at TIMESTAMP;
+
+-- Existing posts get a slug from their title, made unique with their id
+UPDATE posts
+  SET slug = trim(both '-' from regexp_replace(lower(title), '[^a-z0-9]+', '-', 'g')) || '-' || id;
+UPDATE posts SET published_at = created_at WHERE published;
+
+ALTER TABLE posts ALTER COLUMN slug SET NOT NULL;
+ALTER TABLE posts ADD CONSTRAINT posts_slug_key UNIQUE (slug);
+CREATE INDEX posts_author_id_idx ON posts (author_id);
+
+SELECT diesel_manage_updated_at('posts');
+
+CREATE TABLE tags (
+  id SERIAL PRIMARY KEY,
+  name VARCHAR NOT NULL UNIQUE
+);
+
+CREATE TABLE posts_tags (
+  post_id INTEGER NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+  tag_id INTEGER NOT NULL REFERENCES tags (id) ON DELETE CASCADE,
+  PRIMARY KEY (post_id, tag_id)
+);
+
+CREATE INDEX posts_tags_tag_id_idx ON posts_tags (tag_id);
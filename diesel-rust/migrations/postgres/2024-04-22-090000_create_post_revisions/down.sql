@@ -0,0 +1,3 @@
+DROP TRIGGER IF EXISTS record_post_revision ON posts;
+DROP FUNCTION IF EXISTS record_post_revision();
+DROP TABLE post_revisions;
@@ -0,0 +1,34 @@
+-- Every version of a post's title and body, numbered from 1 per post
+CREATE TABLE post_revisions (
+  id SERIAL PRIMARY KEY,
+  post_id INTEGER NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+  revision INTEGER NOT NULL,
+  title VARCHAR NOT NULL,
+  body TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  UNIQUE (post_id, revision)
+);
+
+-- Records the new title and body whenever a post is written or either changes
+CREATE OR REPLACE FUNCTION record_post_revision() RETURNS trigger AS $$
+BEGIN
+    IF (
+        TG_OP = 'INSERT' OR
+        NEW.title IS DISTINCT FROM OLD.title OR
+        NEW.body IS DISTINCT FROM OLD.body
+    ) THEN
+        INSERT INTO post_revisions (post_id, revision, title, body)
+        SELECT NEW.id, COALESCE(MAX(revision), 0) + 1, NEW.title, NEW.body
+        FROM post_revisions
+        WHERE post_id = NEW.id;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER record_post_revision AFTER INSERT OR UPDATE ON posts
+  FOR EACH ROW EXECUTE PROCEDURE record_post_revision();
+
+-- Existing posts start their history at what they are now
+INSERT INTO post_revisions (post_id, revision, title, body, created_at)
+  SELECT id, 1, title, body, updated_at FROM posts;
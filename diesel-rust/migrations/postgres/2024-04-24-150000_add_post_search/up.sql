@@ -0,0 +1,7 @@
+-- Title words weigh more than body words when ranking matches
+ALTER TABLE posts ADD COLUMN search TSVECTOR NOT NULL GENERATED ALWAYS AS (
+  setweight(to_tsvector('english', title), 'A') ||
+  setweight(to_tsvector('english', body), 'B')
+) STORED;
+
+CREATE INDEX posts_search_idx ON posts USING GIN (search);
@@ -0,0 +1,11 @@
+DROP INDEX posts_scheduled_idx;
+
+ALTER TABLE posts ADD COLUMN published BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE posts SET published = (status = 'published');
+
+ALTER TABLE posts
+  DROP COLUMN status,
+  DROP COLUMN publish_at;
+
+DROP TYPE post_status;
@@ -0,0 +1,12 @@
+CREATE TYPE post_status AS ENUM ('draft', 'scheduled', 'published', 'archived');
+
+ALTER TABLE posts
+  ADD COLUMN status post_status NOT NULL DEFAULT 'draft',
+  ADD COLUMN publish_at TIMESTAMP;
+
+UPDATE posts SET status = 'published' WHERE published;
+
+ALTER TABLE posts DROP COLUMN published;
+
+-- The scheduler looks for scheduled posts that are due
+CREATE INDEX posts_scheduled_idx ON posts (publish_at) WHERE status = 'scheduled';
@@ -0,0 +1,16 @@
+-- Readers' comments; replies point at the comment they answer
+CREATE TABLE comments (
+  id SERIAL PRIMARY KEY,
+  post_id INTEGER NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+  parent_id INTEGER REFERENCES comments (id) ON DELETE CASCADE,
+  author_name VARCHAR NOT NULL,
+  author_email VARCHAR NOT NULL,
+  body TEXT NOT NULL,
+  approved BOOLEAN NOT NULL DEFAULT FALSE,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX comments_post_id_idx ON comments (post_id);
+CREATE INDEX comments_parent_id_idx ON comments (parent_id);
+-- The moderation queue
+CREATE INDEX comments_pending_idx ON comments (created_at) WHERE NOT approved;
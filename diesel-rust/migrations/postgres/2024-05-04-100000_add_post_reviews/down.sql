@@ -0,0 +1,5 @@
+DROP TABLE post_reviews;
+
+ALTER TABLE posts DROP COLUMN review;
+
+DROP TYPE review_state;
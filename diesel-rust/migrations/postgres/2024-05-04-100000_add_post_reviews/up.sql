@@ -0,0 +1,18 @@
+-- Drafts are reviewed before they are published. Posts that are already out
+-- went out before reviews existed and count as approved.
+CREATE TYPE review_state AS ENUM ('unreviewed', 'pending', 'approved');
+
+ALTER TABLE posts ADD COLUMN review review_state NOT NULL DEFAULT 'unreviewed';
+
+UPDATE posts SET review = 'approved' WHERE status <> 'draft';
+
+-- Every approval or rejection of a post, and who gave it
+CREATE TABLE post_reviews (
+  id SERIAL PRIMARY KEY,
+  post_id INTEGER NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+  reviewer_id INTEGER NOT NULL REFERENCES authors (id),
+  approved BOOLEAN NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX post_reviews_post_id_idx ON post_reviews (post_id);
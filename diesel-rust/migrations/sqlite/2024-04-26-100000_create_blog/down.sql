@@ -0,0 +1,6 @@
+DROP TABLE posts_fts;
+DROP TABLE post_revisions;
+DROP TABLE posts_tags;
+DROP TABLE tags;
+DROP TABLE posts;
+DROP TABLE authors;
@@ -0,0 +1,104 @@
+-- The whole Postgres schema in one go, since SQLite databases start fresh.
+-- Timestamps are UTC text, which is what Diesel reads for `Timestamp`.
+
+CREATE TABLE authors (
+  id INTEGER PRIMARY KEY NOT NULL,
+  name VARCHAR NOT NULL,
+  email VARCHAR NOT NULL UNIQUE,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE posts (
+  id INTEGER PRIMARY KEY NOT NULL,
+  title VARCHAR NOT NULL,
+  body TEXT NOT NULL,
+  published BOOLEAN NOT NULL DEFAULT 0,
+  slug VARCHAR NOT NULL UNIQUE,
+  author_id INTEGER REFERENCES authors (id) ON DELETE SET NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  published_at TIMESTAMP
+);
+
+CREATE INDEX posts_author_id_idx ON posts (author_id);
+
+-- Like diesel_manage_updated_at: bump updated_at when a row changes, unless
+-- the update set it itself
+CREATE TRIGGER posts_set_updated_at AFTER UPDATE ON posts
+  FOR EACH ROW
+  WHEN NEW.updated_at IS OLD.updated_at AND (
+    NEW.title IS NOT OLD.title OR
+    NEW.body IS NOT OLD.body OR
+    NEW.published IS NOT OLD.published OR
+    NEW.slug IS NOT OLD.slug OR
+    NEW.author_id IS NOT OLD.author_id OR
+    NEW.published_at IS NOT OLD.published_at
+  )
+BEGIN
+  UPDATE posts SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+CREATE TABLE tags (
+  id INTEGER PRIMARY KEY NOT NULL,
+  name VARCHAR NOT NULL UNIQUE
+);
+
+CREATE TABLE posts_tags (
+  post_id INTEGER NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+  tag_id INTEGER NOT NULL REFERENCES tags (id) ON DELETE CASCADE,
+  PRIMARY KEY (post_id, tag_id)
+);
+
+CREATE INDEX posts_tags_tag_id_idx ON posts_tags (tag_id);
+
+CREATE TABLE post_revisions (
+  id INTEGER PRIMARY KEY NOT NULL,
+  post_id INTEGER NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+  revision INTEGER NOT NULL,
+  title VARCHAR NOT NULL,
+  body TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE (post_id, revision)
+);
+
+CREATE TRIGGER posts_record_first_revision AFTER INSERT ON posts
+BEGIN
+  INSERT INTO post_revisions (post_id, revision, title, body)
+    VALUES (NEW.id, 1, NEW.title, NEW.body);
+END;
+
+CREATE TRIGGER posts_record_revision AFTER UPDATE OF title, body ON posts
+  FOR EACH ROW
+  WHEN NEW.title IS NOT OLD.title OR NEW.body IS NOT OLD.body
+BEGIN
+  INSERT INTO post_revisions (post_id, revision, title, body)
+    SELECT NEW.id, COALESCE(MAX(revision), 0) + 1, NEW.title, NEW.body
+    FROM post_revisions
+    WHERE post_id = NEW.id;
+END;
+
+-- Full-text search: an FTS5 index over the title and body of posts, kept up
+-- to date by triggers. It stands in for the tsvector column of Postgres.
+CREATE VIRTUAL TABLE posts_fts USING fts5(
+  title, body,
+  content = 'posts', content_rowid = 'id',
+  tokenize = 'porter unicode61'
+);
+
+CREATE TRIGGER posts_fts_insert AFTER INSERT ON posts
+BEGIN
+  INSERT INTO posts_fts (rowid, title, body) VALUES (NEW.id, NEW.title, NEW.body);
+END;
+
+CREATE TRIGGER posts_fts_delete AFTER DELETE ON posts
+BEGIN
+  INSERT INTO posts_fts (posts_fts, rowid, title, body)
+    VALUES ('delete', OLD.id, OLD.title, OLD.body);
+END;
+
+CREATE TRIGGER posts_fts_update AFTER UPDATE OF title, body ON posts
+BEGIN
+  INSERT INTO posts_fts (posts_fts, rowid, title, body)
+    VALUES ('delete', OLD.id, OLD.title, OLD.body);
+  INSERT INTO posts_fts (rowid, title, body) VALUES (NEW.id, NEW.title, NEW.body);
+END;
@@ -0,0 +1,23 @@
+DROP TRIGGER posts_set_updated_at;
+DROP INDEX posts_scheduled_idx;
+
+ALTER TABLE posts ADD COLUMN published BOOLEAN NOT NULL DEFAULT 0;
+
+UPDATE posts SET published = (status = 'published');
+
+ALTER TABLE posts DROP COLUMN status;
+ALTER TABLE posts DROP COLUMN publish_at;
+
+CREATE TRIGGER posts_set_updated_at AFTER UPDATE ON posts
+  FOR EACH ROW
+  WHEN NEW.updated_at IS OLD.updated_at AND (
+    NEW.title IS NOT OLD.title OR
+    NEW.body IS NOT OLD.body OR
+    NEW.published IS NOT OLD.published OR
+    NEW.slug IS NOT OLD.slug OR
+    NEW.author_id IS NOT OLD.author_id OR
+    NEW.published_at IS NOT OLD.published_at
+  )
+BEGIN
+  UPDATE posts SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
@@ -0,0 +1,29 @@
+-- The updated_at trigger names every column, so it goes while the columns
+-- change and comes back with the new ones.
+DROP TRIGGER posts_set_updated_at;
+
+ALTER TABLE posts ADD COLUMN status TEXT NOT NULL DEFAULT 'draft'
+  CHECK (status IN ('draft', 'scheduled', 'published', 'archived'));
+ALTER TABLE posts ADD COLUMN publish_at TIMESTAMP;
+
+UPDATE posts SET status = 'published' WHERE published;
+
+ALTER TABLE posts DROP COLUMN published;
+
+-- The scheduler looks for scheduled posts that are due
+CREATE INDEX posts_scheduled_idx ON posts (publish_at) WHERE status = 'scheduled';
+
+CREATE TRIGGER posts_set_updated_at AFTER UPDATE ON posts
+  FOR EACH ROW
+  WHEN NEW.updated_at IS OLD.updated_at AND (
+    NEW.title IS NOT OLD.title OR
+    NEW.body IS NOT OLD.body OR
+    NEW.status IS NOT OLD.status OR
+    NEW.slug IS NOT OLD.slug OR
+    NEW.author_id IS NOT OLD.author_id OR
+    NEW.published_at IS NOT OLD.published_at OR
+    NEW.publish_at IS NOT OLD.publish_at
+  )
+BEGIN
+  UPDATE posts SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
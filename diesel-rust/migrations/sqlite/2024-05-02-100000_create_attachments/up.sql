@@ -0,0 +1,16 @@
+-- Files attached to posts. The bytes live in the blog's storage under
+-- storage_key, which is made from the sha256, so identical files share one.
+CREATE TABLE attachments (
+  id INTEGER PRIMARY KEY NOT NULL,
+  post_id INTEGER NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+  filename VARCHAR NOT NULL,
+  mime_type VARCHAR NOT NULL,
+  size BIGINT NOT NULL,
+  sha256 VARCHAR NOT NULL,
+  storage_key VARCHAR NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  -- Posts refer to their attachments by file name
+  UNIQUE (post_id, filename)
+);
+
+CREATE INDEX attachments_storage_key_idx ON attachments (storage_key);
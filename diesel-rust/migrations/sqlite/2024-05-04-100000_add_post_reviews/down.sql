@@ -0,0 +1,20 @@
+DROP TABLE post_reviews;
+
+DROP TRIGGER posts_set_updated_at;
+
+ALTER TABLE posts DROP COLUMN review;
+
+CREATE TRIGGER posts_set_updated_at AFTER UPDATE ON posts
+  FOR EACH ROW
+  WHEN NEW.updated_at IS OLD.updated_at AND (
+    NEW.title IS NOT OLD.title OR
+    NEW.body IS NOT OLD.body OR
+    NEW.status IS NOT OLD.status OR
+    NEW.slug IS NOT OLD.slug OR
+    NEW.author_id IS NOT OLD.author_id OR
+    NEW.published_at IS NOT OLD.published_at OR
+    NEW.publish_at IS NOT OLD.publish_at
+  )
+BEGIN
+  UPDATE posts SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
@@ -0,0 +1,37 @@
+-- The updated_at trigger names every column, so it goes while the columns
+-- change and comes back with the new ones.
+DROP TRIGGER posts_set_updated_at;
+
+-- Drafts are reviewed before they are published. Posts that are already out
+-- went out before reviews existed and count as approved.
+ALTER TABLE posts ADD COLUMN review TEXT NOT NULL DEFAULT 'unreviewed'
+  CHECK (review IN ('unreviewed', 'pending', 'approved'));
+
+UPDATE posts SET review = 'approved' WHERE status <> 'draft';
+
+CREATE TRIGGER posts_set_updated_at AFTER UPDATE ON posts
+  FOR EACH ROW
+  WHEN NEW.updated_at IS OLD.updated_at AND (
+    NEW.title IS NOT OLD.title OR
+    NEW.body IS NOT OLD.body OR
+    NEW.status IS NOT OLD.status OR
+    NEW.review IS NOT OLD.review OR
+    NEW.slug IS NOT OLD.slug OR
+    NEW.author_id IS NOT OLD.author_id OR
+    NEW.published_at IS NOT OLD.published_at OR
+    NEW.publish_at IS NOT OLD.publish_at
+  )
+BEGIN
+  UPDATE posts SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+-- Every approval or rejection of a post, and who gave it
+CREATE TABLE post_reviews (
+  id INTEGER PRIMARY KEY NOT NULL,
+  post_id INTEGER NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+  reviewer_id INTEGER NOT NULL REFERENCES authors (id),
+  approved BOOLEAN NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX post_reviews_post_id_idx ON post_reviews (post_id);
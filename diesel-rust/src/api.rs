@@ -0,0 +1,565 @@
+//! The posts as a JSON API over HTTP, served by `blog-server`.
+//!
+//! Anyone can list and read published posts:
+//!
+//! - `GET /posts?page=&per_page=&tag=&author=&sort=` a page of posts, with
+//!   `total`, `page`, `per_page`, `pages` and `next_after`; `after=<id>`
+//!   pages by cursor instead of number.
+//! - `GET /posts/{slug}` a post with its author, tags and attachments.
+//! - `GET /attachments/{key}` a file attached to a published post.
+//!
+//! References to attachments in bodies, `attachment:<filename>`, are
+//! replaced by the URLs of the files.
+//!
+//! Changing posts takes `Authorization: Bearer <token>`, the token the
+//! server was started with:
+//!
+//! - `POST /posts` creates a post from `title`, `body`, `author` (an email),
+//!   `tags` and `status`.
+//! - `PUT /posts/{id}` changes any of those fields; `publish_at` schedules.
+//! - `DELETE /posts/{id}` deletes a post and returns it.
+//! - `POST /posts/{id}/review` sends a draft for review.
+//! - `POST /posts/{id}/approve` approves a post waiting for review on behalf
+//!   of `reviewer` (an email) and publishes it, or schedules it for
+//!   `publish_at`; `POST /posts/{id}/reject` sends it back as a draft.
+//! - `POST /posts/{id}/attachments/{filename}` attaches the request body as
+//!   a file, of up to [`MAX_ATTACHMENT_SIZE`] bytes.
+//!
+//! With the token, `GET /posts/{slug}` and `GET /attachments/{key}` find
+//! drafts too. Errors are
+//! `{"error": "..."}` with a 4xx or 5xx status; a step the review workflow
+//! doesn't allow, like publishing a draft nobody approved, is a 409.
+//!
+//! Queries run on the blocking thread pool, with connections from a
+//! [`BlogPool`].
+
+use crate::attachments::{self, rewrite_references};
+use crate::error::BlogError;
+use crate::models::{Attachment, PostChanges, PostDetails, PostStatus};
+use crate::pool::BlogPool;
+use crate::query::{PostPage, PostQuery, Sort};
+use crate::repository::PostRepository;
+use crate::storage::Storage;
+use actix_web::dev::Payload;
+use actix_web::http::{header, StatusCode};
+use actix_web::{web, FromRequest, HttpRequest, HttpResponse, ResponseError};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::future::{ready, Ready};
+
+/// The most posts a page can have.
+pub const MAX_PER_PAGE: i64 = 100;
+
+const DEFAULT_PER_PAGE: i64 = 10;
+
+/// The largest file that can be attached, in bytes.
+pub const MAX_ATTACHMENT_SIZE: usize = 10 * 1024 * 1024;
+
+/// Settings shared by the handlers.
+#[derive(Clone)]
+pub struct ApiConfig {
+    /// The bearer token that allows changing posts; with `None`, nothing can
+    /// be changed.
+    pub token: Option<String>,
+}
+
+/// Adds the routes to an app, which must have the [`BlogPool`], the
+/// [`ApiConfig`] and a `web::Data<dyn Storage>` for attachments as
+/// `app_data`.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/posts")
+            .route(web::get().to(list_posts))
+            .route(web::post().to(create_post)),
+    )
+    // Slugs can be numbers too, so one resource takes both slugs and ids.
+    .service(
+        web::resource("/posts/{post}")
+            .route(web::get().to(get_post))
+            .route(web::put().to(update_post))
+            .route(web::delete().to(delete_post)),
+    )
+    .service(web::resource("/posts/{id}/review").route(web::post().to(request_review)))
+    .service(web::resource("/posts/{id}/approve").route(web::post().to(approve_post)))
+    .service(web::resource("/posts/{id}/reject").route(web::post().to(reject_post)))
+    .service(
+        web::resource("/posts/{id}/attachments/{filename}")
+            .app_data(web::PayloadConfig::new(MAX_ATTACHMENT_SIZE))
+            .route(web::post().to(add_attachment)),
+    )
+    .service(web::resource("/attachments/{key:.*}").route(web::get().to(get_attachment)));
+}
+
+/// Why a request failed.
+#[derive(Debug)]
+pub enum ApiError {
+    Blog(BlogError),
+    BadRequest(String),
+    /// Nothing is at this URL, like a file that isn't attached any more.
+    NotFound(String),
+    Unauthorized,
+    /// The query never finished, like when its thread panicked.
+    Cancelled,
+}
+
+impl From<BlogError> for ApiError {
+    fn from(err: BlogError) -> Self {
+        ApiError::Blog(err)
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApiError::Blog(err) => write!(f, "{}", err),
+            ApiError::BadRequest(message) | ApiError::NotFound(message) => write!(f, "{}", message),
+            ApiError::Unauthorized => write!(f, "A valid bearer token is needed to change posts"),
+            ApiError::Cancelled => write!(f, "The query was cancelled"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::Blog(
+                BlogError::NotFound(_)
+                | BlogError::SlugNotFound(_)
+                | BlogError::AuthorNotFound(_)
+                | BlogError::RevisionNotFound { .. }
+                | BlogError::CommentNotFound(_)
+                | BlogError::AttachmentNotFound(_),
+            ) => StatusCode::NOT_FOUND,
+            ApiError::Blog(BlogError::AttachmentExists { .. } | BlogError::InvalidTransition { .. }) => {
+                StatusCode::CONFLICT
+            }
+            ApiError::Blog(BlogError::InvalidAttachmentName(_)) => StatusCode::BAD_REQUEST,
+            ApiError::Blog(_) | ApiError::Cancelled => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let ApiError::Unauthorized = self {
+            response.insert_header((header::WWW_AUTHENTICATE, "Bearer"));
+        }
+        let error = if self.status_code().is_server_error() {
+            // The details can tell too much about the server.
+            eprintln!("{}", self);
+            "Internal server error".to_string()
+        } else {
+            self.to_string()
+        };
+        response.json(ErrorBody { error })
+    }
+}
+
+/// Proof that a request carries the admin token; extracting it fails with
+/// 401 otherwise.
+pub struct Admin;
+
+impl FromRequest for Admin {
+    type Error = ApiError;
+    type Future = Ready<Result<Admin, ApiError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let expected = req.app_data::<web::Data<ApiConfig>>().and_then(|config| config.token.as_deref());
+        let given = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        ready(match (expected, given) {
+            (Some(expected), Some(given)) if same_token(expected.as_bytes(), given.trim().as_bytes()) => Ok(Admin),
+            _ => Err(ApiError::Unauthorized),
+        })
+    }
+}
+
+/// Compares tokens in a time that doesn't depend on where they differ.
+fn same_token(expected: &[u8], given: &[u8]) -> bool {
+    expected.len() == given.len() && expected.iter().zip(given).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Runs `f` with a pooled connection on the blocking thread pool.
+async fn with_repo<T, F>(pool: web::Data<BlogPool>, f: F) -> Result<T, ApiError>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut PostRepository) -> Result<T, ApiError> + Send + 'static,
+{
+    web::block(move || {
+        let mut conn = pool.get().map_err(BlogError::Pool)?;
+        f(&mut PostRepository::new(&mut conn))
+    })
+    .await
+    .map_err(|_| ApiError::Cancelled)?
+}
+
+#[derive(Deserialize)]
+pub struct ListParams {
+    page: Option<i64>,
+    per_page: Option<i64>,
+    after: Option<i32>,
+    tag: Option<String>,
+    author: Option<i32>,
+    sort: Option<String>,
+}
+
+/// A page of posts with what it takes to ask for the others.
+#[derive(Serialize)]
+pub struct Listing {
+    #[serde(flatten)]
+    page: PostPage,
+    per_page: i64,
+    /// Number of pages of `per_page` posts.
+    pages: i64,
+}
+
+/// `body` with its references to `attachments` replaced by their URLs.
+fn link_attachments(storage: &dyn Storage, body: &str, attachments: &[Attachment]) -> String {
+    rewrite_references(body, attachments, |attachment| storage.url(&attachment.storage_key))
+}
+
+async fn list_posts(
+    pool: web::Data<BlogPool>,
+    storage: web::Data<dyn Storage>,
+    params: web::Query<ListParams>,
+) -> Result<HttpResponse, ApiError> {
+    let params = params.into_inner();
+    let per_page = params.per_page.unwrap_or(DEFAULT_PER_PAGE);
+    if !(1..=MAX_PER_PAGE).contains(&per_page) {
+        return Err(ApiError::BadRequest(format!("per_page must be from 1 to {}", MAX_PER_PAGE)));
+    }
+
+    let mut query = PostQuery::new();
+    if let Some(sort) = &params.sort {
+        query = query.sort(sort.parse::<Sort>().map_err(ApiError::BadRequest)?);
+    }
+    if let Some(tag) = &params.tag {
+        query = query.tag(tag);
+    }
+    if let Some(author) = params.author {
+        query = query.author(author);
+    }
+    query = match (params.after, params.page) {
+        (Some(_), Some(_)) => return Err(ApiError::BadRequest("Give page or after, not both".to_string())),
+        (Some(after), None) => query.after(after, per_page),
+        (None, page) => {
+            let page = page.unwrap_or(1);
+            let max_page = PostQuery::max_page(per_page);
+            if !(1..=max_page).contains(&page) {
+                return Err(ApiError::BadRequest(format!("page must be from 1 to {}", max_page)));
+            }
+            query.page(page, per_page)
+        }
+    };
+
+    let page = with_repo(pool, move |repo| {
+        let mut page = repo.query(&query)?;
+        let ids: Vec<i32> = page.posts.iter().map(|post| post.id).collect();
+        let attached = repo.attachments_of(&ids)?;
+        for post in &mut page.posts {
+            let own: Vec<Attachment> = attached.iter().filter(|attachment| attachment.post_id == post.id).cloned().collect();
+            post.body = link_attachments(&**storage, &post.body, &own);
+        }
+        Ok(page)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(Listing {
+        pages: (page.total + per_page - 1) / per_page,
+        per_page,
+        page,
+    }))
+}
+
+async fn get_post(
+    pool: web::Data<BlogPool>,
+    storage: web::Data<dyn Storage>,
+    post: web::Path<String>,
+    admin: Option<Admin>,
+) -> Result<HttpResponse, ApiError> {
+    let slug = post.into_inner();
+    let mut details = with_repo(pool, move |repo| {
+        let post = repo.get_by_slug(&slug)?;
+        if !post.is_published() && admin.is_none() {
+            return Err(BlogError::SlugNotFound(slug).into());
+        }
+        Ok(repo.details(post.id)?)
+    })
+    .await?;
+    details.post.body = link_attachments(&**storage, &details.post.body, &details.attachments);
+    Ok(HttpResponse::Ok().json(details))
+}
+
+/// A post as sent to `POST /posts` and `PUT /posts/{id}`. Fields left out of
+/// a `PUT` stay as they are.
+#[derive(Deserialize)]
+pub struct PostInput {
+    title: Option<String>,
+    body: Option<String>,
+    /// Email of the author.
+    author: Option<String>,
+    tags: Option<Vec<String>>,
+    status: Option<PostStatus>,
+    /// When to publish a `scheduled` post, in UTC.
+    publish_at: Option<NaiveDateTime>,
+}
+
+async fn create_post(
+    pool: web::Data<BlogPool>,
+    _: Admin,
+    input: web::Json<PostInput>,
+) -> Result<HttpResponse, ApiError> {
+    let input = input.into_inner();
+    let title = match input.title.as_deref().map(str::trim) {
+        Some(title) if !title.is_empty() => title.to_string(),
+        _ => return Err(ApiError::BadRequest("A post needs a title".to_string())),
+    };
+
+    check_status(&input)?;
+
+    let details = with_repo(pool, move |repo| {
+        Ok(repo.transaction(|repo| {
+            let post = repo.create(&title, input.body.as_deref().unwrap_or(""))?;
+            apply(repo, post.id, &input)
+        })?)
+    })
+    .await?;
+    Ok(HttpResponse::Created().json(details))
+}
+
+async fn update_post(
+    pool: web::Data<BlogPool>,
+    _: Admin,
+    id: web::Path<i32>,
+    input: web::Json<PostInput>,
+) -> Result<HttpResponse, ApiError> {
+    let (id, input) = (id.into_inner(), input.into_inner());
+    if matches!(input.title.as_deref().map(str::trim), Some("")) {
+        return Err(ApiError::BadRequest("A post needs a title".to_string()));
+    }
+    check_status(&input)?;
+
+    let details = with_repo(pool, move |repo| {
+        Ok(repo.transaction(|repo| {
+            repo.get(id)?;
+            if input.title.is_some() || input.body.is_some() {
+                let changes = PostChanges {
+                    title: input.title.as_deref().map(str::trim),
+                    body: input.body.as_deref(),
+                };
+                repo.update(id, &changes)?;
+            }
+            apply(repo, id, &input)
+        })?)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(details))
+}
+
+/// Rejects a `publish_at` that doesn't go with the `status`, or is past.
+fn check_status(input: &PostInput) -> Result<(), ApiError> {
+    match (input.status, input.publish_at) {
+        (Some(PostStatus::Scheduled), Some(at)) if at <= chrono::Utc::now().naive_utc() => {
+            Err(ApiError::BadRequest("publish_at must be in the future".to_string()))
+        }
+        (Some(PostStatus::Scheduled), None) => Err(ApiError::BadRequest("A scheduled post needs publish_at".to_string())),
+        (Some(PostStatus::Scheduled), Some(_)) | (_, None) => Ok(()),
+        (_, Some(_)) => Err(ApiError::BadRequest("publish_at is only for scheduled posts".to_string())),
+    }
+}
+
+/// Sets the author, tags and status of a post from `input`, leaving out what
+/// it leaves out.
+fn apply(repo: &mut PostRepository, id: i32, input: &PostInput) -> crate::error::Result<PostDetails> {
+    if let Some(email) = &input.author {
+        let author = repo.find_author(email)?;
+        repo.set_author(id, Some(author.id))?;
+    }
+    if let Some(tags) = &input.tags {
+        repo.set_tags(id, &tags.iter().map(String::as_str).collect::<Vec<_>>())?;
+    }
+    let current = repo.get(id)?;
+    match (input.status, input.publish_at) {
+        (Some(PostStatus::Scheduled), Some(at)) => {
+            repo.schedule(id, at)?;
+        }
+        // Keeping the status leaves the post alone: publishing a published
+        // post again would move its date.
+        (Some(status), _) if status == current.status => {}
+        (Some(PostStatus::Published), _) => {
+            repo.publish(id)?;
+        }
+        (Some(PostStatus::Draft), _) => {
+            repo.unpublish(id)?;
+        }
+        (Some(PostStatus::Archived), _) => {
+            repo.archive(id)?;
+        }
+        _ => {}
+    }
+    repo.details(id)
+}
+
+/// Who is approving or rejecting a post, and for approvals when to publish
+/// it.
+#[derive(Deserialize)]
+pub struct ReviewInput {
+    /// Email of the reviewer, one of the authors.
+    reviewer: String,
+    publish_at: Option<NaiveDateTime>,
+}
+
+async fn request_review(pool: web::Data<BlogPool>, _: Admin, id: web::Path<i32>) -> Result<HttpResponse, ApiError> {
+    let id = id.into_inner();
+    let details = with_repo(pool, move |repo| {
+        repo.request_review(id)?;
+        Ok(repo.details(id)?)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(details))
+}
+
+async fn approve_post(
+    pool: web::Data<BlogPool>,
+    _: Admin,
+    id: web::Path<i32>,
+    input: web::Json<ReviewInput>,
+) -> Result<HttpResponse, ApiError> {
+    let (id, input) = (id.into_inner(), input.into_inner());
+    if matches!(input.publish_at, Some(at) if at <= chrono::Utc::now().naive_utc()) {
+        return Err(ApiError::BadRequest("publish_at must be in the future".to_string()));
+    }
+
+    let details = with_repo(pool, move |repo| {
+        let reviewer = repo.find_author(&input.reviewer)?;
+        repo.approve(id, reviewer.id, input.publish_at)?;
+        Ok(repo.details(id)?)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(details))
+}
+
+async fn reject_post(
+    pool: web::Data<BlogPool>,
+    _: Admin,
+    id: web::Path<i32>,
+    input: web::Json<ReviewInput>,
+) -> Result<HttpResponse, ApiError> {
+    let (id, input) = (id.into_inner(), input.into_inner());
+    if input.publish_at.is_some() {
+        return Err(ApiError::BadRequest("publish_at is only for approvals".to_string()));
+    }
+
+    let details = with_repo(pool, move |repo| {
+        let reviewer = repo.find_author(&input.reviewer)?;
+        repo.reject(id, reviewer.id)?;
+        Ok(repo.details(id)?)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(details))
+}
+
+async fn delete_post(
+    pool: web::Data<BlogPool>,
+    storage: web::Data<dyn Storage>,
+    _: Admin,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    let id = id.into_inner();
+    let post = with_repo(pool, move |repo| {
+        let (post, attached) = repo.transaction(|repo| {
+            let attached = repo.attachments_of(&[id])?;
+            Ok((repo.delete(id)?, attached))
+        })?;
+        // Only once the rows are gone, so a failed delete keeps its files.
+        attachments::release(repo, &**storage, &attached)?;
+        Ok(post)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(post))
+}
+
+async fn add_attachment(
+    pool: web::Data<BlogPool>,
+    storage: web::Data<dyn Storage>,
+    _: Admin,
+    path: web::Path<(i32, String)>,
+    body: web::Bytes,
+) -> Result<HttpResponse, ApiError> {
+    let (id, filename) = path.into_inner();
+    let attachment = with_repo(pool, move |repo| {
+        Ok(attachments::attach(repo, &**storage, id, &filename, &body)?)
+    })
+    .await?;
+    Ok(HttpResponse::Created().json(attachment))
+}
+
+async fn get_attachment(
+    pool: web::Data<BlogPool>,
+    storage: web::Data<dyn Storage>,
+    key: web::Path<String>,
+    admin: Option<Admin>,
+) -> Result<HttpResponse, ApiError> {
+    let key = key.into_inner();
+    let (mime_type, public, bytes) = with_repo(pool, move |repo| {
+        // The file may be attached to several posts; one published is enough
+        // for everyone to see it, otherwise only admins can.
+        let mut shown = None;
+        for attachment in repo.attachments_by_key(&key)? {
+            if repo.get(attachment.post_id)?.is_published() {
+                shown = Some((attachment, true));
+                break;
+            }
+            if admin.is_some() && shown.is_none() {
+                shown = Some((attachment, false));
+            }
+        }
+        let Some((attachment, public)) = shown else {
+            return Err(ApiError::NotFound(format!("Unable to find attachment {}", key)));
+        };
+        Ok((attachment.mime_type, public, storage.get(&attachment.storage_key)?))
+    })
+    .await?;
+
+    let mut response = HttpResponse::Ok();
+    response
+        .content_type(mime_type.as_str())
+        .insert_header((header::X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        // The contents under a key never change, but unpublished files must
+        // stay out of shared caches.
+        .insert_header((
+            header::CACHE_CONTROL,
+            if public { "public, max-age=31536000, immutable" } else { "private, max-age=31536000, immutable" },
+        ));
+    if !attachments::shows_inline(&mime_type) {
+        // SVG, HTML and the like can run scripts on this origin.
+        response
+            .insert_header((header::CONTENT_DISPOSITION, "attachment"))
+            .insert_header((header::CONTENT_SECURITY_POLICY, "sandbox"));
+    }
+    Ok(response.body(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_tokens() {
+        assert!(same_token(b"secret", b"secret"));
+        assert!(!same_token(b"secret", b"secreT"));
+        assert!(!same_token(b"secret", b"secret2"));
+        assert!(!same_token(b"secret", b""));
+    }
+}
@@ -0,0 +1,601 @@
+use crate::error::{BlogError, OrNotFound, Result};
+use crate::comments::{thread, CommentThread};
+use crate::models::{
+    Author, Comment, NewAuthor, NewComment, NewPost, NewPostReview, Post, PostChanges, PostReview, PostStatus, PostTag,
+    ReviewState, Revision, Tag, Transition,
+};
+use crate::repository::{contains_pattern, listing, slug_pattern, tag_names};
+use crate::query::{PostPage, PostQuery, Start, Visibility};
+use crate::schema::posts::dsl::*;
+use crate::schema::{authors, comments, post_reviews, post_revisions, posts_tags, tags};
+use crate::search::{
+    ts_headline, ts_rank_cd, websearch_to_tsquery, English, SearchHit, TsvectorExpressionMethods,
+    HEADLINE_OPTIONS,
+};
+use crate::slug::{next_free_slug, slugify};
+use chrono::NaiveDateTime;
+use diesel::dsl::now;
+use diesel::pg::Pg;
+use diesel::{
+    BelongingToDsl, EscapeExpressionMethods, ExpressionMethods, NullableExpressionMethods,
+    OptionalExtension, QueryDsl, SelectableHelper, TextExpressionMethods,
+};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+
+/// Loads a post, locked until the transaction ends.
+async fn locked(conn: &mut AsyncPgConnection, post_id: i32) -> Result<Post> {
+    posts
+        .find(post_id)
+        .select(Post::as_select())
+        .for_update()
+        .first(conn)
+        .await
+        .optional()
+        .or_not_found(post_id)
+}
+
+/// Loads and locks a post for a step of the workflow, like
+/// `PostRepository` does.
+async fn for_transition(conn: &mut AsyncPgConnection, post_id: i32, transition: Transition) -> Result<Post> {
+    let post = locked(conn, post_id).await?;
+    BlogError::check_transition(&post, transition)?;
+    Ok(post)
+}
+
+/// Sends an edited post back to review when its new text needs one, like
+/// `PostRepository` does for `update` and `revert`.
+async fn withdraw_review(conn: &mut AsyncPgConnection, before: &Post, edited: Post) -> Result<Post> {
+    if !before.edit_needs_review(&edited) {
+        return Ok(edited);
+    }
+    let new_status = match edited.status {
+        PostStatus::Scheduled => PostStatus::Draft,
+        other => other,
+    };
+    Ok(diesel::update(posts.find(edited.id))
+        .set((
+            status.eq(new_status),
+            review.eq(ReviewState::Unreviewed),
+            publish_at.eq(None::<NaiveDateTime>),
+        ))
+        .returning(Post::as_returning())
+        .get_result(conn)
+        .await?)
+}
+
+async fn record_review(conn: &mut AsyncPgConnection, post_id: i32, reviewer_id: i32, approved: bool) -> Result<PostReview> {
+    Ok(diesel::insert_into(post_reviews::table)
+        .values(&NewPostReview { post_id, reviewer_id, approved })
+        .returning(PostReview::as_returning())
+        .get_result(conn)
+        .await?)
+}
+
+/// Publishes a post now, once its transition was checked. `approve` and
+/// `publish` share it so both write the same row.
+async fn publish_post(conn: &mut AsyncPgConnection, post_id: i32) -> Result<Post> {
+    Ok(diesel::update(posts.find(post_id))
+        .set((
+            status.eq(PostStatus::Published),
+            published_at.eq(now.nullable()),
+            publish_at.eq(None::<NaiveDateTime>),
+        ))
+        .returning(Post::as_returning())
+        .get_result(conn)
+        .await?)
+}
+
+/// Schedules a post for `at`, like [`publish_post`] for `approve` and
+/// `schedule`.
+async fn schedule_post(conn: &mut AsyncPgConnection, post_id: i32, at: NaiveDateTime) -> Result<Post> {
+    Ok(diesel::update(posts.find(post_id))
+        .set((
+            status.eq(PostStatus::Scheduled),
+            published_at.eq(None::<NaiveDateTime>),
+            publish_at.eq(at),
+        ))
+        .returning(Post::as_returning())
+        .get_result(conn)
+        .await?)
+}
+
+/// The operations of [`PostRepository`](crate::PostRepository) on a
+/// diesel-async connection.
+pub struct AsyncPostRepository<'a> {
+    conn: &'a mut AsyncPgConnection,
+}
+
+impl<'a> AsyncPostRepository<'a> {
+    pub fn new(conn: &'a mut AsyncPgConnection) -> Self {
+        AsyncPostRepository { conn }
+    }
+
+    /// Saves a new draft with a slug made from its title.
+    pub async fn create(&mut self, new_title: &str, new_body: &str) -> Result<Post> {
+        let base = slugify(new_title);
+        let taken: Vec<String> = posts
+            .select(slug)
+            .filter(slug.like(slug_pattern(&base)).escape('\\'))
+            .load(self.conn)
+            .await?;
+        let new_slug = next_free_slug(&base, &taken);
+
+        // The unique constraint on `slug` catches a concurrent insert.
+        let new_post = NewPost {
+            title: new_title,
+            body: new_body,
+            slug: &new_slug,
+            author_id: None,
+        };
+        Ok(diesel::insert_into(posts)
+            .values(&new_post)
+            .returning(Post::as_returning())
+            .get_result(self.conn)
+            .await?)
+    }
+
+    pub async fn get(&mut self, post_id: i32) -> Result<Post> {
+        posts
+            .find(post_id)
+            .select(Post::as_select())
+            .first(self.conn)
+            .await
+            .optional()
+            .or_not_found(post_id)
+    }
+
+    pub async fn get_by_slug(&mut self, post_slug: &str) -> Result<Post> {
+        posts
+            .filter(slug.eq(post_slug))
+            .select(Post::as_select())
+            .first(self.conn)
+            .await
+            .optional()
+            .or_else_missing(|| BlogError::SlugNotFound(post_slug.to_string()))
+    }
+
+    /// Runs a listing built with [`PostQuery`].
+    pub async fn query(&mut self, query: &PostQuery) -> Result<PostPage> {
+        let cursor = match query.start() {
+            Start::After(post_id) => Some(self.get(post_id).await?),
+            Start::Page(_) => None,
+        };
+        let total = query.filtered::<Pg>().count().get_result(self.conn).await?;
+        let rows = query
+            .paged::<Pg>(cursor.as_ref())
+            .select(Post::as_select())
+            .load(self.conn).await?;
+
+        Ok(query.finish(rows, total))
+    }
+
+    /// The first `limit` posts, only published ones unless `include_drafts`
+    /// is set.
+    pub async fn list(&mut self, include_drafts: bool, limit: i64) -> Result<Vec<Post>> {
+        Ok(self.query(&listing(include_drafts, limit)).await?.posts)
+    }
+
+    /// Like [`list`](Self::list), for the posts with this tag.
+    pub async fn list_by_tag(&mut self, tag: &str, include_drafts: bool, limit: i64) -> Result<Vec<Post>> {
+        Ok(self.query(&listing(include_drafts, limit).tag(tag)).await?.posts)
+    }
+
+    /// Like [`list`](Self::list), for the posts of an author.
+    pub async fn list_by_author(&mut self, author: i32, include_drafts: bool, limit: i64) -> Result<Vec<Post>> {
+        Ok(self.query(&listing(include_drafts, limit).author(author)).await?.posts)
+    }
+
+    pub async fn search(&mut self, text: &str, visibility: Visibility, limit: i64) -> Result<Vec<SearchHit>> {
+        if text.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        let query = || websearch_to_tsquery(English, text.to_string());
+
+        let mut matching = posts.filter(search.matches(query())).into_boxed();
+        if let Some(statuses) = visibility.statuses() {
+            matching = matching.filter(status.eq_any(statuses));
+        }
+
+        let rank = || ts_rank_cd(search, query());
+        let hits: Vec<(Post, f32, String)> = matching
+            .select((
+                Post::as_select(),
+                rank(),
+                ts_headline(English, body, query(), HEADLINE_OPTIONS),
+            ))
+            .order((rank().desc(), id))
+            .limit(limit)
+            .load(self.conn)
+            .await?;
+
+        Ok(hits
+            .into_iter()
+            .map(|(post, rank, snippet)| SearchHit { post, rank, snippet })
+            .collect())
+    }
+
+    pub async fn request_review(&mut self, post_id: i32) -> Result<Post> {
+        self.conn
+            .transaction::<_, BlogError, _>(|conn| {
+                async move {
+                    for_transition(conn, post_id, Transition::RequestReview).await?;
+                    Ok(diesel::update(posts.find(post_id))
+                        .set(review.eq(ReviewState::Pending))
+                        .returning(Post::as_returning())
+                        .get_result(conn)
+                        .await?)
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    pub async fn approve(&mut self, post_id: i32, reviewer_id: i32, at: Option<NaiveDateTime>) -> Result<Post> {
+        self.conn
+            .transaction::<_, BlogError, _>(|conn| {
+                async move {
+                    for_transition(conn, post_id, Transition::Approve).await?;
+                    record_review(conn, post_id, reviewer_id, true).await?;
+                    diesel::update(posts.find(post_id))
+                        .set(review.eq(ReviewState::Approved))
+                        .execute(conn)
+                        .await?;
+                    match at {
+                        Some(at) => schedule_post(conn, post_id, at).await,
+                        None => publish_post(conn, post_id).await,
+                    }
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    pub async fn reject(&mut self, post_id: i32, reviewer_id: i32) -> Result<Post> {
+        self.conn
+            .transaction::<_, BlogError, _>(|conn| {
+                async move {
+                    for_transition(conn, post_id, Transition::Reject).await?;
+                    record_review(conn, post_id, reviewer_id, false).await?;
+                    Ok(diesel::update(posts.find(post_id))
+                        .set(review.eq(ReviewState::Unreviewed))
+                        .returning(Post::as_returning())
+                        .get_result(conn)
+                        .await?)
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    pub async fn reviews(&mut self, post_id: i32) -> Result<Vec<(PostReview, Author)>> {
+        self.get(post_id).await?;
+        Ok(post_reviews::table
+            .inner_join(authors::table)
+            .filter(post_reviews::post_id.eq(post_id))
+            .order(post_reviews::id)
+            .select((PostReview::as_select(), Author::as_select()))
+            .load(self.conn)
+            .await?)
+    }
+
+    pub async fn publish(&mut self, post_id: i32) -> Result<Post> {
+        self.conn
+            .transaction::<_, BlogError, _>(|conn| {
+                async move {
+                    for_transition(conn, post_id, Transition::Publish).await?;
+                    publish_post(conn, post_id).await
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    pub async fn schedule(&mut self, post_id: i32, at: NaiveDateTime) -> Result<Post> {
+        self.conn
+            .transaction::<_, BlogError, _>(|conn| {
+                async move {
+                    for_transition(conn, post_id, Transition::Schedule).await?;
+                    schedule_post(conn, post_id, at).await
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    pub async fn unpublish(&mut self, post_id: i32) -> Result<Post> {
+        self.conn
+            .transaction::<_, BlogError, _>(|conn| {
+                async move {
+                    for_transition(conn, post_id, Transition::Unpublish).await?;
+                    Ok(diesel::update(posts.find(post_id))
+                        .set((
+                            status.eq(PostStatus::Draft),
+                            review.eq(ReviewState::Unreviewed),
+                            published_at.eq(None::<NaiveDateTime>),
+                            publish_at.eq(None::<NaiveDateTime>),
+                        ))
+                        .returning(Post::as_returning())
+                        .get_result(conn)
+                        .await?)
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    pub async fn archive(&mut self, post_id: i32) -> Result<Post> {
+        self.conn
+            .transaction::<_, BlogError, _>(|conn| {
+                async move {
+                    for_transition(conn, post_id, Transition::Archive).await?;
+                    Ok(diesel::update(posts.find(post_id))
+                        .set((status.eq(PostStatus::Archived), publish_at.eq(None::<NaiveDateTime>)))
+                        .returning(Post::as_returning())
+                        .get_result(conn)
+                        .await?)
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    pub async fn publish_due(&mut self, until: NaiveDateTime) -> Result<Vec<Post>> {
+        let due = posts.filter(status.eq(PostStatus::Scheduled)).filter(publish_at.le(until));
+        Ok(diesel::update(due)
+            .set((
+                status.eq(PostStatus::Published),
+                published_at.eq(publish_at),
+                publish_at.eq(None::<NaiveDateTime>),
+            ))
+            .returning(Post::as_returning())
+            .get_results(self.conn)
+            .await?)
+    }
+
+    /// Changes the title and/or body of a post, sending one that isn't
+    /// published back to review.
+    pub async fn update(&mut self, post_id: i32, changes: &PostChanges<'_>) -> Result<Post> {
+        self.conn
+            .transaction::<_, BlogError, _>(|conn| {
+                async move {
+                    let before = locked(conn, post_id).await?;
+                    let edited = diesel::update(posts.find(post_id))
+                        .set(changes)
+                        .returning(Post::as_returning())
+                        .get_result(conn)
+                        .await?;
+                    withdraw_review(conn, &before, edited).await
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    /// Deletes a post, returning what was deleted.
+    pub async fn delete(&mut self, post_id: i32) -> Result<Post> {
+        diesel::delete(posts.find(post_id))
+            .returning(Post::as_returning())
+            .get_result(self.conn)
+            .await
+            .optional()
+            .or_not_found(post_id)
+    }
+
+    /// Posts whose title contains `text`, taken literally.
+    pub async fn find_by_title(&mut self, text: &str) -> Result<Vec<Post>> {
+        Ok(posts
+            .filter(title.like(contains_pattern(text)).escape('\\'))
+            .order(id)
+            .select(Post::as_select())
+            .load(self.conn)
+            .await?)
+    }
+
+    /// Deletes the posts with these ids, returning how many were deleted.
+    /// A single statement, so it needs no explicit transaction.
+    pub async fn delete_many(&mut self, ids: &[i32]) -> Result<usize> {
+        Ok(diesel::delete(posts.filter(id.eq_any(ids)))
+            .execute(self.conn)
+            .await?)
+    }
+
+    /// Deletes every post whose title contains `text`, taken literally.
+    pub async fn delete_matching(&mut self, text: &str) -> Result<usize> {
+        Ok(diesel::delete(posts.filter(title.like(contains_pattern(text)).escape('\\')))
+            .execute(self.conn)
+            .await?)
+    }
+
+    /// Every revision of a post, oldest first.
+    pub async fn history(&mut self, post_id: i32) -> Result<Vec<Revision>> {
+        self.get(post_id).await?;
+
+        Ok(post_revisions::table
+            .filter(post_revisions::post_id.eq(post_id))
+            .order(post_revisions::revision)
+            .select(Revision::as_select())
+            .load(self.conn)
+            .await?)
+    }
+
+    pub async fn revision(&mut self, post_id: i32, number: i32) -> Result<Revision> {
+        post_revisions::table
+            .filter(post_revisions::post_id.eq(post_id))
+            .filter(post_revisions::revision.eq(number))
+            .select(Revision::as_select())
+            .first(self.conn)
+            .await
+            .optional()
+            .or_else_missing(|| BlogError::RevisionNotFound { post_id, revision: number })
+    }
+
+    /// Puts back the title and body of an earlier revision, which is saved
+    /// as a new revision in turn, sending a post that isn't published back
+    /// to review.
+    pub async fn revert(&mut self, post_id: i32, number: i32) -> Result<Post> {
+        self.conn
+            .transaction::<_, BlogError, _>(|conn| {
+                async move {
+                    let before = locked(conn, post_id).await?;
+                    let old = AsyncPostRepository { conn }.revision(post_id, number).await?;
+                    let reverted = diesel::update(posts.find(post_id))
+                        .set((title.eq(old.title), body.eq(old.body)))
+                        .returning(Post::as_returning())
+                        .get_result(conn)
+                        .await?;
+                    withdraw_review(conn, &before, reverted).await
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    pub async fn set_author(&mut self, post_id: i32, author: Option<i32>) -> Result<Post> {
+        diesel::update(posts.find(post_id))
+            .set(author_id.eq(author))
+            .returning(Post::as_returning())
+            .get_result(self.conn)
+            .await
+            .optional()
+            .or_not_found(post_id)
+    }
+
+    pub async fn set_tags(&mut self, post_id: i32, names: &[&str]) -> Result<Vec<Tag>> {
+        let names = tag_names(names);
+        self.get(post_id).await?;
+
+        Ok(self
+            .conn
+            .transaction::<_, diesel::result::Error, _>(|conn| {
+                async move {
+                    let new_tags: Vec<_> = names.iter().map(|tag| tags::name.eq(tag)).collect();
+                    diesel::insert_into(tags::table)
+                        .values(&new_tags)
+                        .on_conflict_do_nothing()
+                        .execute(conn)
+                        .await?;
+                    let post_tags: Vec<Tag> = tags::table
+                        .filter(tags::name.eq_any(&names))
+                        .order(tags::name)
+                        .select(Tag::as_select())
+                        .load(conn)
+                        .await?;
+
+                    diesel::delete(posts_tags::table.filter(posts_tags::post_id.eq(post_id)))
+                        .execute(conn)
+                        .await?;
+                    let links: Vec<PostTag> = post_tags
+                        .iter()
+                        .map(|tag| PostTag { post_id, tag_id: tag.id })
+                        .collect();
+                    diesel::insert_into(posts_tags::table)
+                        .values(&links)
+                        .execute(conn)
+                        .await?;
+
+                    Ok(post_tags)
+                }
+                .scope_boxed()
+            })
+            .await?)
+    }
+
+    pub async fn tags_of(&mut self, post_id: i32) -> Result<Vec<Tag>> {
+        Ok(tags::table
+            .inner_join(posts_tags::table)
+            .filter(posts_tags::post_id.eq(post_id))
+            .order(tags::name)
+            .select(Tag::as_select())
+            .load(self.conn)
+            .await?)
+    }
+
+    pub async fn create_author(&mut self, name: &str, email: &str) -> Result<Author> {
+        Ok(diesel::insert_into(authors::table)
+            .values(&NewAuthor { name, email })
+            .returning(Author::as_returning())
+            .get_result(self.conn)
+            .await?)
+    }
+
+    pub async fn find_author(&mut self, email: &str) -> Result<Author> {
+        authors::table
+            .filter(authors::email.eq(email))
+            .select(Author::as_select())
+            .first(self.conn)
+            .await
+            .optional()
+            .or_else_missing(|| BlogError::AuthorNotFound(email.to_string()))
+    }
+
+    pub async fn add_comment(&mut self, comment: &NewComment<'_>) -> Result<Comment> {
+        self.get(comment.post_id).await?;
+        if let Some(parent) = comment.parent_id {
+            if self.comment(parent).await?.post_id != comment.post_id {
+                return Err(BlogError::CommentNotFound(parent));
+            }
+        }
+
+        Ok(diesel::insert_into(comments::table)
+            .values(comment)
+            .returning(Comment::as_returning())
+            .get_result(self.conn)
+            .await?)
+    }
+
+    pub async fn comment(&mut self, comment_id: i32) -> Result<Comment> {
+        comments::table
+            .find(comment_id)
+            .select(Comment::as_select())
+            .first(self.conn)
+            .await
+            .optional()
+            .or_else_missing(|| BlogError::CommentNotFound(comment_id))
+    }
+
+    pub async fn comments(&mut self, post_id: i32, include_pending: bool) -> Result<Vec<CommentThread>> {
+        let post = self.get(post_id).await?;
+
+        let mut query = Comment::belonging_to(&post).into_boxed();
+        if !include_pending {
+            query = query.filter(comments::approved.eq(true));
+        }
+        let all = query
+            .order((comments::created_at, comments::id))
+            .select(Comment::as_select())
+            .load(self.conn)
+            .await?;
+
+        Ok(thread(all))
+    }
+
+    pub async fn pending_comments(&mut self, limit: i64) -> Result<Vec<Comment>> {
+        Ok(comments::table
+            .filter(comments::approved.eq(false))
+            .order((comments::created_at, comments::id))
+            .limit(limit)
+            .select(Comment::as_select())
+            .load(self.conn)
+            .await?)
+    }
+
+    pub async fn approve_comment(&mut self, comment_id: i32) -> Result<Comment> {
+        diesel::update(comments::table.find(comment_id))
+            .set(comments::approved.eq(true))
+            .returning(Comment::as_returning())
+            .get_result(self.conn)
+            .await
+            .optional()
+            .or_else_missing(|| BlogError::CommentNotFound(comment_id))
+    }
+
+    pub async fn delete_comment(&mut self, comment_id: i32) -> Result<Comment> {
+        diesel::delete(comments::table.find(comment_id))
+            .returning(Comment::as_returning())
+            .get_result(self.conn)
+            .await
+            .optional()
+            .or_else_missing(|| BlogError::CommentNotFound(comment_id))
+    }
+}
@@ -0,0 +1,198 @@
+//! Attaching files to posts, and linking to them from post bodies.
+//!
+//! A body refers to an attachment of its post as `attachment:<filename>`,
+//! as in `![A cat](attachment:cat.png)`. [`rewrite_references`] turns those
+//! into the URLs the files are served from.
+
+use crate::error::{BlogError, Result};
+use crate::models::{Attachment, NewAttachment};
+use crate::repository::PostRepository;
+use crate::storage::Storage;
+use sha2::{Digest, Sha256};
+
+/// What a reference to an attachment starts with.
+pub const REFERENCE_PREFIX: &str = "attachment:";
+
+/// Characters that end a reference, and so can't be in file names.
+fn ends_reference(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '(' | ')' | '[' | ']' | '<' | '>' | '"' | '\'' | '/' | '\\')
+}
+
+/// Saves `bytes` to the storage and records them as the attachment
+/// `filename` of a post. Nothing is recorded if storing fails.
+pub fn attach(
+    repo: &mut PostRepository,
+    storage: &dyn Storage,
+    post_id: i32,
+    filename: &str,
+    bytes: &[u8],
+) -> Result<Attachment> {
+    if filename.is_empty() || filename.starts_with('.') || filename.chars().any(ends_reference) {
+        return Err(BlogError::InvalidAttachmentName(filename.to_string()));
+    }
+    let sha256 = sha256_hex(bytes);
+    let key = storage_key(&sha256, filename);
+
+    repo.transaction(|repo| {
+        let attachment = repo.add_attachment(&NewAttachment {
+            post_id,
+            filename,
+            mime_type: mime_type(filename),
+            size: bytes.len() as i64,
+            sha256: &sha256,
+            storage_key: &key,
+        })?;
+        storage.put(&key, bytes)?;
+        Ok(attachment)
+    })
+}
+
+/// Deletes an attachment, and its file unless another attachment has the
+/// same contents.
+pub fn detach(repo: &mut PostRepository, storage: &dyn Storage, attachment_id: i32) -> Result<Attachment> {
+    let attachment = repo.delete_attachment(attachment_id)?;
+    release(repo, storage, std::slice::from_ref(&attachment))?;
+    Ok(attachment)
+}
+
+/// Deletes the files of attachments that are gone, like the ones of a
+/// deleted post, keeping those still used by others.
+pub fn release(repo: &mut PostRepository, storage: &dyn Storage, gone: &[Attachment]) -> Result<()> {
+    for attachment in gone {
+        if repo.attachments_by_key(&attachment.storage_key)?.is_empty() {
+            storage.delete(&attachment.storage_key)?;
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the references to `attachments` in `body` with `url(attachment)`.
+/// References to files that aren't attached are left as they are.
+pub fn rewrite_references(body: &str, attachments: &[Attachment], url: impl Fn(&Attachment) -> String) -> String {
+    if attachments.is_empty() {
+        return body.to_string();
+    }
+
+    let mut out = String::with_capacity(body.len());
+    let mut rest = body;
+    while let Some(start) = rest.find(REFERENCE_PREFIX) {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + REFERENCE_PREFIX.len()..];
+        let end = after.find(ends_reference).unwrap_or(after.len());
+        match attachments.iter().find(|attachment| attachment.filename == after[..end]) {
+            Some(attachment) => out.push_str(&url(attachment)),
+            None => out.push_str(&rest[start..start + REFERENCE_PREFIX.len() + end]),
+        }
+        rest = &after[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Where contents with this digest are stored: named by the digest, so the
+/// same file attached twice is stored once, with the extension of
+/// `filename` kept for the servers that go by it.
+pub fn storage_key(sha256: &str, filename: &str) -> String {
+    match extension(filename) {
+        Some(extension) => format!("{}/{}.{}", &sha256[..2], sha256, extension),
+        None => format!("{}/{}", &sha256[..2], sha256),
+    }
+}
+
+fn extension(filename: &str) -> Option<String> {
+    let (_, extension) = filename.rsplit_once('.')?;
+    if extension.is_empty() || !extension.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    Some(extension.to_ascii_lowercase())
+}
+
+/// Whether a file of this type is safe to show in the browser: raster
+/// images, audio and video, none of which can run scripts.
+pub fn shows_inline(mime_type: &str) -> bool {
+    match mime_type.split_once('/') {
+        Some(("image", subtype)) => subtype != "svg+xml",
+        Some(("audio" | "video", _)) => true,
+        _ => false,
+    }
+}
+
+/// The MIME type of a file, going by its extension.
+pub fn mime_type(filename: &str) -> &'static str {
+    match extension(filename).as_deref() {
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("avif") => "image/avif",
+        Some("svg") => "image/svg+xml",
+        Some("ico") => "image/x-icon",
+        Some("mp4") => "video/mp4",
+        Some("webm") => "video/webm",
+        Some("mp3") => "audio/mpeg",
+        Some("ogg") => "audio/ogg",
+        Some("pdf") => "application/pdf",
+        Some("zip") => "application/zip",
+        Some("json") => "application/json",
+        Some("txt") => "text/plain",
+        Some("md") => "text/markdown",
+        Some("csv") => "text/csv",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn attachment(filename: &str) -> Attachment {
+        let sha256 = sha256_hex(filename.as_bytes());
+        Attachment {
+            id: 1,
+            post_id: 1,
+            filename: filename.to_string(),
+            mime_type: mime_type(filename).to_string(),
+            size: 3,
+            storage_key: storage_key(&sha256, filename),
+            sha256,
+            created_at: NaiveDate::from_ymd_opt(2024, 5, 2).unwrap().and_hms_opt(9, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn rewrites_references_to_attached_files() {
+        let files = [attachment("cat.png"), attachment("notes.pdf")];
+        let body = "![A cat](attachment:cat.png) and [notes](attachment:notes.pdf \"Notes\"), \
+                    not attachment:dog.png or attachment:";
+        let rewritten = rewrite_references(body, &files, |file| format!("/files/{}", file.filename));
+
+        assert_eq!(
+            "![A cat](/files/cat.png) and [notes](/files/notes.pdf \"Notes\"), not attachment:dog.png or attachment:",
+            rewritten
+        );
+    }
+
+    #[test]
+    fn names_storage_keys_by_contents() {
+        let sha256 = sha256_hex(b"abc");
+        assert_eq!("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad", sha256);
+        assert_eq!(format!("ba/{}.png", sha256), storage_key(&sha256, "Cat.PNG"));
+        assert_eq!(format!("ba/{}", sha256), storage_key(&sha256, "README"));
+        assert_eq!("image/jpeg", mime_type("photo.JPG"));
+        assert_eq!("application/octet-stream", mime_type("archive.tar.xz"));
+    }
+
+    #[test]
+    fn only_shows_media_inline() {
+        assert!(shows_inline("image/png"));
+        assert!(shows_inline("video/mp4"));
+        assert!(!shows_inline("image/svg+xml"));
+        assert!(!shows_inline("application/pdf"));
+        assert!(!shows_inline("text/plain"));
+    }
+}
@@ -0,0 +1,51 @@
+//! `blog-server` - the posts of the blog as a JSON API; see
+//! `diesel_rust::api` for the routes.
+//!
+//! Serves the database named by `DATABASE_URL`, with a pool sized by the
+//! `DATABASE_POOL_*` settings. Posts can only be changed with the token
+//! given in `BLOG_API_TOKEN`. Attached files are kept in and served from
+//! `BLOG_ATTACHMENTS_DIR`.
+
+use actix_web::{web, App, HttpServer};
+use clap::Parser;
+use diesel_rust::api::{self, ApiConfig};
+use diesel_rust::establish_blog_pool;
+use diesel_rust::storage::{LocalStorage, Storage};
+use std::io;
+use std::sync::Arc;
+
+#[derive(Parser)]
+#[command(name = "blog-server", version, about = "Serve the posts of the blog as JSON")]
+struct Cli {
+    /// Address to listen on
+    #[arg(long, env = "BLOG_API_ADDR", default_value = "127.0.0.1:8080")]
+    addr: String,
+    /// Bearer token for creating, changing and deleting posts
+    #[arg(long, env = "BLOG_API_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+}
+
+#[actix_web::main]
+async fn main() -> io::Result<()> {
+    dotenvy::dotenv().ok();
+    let cli = Cli::parse();
+
+    let pool = establish_blog_pool().map_err(io::Error::other)?;
+    if cli.token.is_none() {
+        eprintln!("BLOG_API_TOKEN isn't set, so posts can't be changed");
+    }
+    let config = ApiConfig { token: cli.token };
+    let storage: Arc<dyn Storage> = Arc::new(LocalStorage::from_env());
+
+    println!("Serving posts at http://{}/posts", cli.addr);
+    HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::new(config.clone()))
+            .app_data(web::Data::from(storage.clone()))
+            .configure(api::configure)
+    })
+    .bind(&cli.addr)?
+    .run()
+    .await
+}
@@ -0,0 +1,116 @@
+//! Editing posts as front matter documents in `$VISUAL` or `$EDITOR`.
+
+use crate::Failure;
+use diesel_rust::front_matter::{Document, FrontMatter};
+use diesel_rust::models::{Post, PostChanges, PostDetails};
+use diesel_rust::{error, PostRepository};
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::process::Command;
+
+/// The post as the document the user edits.
+pub fn document(details: &PostDetails) -> Document {
+    Document {
+        front_matter: FrontMatter {
+            title: details.post.title.clone(),
+            tags: details.tags.iter().map(|tag| tag.name.clone()).collect(),
+            published: details.post.is_published(),
+            author: details.author.as_ref().map(|author| author.email.clone()),
+        },
+        body: details.post.body.clone(),
+    }
+}
+
+/// Opens `text` in the user's editor and returns what they saved. The
+/// editor may come with arguments, like `code --wait`.
+pub fn edit(text: &str) -> Result<String, Failure> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut words = editor.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| Failure::Usage("$EDITOR is empty".to_string()))?;
+
+    let io_error = |err: std::io::Error| Failure::Usage(format!("Error preparing the file to edit: {}", err));
+    let mut file = tempfile::Builder::new()
+        .prefix("blog-")
+        .suffix(".md")
+        .tempfile()
+        .map_err(io_error)?;
+    file.write_all(text.as_bytes()).map_err(io_error)?;
+    file.flush().map_err(io_error)?;
+
+    let status = Command::new(program)
+        .args(words)
+        .arg(file.path())
+        .status()
+        .map_err(|err| Failure::Usage(format!("Error starting {}: {}", program, err)))?;
+    if !status.success() {
+        return Err(Failure::Usage(format!("{} exited with {}; nothing saved", program, status)));
+    }
+
+    let edited = fs::read_to_string(file.path()).map_err(io_error)?;
+    Ok(edited)
+}
+
+/// Parses an edited document, keeping the file around when it doesn't parse
+/// so the edits aren't lost.
+pub fn parse(edited: &str) -> Result<Document, Failure> {
+    Document::parse(edited).map_err(|err| keep_edits(edited, err))
+}
+
+/// A usage error saying `err`, and where `edited` was kept so the edits
+/// aren't lost.
+pub fn keep_edits(edited: &str, err: String) -> Failure {
+    let kept = tempfile::Builder::new()
+        .prefix("blog-")
+        .suffix(".md")
+        .tempfile()
+        .and_then(|mut file| {
+            file.write_all(edited.as_bytes())?;
+            file.keep().map_err(|err| err.error)
+        });
+    match kept {
+        Ok((_, path)) => Failure::Usage(format!("{}; your edits are in {}", err, path.display())),
+        Err(_) => Failure::Usage(err),
+    }
+}
+
+/// Saves a document over post `id`: title, body, tags, author and whether
+/// it is published. All of it or nothing is saved. Check
+/// [`FrontMatter::check_published`](diesel_rust::front_matter::FrontMatter::check_published)
+/// first for a clearer error than the repository's.
+pub fn save(repo: &mut PostRepository, current: &Post, doc: &Document) -> error::Result<Post> {
+    let front = &doc.front_matter;
+
+    repo.transaction(|repo| {
+        let mut post = if front.title != current.title || doc.body != current.body {
+            let changes = PostChanges {
+                title: Some(&front.title),
+                body: Some(&doc.body),
+            };
+            repo.update(current.id, &changes)?
+        } else {
+            repo.get(current.id)?
+        };
+
+        let author = front.author.as_deref().map(|email| repo.find_author(email)).transpose()?;
+        if author.as_ref().map(|author| author.id) != post.author_id {
+            post = repo.set_author(post.id, author.map(|author| author.id))?;
+        }
+
+        let tags: Vec<&str> = front.tags.iter().map(String::as_str).collect();
+        repo.set_tags(post.id, &tags)?;
+
+        if front.published != post.is_published() {
+            post = if front.published {
+                repo.publish(post.id)?
+            } else {
+                repo.unpublish(post.id)?
+            };
+        }
+        Ok(post)
+    })
+}
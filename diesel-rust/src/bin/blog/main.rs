@@ -0,0 +1,1295 @@
+//! `blog` - write, list and publish posts from the command line.
+//!
+//! Posts are kept in the database named by `DATABASE_URL`: a `postgres://`
+//! URL, or the path of a SQLite file. `blog migrate up` creates or upgrades
+//! its tables; the other commands refuse to run until it is up to date.
+//!
+//! Commands taking a post "by slug or id" look for the slug first, so a post
+//! titled "1984" is `1984`; `#1984` always means post id 1984.
+//!
+//! Exit codes: 0 on success, 1 when the database or a file fails, 2 for bad
+//! usage or input, like an attachment name that's taken or an export that
+//! can't be imported, 3 when the post asked for doesn't exist and 4 when the
+//! post can't take that step of review and publishing from where it is, like
+//! publishing a draft nobody approved.
+
+mod editor;
+
+use clap::{Args, Parser, Subcommand};
+use diesel_rust::models::PostChanges;
+use diesel_rust::*;
+use serde::Serialize;
+use diesel_rust::diff::diff_revisions;
+use diesel_rust::front_matter::Document;
+use diesel_rust::import::{self as importer, ImportFormat};
+use diesel_rust::migrations;
+use diesel_rust::attachments;
+use diesel_rust::storage::{LocalStorage, Storage};
+use diesel_rust::export::{self, Site, Templates};
+use diesel_rust::comments::CommentThread;
+use diesel_rust::models::{Author, Comment, NewComment, Post, PostDetails, PostReview, PostStatus, ReviewState};
+use diesel_rust::query::{parse_since, Sort, Visibility};
+use diesel_rust::search::{HIGHLIGHT_START, HIGHLIGHT_STOP};
+use diesel_rust::seed;
+use diesel_rust::snapshot::Snapshot;
+use chrono::{NaiveDateTime, NaiveTime, Utc};
+use std::io::{stdin, stdout, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "blog", version, about = "Manage the posts of the blog")]
+struct Cli {
+    /// Print results as JSON instead of text
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Write a new draft
+    New(NewArgs),
+    /// List posts
+    List(ListArgs),
+    /// Search titles and bodies: words, "a phrase", or, -word
+    Search(SearchArgs),
+    /// Show a post, by slug or id
+    Show { post: String },
+    /// Send a draft for review before it is published
+    Review { id: i32 },
+    /// Approve a post waiting for review, publishing it now or at --at
+    Approve(ApproveArgs),
+    /// Send a post waiting for review back to its author as a draft
+    Reject(RejectArgs),
+    /// Publish an approved post now, or schedule it with --at
+    Publish(PublishArgs),
+    /// Turn a post back into a draft
+    Unpublish { id: i32 },
+    /// Take a post out of listings without deleting it
+    Archive { id: i32 },
+    /// Publish the scheduled posts that are due
+    Scheduler(SchedulerArgs),
+    /// Change a post; opens it in $EDITOR when no changes are given
+    Edit(EditArgs),
+    /// Delete a post by id, or the posts whose title contains some text
+    Delete(DeleteArgs),
+    /// List the revisions of a post
+    History { id: i32 },
+    /// Show what changed between a revision and the current post
+    Diff(DiffArgs),
+    /// Bring back the title and body of an earlier revision
+    Revert { id: i32, revision: i32 },
+    /// Render the published posts to a static HTML site
+    Export(ExportArgs),
+    /// Fill an empty blog with fake posts, tags and comments
+    Seed(SeedArgs),
+    /// Write the whole blog to JSON, on stdout unless --out is given
+    Dump {
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Load a blog written by `blog dump` into an empty database
+    Load(LoadArgs),
+    /// Import posts from a directory of Markdown files, a WordPress export or
+    /// JSON, updating the posts with the same slugs
+    Import(ImportArgs),
+    /// Manage authors
+    #[command(subcommand)]
+    Author(AuthorCommand),
+    /// Attach files to posts, for linking from bodies as attachment:<name>
+    #[command(subcommand)]
+    Attachment(AttachmentCommand),
+    /// Read and moderate comments
+    #[command(subcommand)]
+    Comment(CommentCommand),
+    /// Bring the database schema up to date, or step it back
+    #[command(subcommand)]
+    Migrate(MigrateCommand),
+}
+
+#[derive(Subcommand)]
+enum AuthorCommand {
+    /// Add an author
+    Add { name: String, email: String },
+    /// List authors
+    List,
+}
+
+#[derive(Subcommand)]
+enum AttachmentCommand {
+    /// Attach a file to a post, by slug or id
+    Add {
+        post: String,
+        file: PathBuf,
+        /// Name to refer to it by instead of its file name
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// List the files attached to a post, by slug or id
+    List { post: String },
+    /// Delete an attachment by id
+    Delete { id: i32 },
+}
+
+#[derive(Subcommand)]
+enum MigrateCommand {
+    /// Run the migrations the database doesn't have yet
+    Up,
+    /// Revert the newest migration
+    Down,
+    /// List the migrations and whether each has been run
+    Status,
+    /// Revert the newest migration and run it again
+    Redo,
+}
+
+#[derive(Subcommand)]
+enum CommentCommand {
+    /// Show the comments on a post, by slug or id, as threads
+    List {
+        post: String,
+        /// Include comments waiting for approval
+        #[arg(long)]
+        all: bool,
+    },
+    /// List the comments waiting for approval, oldest first
+    Pending {
+        /// Maximum number of comments to show
+        #[arg(long, default_value_t = 20, value_parser = clap::value_parser!(i64).range(1..))]
+        limit: i64,
+    },
+    /// Add a comment to a post, by slug or id
+    Add(CommentArgs),
+    /// Approve a comment so it is shown
+    Approve { id: i32 },
+    /// Delete a comment and the replies to it
+    Delete { id: i32 },
+}
+
+#[derive(Args)]
+struct CommentArgs {
+    post: String,
+    /// Name of the commenter
+    #[arg(long)]
+    name: String,
+    /// Email of the commenter
+    #[arg(long)]
+    email: String,
+    /// Id of the comment this one replies to
+    #[arg(long)]
+    reply_to: Option<i32>,
+    /// Text of the comment; read from stdin when left out
+    #[arg(long)]
+    body: Option<String>,
+}
+
+#[derive(Args)]
+struct NewArgs {
+    /// Title of the post; asked for when left out
+    #[arg(long)]
+    title: Option<String>,
+    /// Body of the post; read from stdin when left out
+    #[arg(long)]
+    body: Option<String>,
+    /// Email of the author
+    #[arg(long)]
+    author: Option<String>,
+    /// Tag the post; repeat for several tags
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+    /// Import a Markdown file with YAML or TOML front matter
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["title", "body", "author", "tags"])]
+    from: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct PublishArgs {
+    id: i32,
+    /// Publish at this time, in UTC, instead of now (2024-05-01T09:00)
+    #[arg(long, value_parser = parse_since)]
+    at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Args)]
+struct ApproveArgs {
+    id: i32,
+    /// Email of the author approving the post
+    #[arg(long, value_name = "EMAIL")]
+    reviewer: String,
+    /// Publish at this time, in UTC, instead of now (2024-05-01T09:00)
+    #[arg(long, value_parser = parse_since)]
+    at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Args)]
+struct RejectArgs {
+    id: i32,
+    /// Email of the author rejecting the post
+    #[arg(long, value_name = "EMAIL")]
+    reviewer: String,
+}
+
+#[derive(Args)]
+struct SchedulerArgs {
+    /// Keep running, checking for due posts every this many seconds
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    interval: Option<u64>,
+}
+
+#[derive(Args)]
+struct ListArgs {
+    /// Include drafts, scheduled and archived posts
+    #[arg(long, conflicts_with_all = ["drafts", "archived"])]
+    all: bool,
+    /// Only list drafts and scheduled posts
+    #[arg(long, conflicts_with = "archived")]
+    drafts: bool,
+    /// Only list archived posts
+    #[arg(long)]
+    archived: bool,
+    /// Page to show, from 1
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(i64).range(1..))]
+    page: i64,
+    /// Number of posts per page
+    #[arg(long, visible_alias = "limit", default_value_t = 5, value_parser = clap::value_parser!(i64).range(1..))]
+    per_page: i64,
+    /// Show the posts after this post id instead of a page number
+    #[arg(long, conflicts_with = "page")]
+    after: Option<i32>,
+    /// Only posts with this tag
+    #[arg(long)]
+    tag: Option<String>,
+    /// Only posts by the author with this email
+    #[arg(long)]
+    author: Option<String>,
+    /// Only posts created since this date (2024-04-01 or 2024-04-01T12:30)
+    #[arg(long, value_parser = parse_since)]
+    since: Option<chrono::NaiveDateTime>,
+    /// Order: oldest, newest, updated or title
+    #[arg(long, default_value_t = Sort::Oldest)]
+    sort: Sort,
+}
+
+#[derive(Args)]
+struct SearchArgs {
+    query: String,
+    /// Include drafts
+    #[arg(long, conflicts_with = "drafts")]
+    all: bool,
+    /// Only search drafts
+    #[arg(long)]
+    drafts: bool,
+    /// Maximum number of posts to show
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(i64).range(1..))]
+    limit: i64,
+}
+
+#[derive(Args)]
+struct EditArgs {
+    id: i32,
+    #[arg(long)]
+    title: Option<String>,
+    #[arg(long)]
+    body: Option<String>,
+    /// Email of the new author
+    #[arg(long, conflicts_with = "no_author")]
+    author: Option<String>,
+    /// Remove the author
+    #[arg(long)]
+    no_author: bool,
+    /// Replace the tags; repeat for several tags
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+    /// Remove all tags
+    #[arg(long, conflicts_with = "tags")]
+    no_tags: bool,
+}
+
+#[derive(Args)]
+struct ExportArgs {
+    /// Directory to write the site to
+    #[arg(long, default_value = "./site")]
+    out: PathBuf,
+    /// Directory with templates to use instead of the built-in ones
+    #[arg(long)]
+    templates: Option<PathBuf>,
+    /// Absolute URL the site will be served from, used in the feed and sitemap
+    #[arg(long, env = "BLOG_BASE_URL", default_value = "http://localhost:8000")]
+    base_url: String,
+    /// Title of the site
+    #[arg(long, env = "BLOG_TITLE", default_value = "Blog")]
+    title: String,
+}
+
+#[derive(Args)]
+struct SeedArgs {
+    /// Number of posts to make
+    #[arg(long, default_value_t = 50, value_parser = clap::value_parser!(u32).range(1..))]
+    count: u32,
+    /// The same seed makes the same posts
+    #[arg(long, default_value_t = 42)]
+    seed: u64,
+    /// Delete everything in the blog first
+    #[arg(long)]
+    replace: bool,
+}
+
+#[derive(Args)]
+struct LoadArgs {
+    file: PathBuf,
+    /// Delete everything in the blog first
+    #[arg(long)]
+    replace: bool,
+}
+
+#[derive(Args)]
+struct ImportArgs {
+    /// A directory of Markdown files, or an export file
+    path: PathBuf,
+    /// markdown, wxr or json; guessed from the path when left out
+    #[arg(long)]
+    format: Option<ImportFormat>,
+    /// Report what would change without saving anything
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Args)]
+struct DiffArgs {
+    id: i32,
+    /// Revision to compare from
+    revision: i32,
+    /// Revision to compare to; the current post when left out
+    #[arg(long)]
+    to: Option<i32>,
+}
+
+#[derive(Args)]
+#[command(group = clap::ArgGroup::new("target").required(true).args(["id", "title"]))]
+struct DeleteArgs {
+    /// Id of the post to delete
+    id: Option<i32>,
+    /// Delete the posts whose title contains this text
+    #[arg(long)]
+    title: Option<String>,
+    /// List the posts that would be deleted without deleting them
+    #[arg(long)]
+    dry_run: bool,
+    /// Don't ask before deleting several posts
+    #[arg(short, long)]
+    yes: bool,
+}
+
+pub(crate) enum Failure {
+    Usage(String),
+    Blog(BlogError),
+}
+
+impl From<BlogError> for Failure {
+    fn from(err: BlogError) -> Self {
+        Failure::Blog(err)
+    }
+}
+
+impl Failure {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Failure::Usage(_)
+            | Failure::Blog(
+                BlogError::AttachmentExists { .. }
+                | BlogError::InvalidAttachmentName(_)
+                | BlogError::InvalidImport { .. },
+            ) => ExitCode::from(2),
+            Failure::Blog(
+                BlogError::NotFound(_)
+                | BlogError::SlugNotFound(_)
+                | BlogError::AuthorNotFound(_)
+                | BlogError::RevisionNotFound { .. }
+                | BlogError::CommentNotFound(_)
+                | BlogError::AttachmentNotFound(_),
+            ) => ExitCode::from(3),
+            Failure::Blog(BlogError::InvalidTransition { .. }) => ExitCode::from(4),
+            Failure::Blog(_) => ExitCode::from(1),
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Failure::Usage(message) => message.clone(),
+            Failure::Blog(err) => err.to_string(),
+        }
+    }
+}
+
+struct Output {
+    json: bool,
+}
+
+impl Output {
+    /// Prints `value` as JSON, or `text` otherwise.
+    fn print<T: Serialize>(&self, value: &T, text: impl FnOnce() -> String) {
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(value).expect("Error serializing output"));
+        } else {
+            println!("{}", text());
+        }
+    }
+}
+
+fn new_post(repo: &mut PostRepository, out: &Output, args: NewArgs) -> Result<(), Failure> {
+    if let Some(path) = &args.from {
+        return import(repo, out, path);
+    }
+
+    let interactive = stdin().is_terminal() && !out.json;
+
+    let title = match args.title {
+        Some(title) => title,
+        None => {
+            if interactive {
+                println!("What would you like your title to be?");
+            }
+            let mut title = String::new();
+            stdin()
+                .read_line(&mut title)
+                .map_err(|err| Failure::Usage(format!("Error reading title: {}", err)))?;
+            title.trim_end().to_string() // Remove the trailing newline
+        }
+    };
+    if title.is_empty() {
+        return Err(Failure::Usage("A post needs a title".to_string()));
+    }
+
+    let body = match args.body {
+        Some(body) => body,
+        None => {
+            if interactive {
+                println!("\nOk! Let's write {} (Press {} when finished)\n", title, EOF);
+            }
+            let mut body = String::new();
+            stdin()
+                .read_to_string(&mut body)
+                .map_err(|err| Failure::Usage(format!("Error reading body: {}", err)))?;
+            body
+        }
+    };
+
+    // All or nothing, so a mistyped author or a bad tag doesn't leave a
+    // half-made post.
+    let post = repo.transaction(|repo| {
+        let author = args.author.as_deref().map(|email| repo.find_author(email)).transpose()?;
+        let mut post = repo.create(&title, &body)?;
+        if let Some(author) = author {
+            post = repo.set_author(post.id, Some(author.id))?;
+        }
+        if !args.tags.is_empty() {
+            repo.set_tags(post.id, &tag_refs(&args.tags))?;
+        }
+        Ok(post)
+    })?;
+
+    out.print(&post, || {
+        format!("\nSaved draft {} with id {} at /{}", post.title, post.id, post.slug)
+    });
+    Ok(())
+}
+
+fn import(repo: &mut PostRepository, out: &Output, path: &Path) -> Result<(), Failure> {
+    let text = std::fs::read_to_string(path).map_err(BlogError::io(path))?;
+    let doc = Document::parse(&text)
+        .map_err(|err| Failure::Usage(format!("{}: {}", path.display(), err)))?;
+    doc.front_matter
+        .check_published(None)
+        .map_err(|err| Failure::Usage(format!("{}: {}", path.display(), err)))?;
+
+    let post = repo.transaction(|repo| {
+        let draft = repo.create(&doc.front_matter.title, &doc.body)?;
+        editor::save(repo, &draft, &doc)
+    })?;
+
+    out.print(&post, || format!("Imported {} with id {} at /{}", post.title, post.id, post.slug));
+    Ok(())
+}
+
+fn tag_refs(tags: &[String]) -> Vec<&str> {
+    tags.iter().map(String::as_str).collect()
+}
+
+fn visibility(all: bool, drafts: bool, archived: bool) -> Visibility {
+    match (all, drafts, archived) {
+        (true, _, _) => Visibility::All,
+        (_, true, _) => Visibility::Drafts,
+        (_, _, true) => Visibility::Archived,
+        _ => Visibility::Published,
+    }
+}
+
+/// What a post is, for the text output: nothing for a published post.
+fn status_label(post: &Post) -> Option<String> {
+    match post.status {
+        PostStatus::Published => None,
+        PostStatus::Draft if post.review == ReviewState::Pending => Some("waiting for review".to_string()),
+        PostStatus::Scheduled => Some(match post.publish_at {
+            Some(at) => format!("scheduled for {}", at.format("%Y-%m-%d %H:%M")),
+            None => "scheduled".to_string(),
+        }),
+        status => Some(status.to_string()),
+    }
+}
+
+fn list(repo: &mut PostRepository, out: &Output, args: ListArgs) -> Result<(), Failure> {
+    let mut query = PostQuery::new()
+        .visibility(visibility(args.all, args.drafts, args.archived))
+        .sort(args.sort);
+    query = match args.after {
+        Some(after) => query.after(after, args.per_page),
+        None => query.page(args.page, args.per_page),
+    };
+    if let Some(tag) = &args.tag {
+        query = query.tag(tag);
+    }
+    if let Some(email) = &args.author {
+        query = query.author(repo.find_author(email)?.id);
+    }
+    if let Some(since) = args.since {
+        query = query.since(since);
+    }
+
+    let page = repo.query(&query)?;
+
+    out.print(&page, || {
+        let mut text = format!("Displaying {} of {} posts", page.posts.len(), page.total);
+        if let Some(number) = page.page {
+            text.push_str(&format!(" (page {})", number));
+        }
+        for post in &page.posts {
+            let heading = match status_label(post) {
+                Some(label) => format!("{} ({})", post.title, label),
+                None => post.title.clone(),
+            };
+            text.push_str(&format!("\n{}\n-----------\n\n{}", heading, post.body));
+        }
+        if let Some(after) = page.next_after {
+            text.push_str(&format!("\n\nMore with --after {}", after));
+        }
+        text
+    });
+    Ok(())
+}
+
+fn search(repo: &mut PostRepository, out: &Output, args: SearchArgs) -> Result<(), Failure> {
+    let hits = repo.search(&args.query, visibility(args.all, args.drafts, false), args.limit)?;
+
+    // Bold the matches on a terminal; elsewhere mark them like *this*.
+    let (start, stop) = if stdout().is_terminal() { ("\x1b[1m", "\x1b[0m") } else { ("*", "*") };
+    out.print(&hits, || {
+        let mut text = format!("Found {} posts", hits.len());
+        for hit in &hits {
+            let snippet = hit.snippet.replace(HIGHLIGHT_START, start).replace(HIGHLIGHT_STOP, stop);
+            text.push_str(&format!("\n\n{} (#{}, /{})\n  {}", hit.post.title, hit.post.id, hit.post.slug, snippet));
+        }
+        text
+    });
+    Ok(())
+}
+
+/// The id of a post given by slug, id or `#<id>`.
+fn post_id(repo: &mut PostRepository, post: &str) -> Result<i32, Failure> {
+    Ok(repo.find(post)?.id)
+}
+
+fn show(repo: &mut PostRepository, out: &Output, post: &str) -> Result<(), Failure> {
+    let id = post_id(repo, post)?;
+    let details = repo.details(id)?;
+    let reviews = repo.reviews(id)?;
+
+    let shown = ShownPost {
+        details: &details,
+        reviews: reviews.iter().map(|(review, reviewer)| ShownReview { review, reviewer }).collect(),
+    };
+    out.print(&shown, || describe(&details, &reviews));
+    Ok(())
+}
+
+/// What `blog show --json` prints: the post's details and its approvals
+/// and rejections with their reviewers, like the text output.
+#[derive(Serialize)]
+struct ShownPost<'a> {
+    #[serde(flatten)]
+    details: &'a PostDetails,
+    reviews: Vec<ShownReview<'a>>,
+}
+
+#[derive(Serialize)]
+struct ShownReview<'a> {
+    #[serde(flatten)]
+    review: &'a PostReview,
+    reviewer: &'a Author,
+}
+
+fn describe(details: &PostDetails, reviews: &[(PostReview, Author)]) -> String {
+    let post = &details.post;
+    let status = match (status_label(post), post.published_at) {
+        (Some(label), _) => label,
+        (None, Some(at)) => format!("published {}", at.format("%Y-%m-%d %H:%M")),
+        (None, None) => "published".to_string(),
+    };
+
+    let mut text = format!("{} (#{}, {})\n/{}\n", post.title, post.id, status, post.slug);
+    if let Some(author) = &details.author {
+        text.push_str(&format!("by {} <{}>\n", author.name, author.email));
+    }
+    if !details.tags.is_empty() {
+        let names: Vec<&str> = details.tags.iter().map(|tag| tag.name.as_str()).collect();
+        text.push_str(&format!("tags: {}\n", names.join(", ")));
+    }
+    if !details.attachments.is_empty() {
+        let names: Vec<&str> = details.attachments.iter().map(|attachment| attachment.filename.as_str()).collect();
+        text.push_str(&format!("attachments: {}\n", names.join(", ")));
+    }
+    for (review, reviewer) in reviews {
+        text.push_str(&format!(
+            "{} by {} <{}> {}\n",
+            if review.approved { "approved" } else { "rejected" },
+            reviewer.name,
+            reviewer.email,
+            review.created_at.format("%Y-%m-%d %H:%M")
+        ));
+    }
+    text.push_str(&format!(
+        "created {}, updated {}\n-----------\n\n{}",
+        post.created_at.format("%Y-%m-%d %H:%M"),
+        post.updated_at.format("%Y-%m-%d %H:%M"),
+        post.body
+    ));
+    text
+}
+
+fn request_review(repo: &mut PostRepository, out: &Output, id: i32) -> Result<(), Failure> {
+    let post = repo.request_review(id)?;
+
+    out.print(&post, || format!("Sent post {} for review", post.title));
+    Ok(())
+}
+
+fn approve(repo: &mut PostRepository, out: &Output, args: ApproveArgs) -> Result<(), Failure> {
+    if let Some(at) = args.at {
+        check_future(at)?;
+    }
+    let reviewer = repo.find_author(&args.reviewer)?;
+    let post = repo.approve(args.id, reviewer.id, args.at)?;
+
+    out.print(&post, || match post.publish_at {
+        Some(at) => format!("Approved post {}, scheduled for {} UTC", post.title, at.format("%Y-%m-%d %H:%M")),
+        None => format!("Approved and published post {}", post.title),
+    });
+    Ok(())
+}
+
+fn reject(repo: &mut PostRepository, out: &Output, args: RejectArgs) -> Result<(), Failure> {
+    let reviewer = repo.find_author(&args.reviewer)?;
+    let post = repo.reject(args.id, reviewer.id)?;
+
+    out.print(&post, || format!("Sent post {} back to its author", post.title));
+    Ok(())
+}
+
+fn publish(repo: &mut PostRepository, out: &Output, args: PublishArgs) -> Result<(), Failure> {
+    let Some(at) = args.at else {
+        let post = repo.publish(args.id)?;
+        out.print(&post, || format!("Published post {}", post.title));
+        return Ok(());
+    };
+
+    check_future(at)?;
+    let post = repo.schedule(args.id, at)?;
+    out.print(&post, || {
+        format!("Scheduled post {} for {} UTC", post.title, at.format("%Y-%m-%d %H:%M"))
+    });
+    Ok(())
+}
+
+/// Refuses a `--at` that has already gone by.
+fn check_future(at: NaiveDateTime) -> Result<(), Failure> {
+    if at <= Utc::now().naive_utc() {
+        return Err(Failure::Usage(format!(
+            "{} is in the past; leave out --at to publish now",
+            at.format("%Y-%m-%d %H:%M")
+        )));
+    }
+    Ok(())
+}
+
+fn unpublish(repo: &mut PostRepository, out: &Output, id: i32) -> Result<(), Failure> {
+    let post = repo.unpublish(id)?;
+
+    out.print(&post, || format!("Unpublished post {}", post.title));
+    Ok(())
+}
+
+fn archive(repo: &mut PostRepository, out: &Output, id: i32) -> Result<(), Failure> {
+    let post = repo.archive(id)?;
+
+    out.print(&post, || format!("Archived post {}", post.title));
+    Ok(())
+}
+
+/// Publishes the due posts once, or every `--interval` seconds until
+/// stopped. Run it from cron without `--interval`.
+fn scheduler(repo: &mut PostRepository, out: &Output, args: SchedulerArgs) -> Result<(), Failure> {
+    loop {
+        let published = repo.publish_due(Utc::now().naive_utc())?;
+        if args.interval.is_none() || !published.is_empty() {
+            out.print(&published, || {
+                let mut text = format!("Published {} scheduled posts", published.len());
+                for post in &published {
+                    text.push_str(&format!("\n#{} {}", post.id, post.title));
+                }
+                text
+            });
+        }
+
+        match args.interval {
+            Some(secs) => std::thread::sleep(std::time::Duration::from_secs(secs)),
+            None => return Ok(()),
+        }
+    }
+}
+
+fn edit(repo: &mut PostRepository, out: &Output, args: EditArgs) -> Result<(), Failure> {
+    let retag = !args.tags.is_empty() || args.no_tags;
+    let reassign = args.author.is_some() || args.no_author;
+    if args.title.is_none() && args.body.is_none() && !retag && !reassign {
+        return edit_in_editor(repo, out, args.id);
+    }
+
+    // All or nothing, like `new`: a mistyped author leaves the title alone.
+    let post = repo.transaction(|repo| {
+        let mut post = if args.title.is_some() || args.body.is_some() {
+            let changes = PostChanges {
+                title: args.title.as_deref(),
+                body: args.body.as_deref(),
+            };
+            repo.update(args.id, &changes)?
+        } else {
+            repo.get(args.id)?
+        };
+        if reassign {
+            let author = args.author.as_deref().map(|email| repo.find_author(email)).transpose()?;
+            post = repo.set_author(post.id, author.map(|author| author.id))?;
+        }
+        if retag {
+            repo.set_tags(post.id, &tag_refs(&args.tags))?;
+        }
+        Ok(post)
+    })?;
+
+    out.print(&post, || format!("Updated post {}", post.title));
+    Ok(())
+}
+
+fn edit_in_editor(repo: &mut PostRepository, out: &Output, id: i32) -> Result<(), Failure> {
+    if !stdin().is_terminal() {
+        return Err(Failure::Usage(
+            "Nothing to change: pass --title, --body, --author or --tag, or run in a terminal to use $EDITOR"
+                .to_string(),
+        ));
+    }
+
+    let details = repo.details(id)?;
+    let before = editor::document(&details);
+    let edited = editor::edit(&before.to_yaml())?;
+    let after = editor::parse(&edited)?;
+    if after == before {
+        out.print(&details.post, || "No changes".to_string());
+        return Ok(());
+    }
+    after
+        .front_matter
+        .check_published(Some(&details.post))
+        .map_err(|err| editor::keep_edits(&edited, err))?;
+
+    let post = editor::save(repo, &details.post, &after)?;
+    out.print(&post, || format!("Updated post {}", post.title));
+    Ok(())
+}
+
+fn history(repo: &mut PostRepository, out: &Output, id: i32) -> Result<(), Failure> {
+    let revisions = repo.history(id)?;
+
+    out.print(&revisions, || {
+        revisions
+            .iter()
+            .map(|revision| {
+                format!(
+                    "r{}  {}  {}",
+                    revision.revision,
+                    revision.created_at.format("%Y-%m-%d %H:%M:%S"),
+                    revision.title
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    });
+    Ok(())
+}
+
+fn diff(repo: &mut PostRepository, out: &Output, args: DiffArgs) -> Result<(), Failure> {
+    let from = repo.revision(args.id, args.revision)?;
+    let to = match args.to {
+        Some(number) => repo.revision(args.id, number)?,
+        // The newest revision is the post as it is now.
+        None => repo
+            .history(args.id)?
+            .pop()
+            .ok_or(BlogError::NotFound(args.id))?,
+    };
+    let diff = diff_revisions(&from, &to);
+
+    out.print(
+        &serde_json::json!({ "from": from.revision, "to": to.revision, "diff": diff }),
+        || {
+            if diff.is_empty() {
+                format!("r{} and r{} are the same", from.revision, to.revision)
+            } else {
+                diff.trim_end().to_string()
+            }
+        },
+    );
+    Ok(())
+}
+
+fn revert(repo: &mut PostRepository, out: &Output, id: i32, revision: i32) -> Result<(), Failure> {
+    let post = repo.revert(id, revision)?;
+
+    out.print(&post, || format!("Reverted post {} to revision {}", post.title, revision));
+    Ok(())
+}
+
+fn export(repo: &mut PostRepository, out: &Output, args: ExportArgs) -> Result<(), Failure> {
+    let templates = Templates::load(args.templates.as_deref())?;
+    let site = Site::new(&args.title, &args.base_url);
+
+    let posts = repo.published_details()?;
+    let files = export::build(&site, &templates, &posts);
+    export::write(&args.out, &files)?;
+    let copied = export::copy_attachments(&args.out, &LocalStorage::from_env(), &posts)?;
+
+    let paths: Vec<PathBuf> = files.into_iter().map(|(path, _)| args.out.join(path)).chain(copied).collect();
+    out.print(&paths, || format!("Wrote {} files to {}", paths.len(), args.out.display()));
+    Ok(())
+}
+
+/// Loads `snapshot` in one transaction. Ids are kept, so the blog has to be
+/// empty, or emptied with `replace`.
+fn load_snapshot(repo: &mut PostRepository, out: &Output, snapshot: &Snapshot, replace: bool) -> Result<(), Failure> {
+    if !replace && !repo.is_empty()? {
+        return Err(Failure::Usage(
+            "The blog already has posts or authors; use --replace to delete them first".to_string(),
+        ));
+    }
+    repo.transaction(|repo| {
+        if replace {
+            repo.clear()?;
+        }
+        repo.load(snapshot)
+    })?;
+
+    out.print(
+        &serde_json::json!({
+            "authors": snapshot.authors.len(),
+            "posts": snapshot.posts.len(),
+            "tags": snapshot.tags.len(),
+            "revisions": snapshot.revisions.len(),
+            "comments": snapshot.comments.len(),
+            "attachments": snapshot.attachments.len(),
+            "reviews": snapshot.reviews.len(),
+        }),
+        || format!("Loaded {}", snapshot.summary()),
+    );
+    Ok(())
+}
+
+fn seed(repo: &mut PostRepository, out: &Output, args: SeedArgs) -> Result<(), Failure> {
+    // Dates run up to the start of today, so a seed makes the same blog all day.
+    let until = Utc::now().date_naive().and_time(NaiveTime::MIN);
+    let snapshot = seed::generate(args.count as usize, args.seed, until);
+
+    load_snapshot(repo, out, &snapshot, args.replace)
+}
+
+fn dump(repo: &mut PostRepository, path: Option<&Path>) -> Result<(), Failure> {
+    let snapshot = repo.dump()?;
+    match path {
+        Some(path) => {
+            std::fs::write(path, snapshot.to_json()).map_err(BlogError::io(path))?;
+            eprintln!("Wrote {} to {}", snapshot.summary(), path.display());
+        }
+        None => println!("{}", snapshot.to_json()),
+    }
+    Ok(())
+}
+
+fn load(repo: &mut PostRepository, out: &Output, args: LoadArgs) -> Result<(), Failure> {
+    let text = std::fs::read_to_string(&args.file).map_err(BlogError::io(&args.file))?;
+    let snapshot = Snapshot::from_json(&text)
+        .map_err(|err| Failure::Usage(format!("{}: {}", args.file.display(), err)))?;
+
+    load_snapshot(repo, out, &snapshot, args.replace)
+}
+
+fn import_posts(repo: &mut PostRepository, out: &Output, args: ImportArgs) -> Result<(), Failure> {
+    let format = match args.format.or_else(|| ImportFormat::detect(&args.path)) {
+        Some(format) => format,
+        None => {
+            return Err(Failure::Usage(format!(
+                "Can't tell what kind of export {} is; pass --format markdown, wxr or json",
+                args.path.display()
+            )))
+        }
+    };
+    let posts = importer::read(&args.path, format)?;
+    let report = importer::import(repo, &posts, args.dry_run)?;
+
+    out.print(&report, || {
+        let verbs = if report.dry_run { ["Would create", "would update", "would skip"] } else { ["Created", "updated", "skipped"] };
+        let mut text = format!(
+            "{} {}, {} {}, {} {} unchanged posts",
+            verbs[0],
+            report.created.len(),
+            verbs[1],
+            report.updated.len(),
+            verbs[2],
+            report.skipped.len()
+        );
+        if !report.authors.is_empty() {
+            let verb = if report.dry_run { "Would add" } else { "Added" };
+            text.push_str(&format!("\n{} authors {}", verb, report.authors.join(", ")));
+        }
+        for (label, slugs) in [("new", &report.created), ("changed", &report.updated)] {
+            for slug in slugs {
+                text.push_str(&format!("\n  {} /{}", label, slug));
+            }
+        }
+        text
+    });
+    Ok(())
+}
+
+fn confirm(question: &str) -> Result<bool, Failure> {
+    print!("{} [y/N] ", question);
+    stdout().flush().ok();
+
+    let mut answer = String::new();
+    stdin()
+        .read_line(&mut answer)
+        .map_err(|err| Failure::Usage(format!("Error reading answer: {}", err)))?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+fn delete(repo: &mut PostRepository, out: &Output, args: DeleteArgs) -> Result<(), Failure> {
+    let matches = match (args.id, &args.title) {
+        (Some(id), _) => vec![repo.get(id)?],
+        (None, Some(text)) => repo.find_by_title(text)?,
+        (None, None) => unreachable!("clap requires an id or --title"),
+    };
+
+    let listing = |matches: &[Post]| {
+        matches
+            .iter()
+            .map(|post| format!("  #{} {}", post.id, post.title))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    if args.dry_run || matches.is_empty() {
+        out.print(&serde_json::json!({ "deleted": 0, "matched": matches }), || {
+            if matches.is_empty() {
+                "No posts match".to_string()
+            } else {
+                format!("Would delete {} posts:\n{}", matches.len(), listing(&matches))
+            }
+        });
+        return Ok(());
+    }
+
+    if matches.len() > 1 && !args.yes {
+        if !stdin().is_terminal() || out.json {
+            return Err(Failure::Usage(format!(
+                "{} posts match; pass --yes to delete them all",
+                matches.len()
+            )));
+        }
+        println!("{}", listing(&matches));
+        if !confirm(&format!("Delete these {} posts?", matches.len()))? {
+            println!("Nothing deleted");
+            return Ok(());
+        }
+    }
+
+    let ids: Vec<i32> = matches.iter().map(|post| post.id).collect();
+    let attached = repo.attachments_of(&ids)?;
+    let num_deleted = repo.delete_many(&ids)?;
+    attachments::release(repo, &LocalStorage::from_env(), &attached)?;
+
+    out.print(&serde_json::json!({ "deleted": num_deleted, "matched": matches }), || {
+        format!("Deleted {} posts", num_deleted)
+    });
+    Ok(())
+}
+
+fn author(repo: &mut PostRepository, out: &Output, command: AuthorCommand) -> Result<(), Failure> {
+    match command {
+        AuthorCommand::Add { name, email } => {
+            let author = repo.create_author(&name, &email)?;
+            out.print(&author, || format!("Added author {} with id {}", author.name, author.id));
+        }
+        AuthorCommand::List => {
+            let authors = repo.authors()?;
+            out.print(&authors, || {
+                authors
+                    .iter()
+                    .map(|author| format!("#{} {} <{}>", author.id, author.name, author.email))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            });
+        }
+    }
+    Ok(())
+}
+
+fn attachment(repo: &mut PostRepository, out: &Output, command: AttachmentCommand) -> Result<(), Failure> {
+    let storage = LocalStorage::from_env();
+    match command {
+        AttachmentCommand::Add { post, file, name } => {
+            let name = match name {
+                Some(name) => name,
+                None => file
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .ok_or_else(|| Failure::Usage(format!("{} has no file name; pass --name", file.display())))?
+                    .to_string(),
+            };
+            let bytes = std::fs::read(&file).map_err(BlogError::io(&file))?;
+            let id = post_id(repo, &post)?;
+            let attachment = attachments::attach(repo, &storage, id, &name, &bytes)?;
+            out.print(&attachment, || {
+                format!(
+                    "Attached {} ({} bytes) with id {}; link to it as attachment:{}",
+                    attachment.filename, attachment.size, attachment.id, attachment.filename
+                )
+            });
+        }
+        AttachmentCommand::List { post } => {
+            let id = post_id(repo, &post)?;
+            let attached = repo.attachments(id)?;
+            out.print(&attached, || {
+                let mut text = format!("{} attachments", attached.len());
+                for attachment in &attached {
+                    text.push_str(&format!(
+                        "\n#{} {} ({}, {} bytes) {}",
+                        attachment.id,
+                        attachment.filename,
+                        attachment.mime_type,
+                        attachment.size,
+                        storage.url(&attachment.storage_key)
+                    ));
+                }
+                text
+            });
+        }
+        AttachmentCommand::Delete { id } => {
+            let attachment = attachments::detach(repo, &storage, id)?;
+            out.print(&attachment, || format!("Deleted attachment {}", attachment.filename));
+        }
+    }
+    Ok(())
+}
+
+fn migrate(conn: &mut BlogConnection, out: &Output, command: MigrateCommand) -> Result<(), Failure> {
+    match command {
+        MigrateCommand::Up => {
+            let applied = migrations::run_pending(conn)?;
+            out.print(&applied, || {
+                if applied.is_empty() {
+                    "The database is up to date".to_string()
+                } else {
+                    applied.iter().map(|name| format!("Applied {}", name)).collect::<Vec<_>>().join("\n")
+                }
+            });
+        }
+        MigrateCommand::Down => {
+            let reverted = migrations::revert_last(conn)?;
+            out.print(&reverted, || match &reverted {
+                Some(name) => format!("Reverted {}", name),
+                None => "No migration has been run".to_string(),
+            });
+        }
+        MigrateCommand::Status => {
+            let status = migrations::status(conn)?;
+            out.print(&status, || {
+                status
+                    .iter()
+                    .map(|migration| format!("[{}] {}", if migration.applied { "X" } else { " " }, migration.name))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            });
+        }
+        MigrateCommand::Redo => {
+            let redone = migrations::redo(conn)?;
+            out.print(&redone, || match &redone {
+                Some(name) => format!("Redid {}", name),
+                None => "No migration has been run".to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The id, author and date of a comment on one line.
+fn comment_line(comment: &Comment) -> String {
+    let mut line = format!(
+        "#{} {} <{}> on {}",
+        comment.id,
+        comment.author_name,
+        comment.author_email,
+        comment.created_at.format("%Y-%m-%d %H:%M")
+    );
+    if !comment.approved {
+        line.push_str(" (pending)");
+    }
+    line
+}
+
+fn describe_threads(threads: &[CommentThread], depth: usize, text: &mut String) {
+    let indent = "  ".repeat(depth);
+    for thread in threads {
+        text.push_str(&format!("\n{}{}", indent, comment_line(&thread.comment)));
+        for line in thread.comment.body.lines() {
+            text.push_str(&format!("\n{}  {}", indent, line));
+        }
+        describe_threads(&thread.replies, depth + 1, text);
+    }
+}
+
+fn comment(repo: &mut PostRepository, out: &Output, command: CommentCommand) -> Result<(), Failure> {
+    match command {
+        CommentCommand::List { post, all } => {
+            let id = post_id(repo, &post)?;
+            let threads = repo.comments(id, all)?;
+            out.print(&threads, || {
+                let count: usize = threads.iter().map(CommentThread::count).sum();
+                let mut text = format!("{} comments", count);
+                describe_threads(&threads, 0, &mut text);
+                text
+            });
+        }
+        CommentCommand::Pending { limit } => {
+            let pending = repo.pending_comments(limit)?;
+            out.print(&pending, || {
+                let mut text = format!("{} comments waiting for approval", pending.len());
+                for comment in &pending {
+                    text.push_str(&format!("\n\n{} on post {}\n  {}", comment_line(comment), comment.post_id, comment.body));
+                }
+                text
+            });
+        }
+        CommentCommand::Add(args) => {
+            let body = match args.body {
+                Some(body) => body,
+                None => {
+                    let mut body = String::new();
+                    stdin()
+                        .read_to_string(&mut body)
+                        .map_err(|err| Failure::Usage(format!("Error reading comment: {}", err)))?;
+                    body
+                }
+            };
+            if body.trim().is_empty() {
+                return Err(Failure::Usage("A comment needs some text".to_string()));
+            }
+
+            let new_comment = NewComment {
+                post_id: post_id(repo, &args.post)?,
+                parent_id: args.reply_to,
+                author_name: &args.name,
+                author_email: &args.email,
+                body: &body,
+            };
+            let comment = repo.add_comment(&new_comment)?;
+            out.print(&comment, || format!("Added comment {}, waiting for approval", comment.id));
+        }
+        CommentCommand::Approve { id } => {
+            let comment = repo.approve_comment(id)?;
+            out.print(&comment, || format!("Approved comment {}", comment.id));
+        }
+        CommentCommand::Delete { id } => {
+            let comment = repo.delete_comment(id)?;
+            out.print(&comment, || format!("Deleted comment {} and its replies", comment.id));
+        }
+    }
+    Ok(())
+}
+
+fn run(cli: Cli) -> Result<(), Failure> {
+    let out = Output { json: cli.json };
+    // Every other command refuses to run on a database that isn't migrated.
+    let command = match cli.command {
+        Command::Migrate(command) => {
+            return migrate(&mut BlogConnection::establish(&database_url()?)?, &out, command);
+        }
+        command => command,
+    };
+    let connection = &mut try_establish_connection()?;
+    let repo = &mut PostRepository::new(connection);
+
+    match command {
+        Command::New(args) => new_post(repo, &out, args),
+        Command::List(args) => list(repo, &out, args),
+        Command::Search(args) => search(repo, &out, args),
+        Command::Show { post } => show(repo, &out, &post),
+        Command::Review { id } => request_review(repo, &out, id),
+        Command::Approve(args) => approve(repo, &out, args),
+        Command::Reject(args) => reject(repo, &out, args),
+        Command::Publish(args) => publish(repo, &out, args),
+        Command::Unpublish { id } => unpublish(repo, &out, id),
+        Command::Archive { id } => archive(repo, &out, id),
+        Command::Scheduler(args) => scheduler(repo, &out, args),
+        Command::Edit(args) => edit(repo, &out, args),
+        Command::Delete(args) => delete(repo, &out, args),
+        Command::History { id } => history(repo, &out, id),
+        Command::Diff(args) => diff(repo, &out, args),
+        Command::Revert { id, revision } => revert(repo, &out, id, revision),
+        Command::Export(args) => export(repo, &out, args),
+        Command::Seed(args) => seed(repo, &out, args),
+        Command::Dump { out: path } => dump(repo, path.as_deref()),
+        Command::Load(args) => load(repo, &out, args),
+        Command::Import(args) => import_posts(repo, &out, args),
+        Command::Author(command) => author(repo, &out, command),
+        Command::Attachment(command) => attachment(repo, &out, command),
+        Command::Comment(command) => comment(repo, &out, command),
+        Command::Migrate(_) => unreachable!("handled before connecting"),
+    }
+}
+
+fn main() -> ExitCode {
+    // Options like --base-url can come from `.env` too.
+    dotenvy::dotenv().ok();
+
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => {
+            eprintln!("{}", failure.message());
+            failure.exit_code()
+        }
+    }
+}
+
+#[cfg(not(windows))]
+const EOF: &str = "CTRL+D";
+
+#[cfg(windows)]
+const EOF: &str = "CTRL+Z";
@@ -1,22 +0,0 @@
-use diesel::prelude::*;
-use diesel_rust::*;
-use diesel_rust::models::*;
-
-fn main() {
-    use self::schema::posts::dsl::*;
-
-    let connection = &mut establish_connection();
-    let results = posts
-        .filter(published.eq(true))
-        .limit(5)
-        .select(Post::as_select())
-        .load(connection)
-        .expect("Error loading posts");
-
-    println!("Displaying {} posts", results.len());
-    for post in results {
-        println!("{}", post.title);
-        println!("-----------\n");
-        println!("{}", post.body);
-    }
-}
\ No newline at end of file
@@ -0,0 +1,91 @@
+//! Threading comments into the replies they were written as.
+
+use crate::models::Comment;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// A comment with the replies to it, oldest first.
+#[derive(Serialize)]
+pub struct CommentThread {
+    #[serde(flatten)]
+    pub comment: Comment,
+    pub replies: Vec<CommentThread>,
+}
+
+impl CommentThread {
+    /// The number of comments in the thread, this one included.
+    pub fn count(&self) -> usize {
+        1 + self.replies.iter().map(CommentThread::count).sum::<usize>()
+    }
+}
+
+/// Arranges the comments of a post into threads, keeping their order among
+/// siblings. Replies whose parent isn't in `comments`, like replies to a
+/// comment still waiting for approval, are left out with it.
+pub fn thread(comments: Vec<Comment>) -> Vec<CommentThread> {
+    let mut children: HashMap<Option<i32>, Vec<Comment>> = HashMap::new();
+    for comment in comments {
+        children.entry(comment.parent_id).or_default().push(comment);
+    }
+
+    fn build(parent: Option<i32>, children: &mut HashMap<Option<i32>, Vec<Comment>>) -> Vec<CommentThread> {
+        children
+            .remove(&parent)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|comment| {
+                let replies = build(Some(comment.id), children);
+                CommentThread { comment, replies }
+            })
+            .collect()
+    }
+
+    build(None, &mut children)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn comment(id: i32, parent_id: Option<i32>) -> Comment {
+        Comment {
+            id,
+            post_id: 1,
+            parent_id,
+            author_name: "Ada".to_string(),
+            author_email: "ada@example.com".to_string(),
+            body: format!("Comment {}", id),
+            approved: true,
+            created_at: NaiveDate::from_ymd_opt(2024, 4, 30).unwrap().and_hms_opt(9, 0, 0).unwrap(),
+        }
+    }
+
+    fn ids(threads: &[CommentThread]) -> Vec<(i32, Vec<i32>)> {
+        threads
+            .iter()
+            .map(|thread| (thread.comment.id, thread.replies.iter().map(|reply| reply.comment.id).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn nests_replies_under_their_parents() {
+        let threads = thread(vec![
+            comment(1, None),
+            comment(2, Some(1)),
+            comment(3, None),
+            comment(4, Some(2)),
+            comment(5, Some(1)),
+        ]);
+
+        assert_eq!(vec![(1, vec![2, 5]), (3, vec![])], ids(&threads));
+        assert_eq!(vec![4], threads[0].replies[0].replies.iter().map(|reply| reply.comment.id).collect::<Vec<_>>());
+        assert_eq!(4, threads[0].count());
+    }
+
+    #[test]
+    fn drops_replies_to_missing_comments() {
+        let threads = thread(vec![comment(1, None), comment(3, Some(2)), comment(4, Some(3))]);
+        assert_eq!(vec![(1, vec![])], ids(&threads));
+    }
+}
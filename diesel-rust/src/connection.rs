@@ -0,0 +1,117 @@
+//! A connection to whichever database the blog is kept in.
+//!
+//! Queries are written once and compiled for every backend with
+//! [`dispatch!`], which matches on the connection and runs the same code
+//! with the concrete connection type. Diesel's own `MultiConnection` can't
+//! be used: it has no `ON CONFLICT`, and full-text search needs different
+//! SQL on each backend.
+
+use crate::error::Result;
+use diesel::connection::{AnsiTransactionManager, TransactionManager};
+use diesel::prelude::*;
+
+#[cfg(feature = "postgres")]
+use diesel::pg::PgConnection;
+#[cfg(feature = "sqlite")]
+use diesel::{connection::SimpleConnection, sqlite::SqliteConnection};
+
+pub enum BlogConnection {
+    #[cfg(feature = "postgres")]
+    Pg(PgConnection),
+    #[cfg(feature = "sqlite")]
+    Sqlite(SqliteConnection),
+}
+
+/// Evaluates `$body` with `$conn` bound to the concrete connection inside a
+/// [`BlogConnection`].
+macro_rules! dispatch {
+    ($connection:expr, $conn:ident => $body:expr) => {
+        match $connection {
+            #[cfg(feature = "postgres")]
+            $crate::connection::BlogConnection::Pg($conn) => $body,
+            #[cfg(feature = "sqlite")]
+            $crate::connection::BlogConnection::Sqlite($conn) => $body,
+        }
+    };
+}
+
+pub(crate) use dispatch;
+
+/// Tells Postgres URLs from SQLite database files.
+fn is_postgres_url(url: &str) -> bool {
+    url.starts_with("postgres://") || url.starts_with("postgresql://")
+}
+
+impl BlogConnection {
+    /// Connects to Postgres for `postgres://` and `postgresql://` URLs, and
+    /// opens a SQLite database file for anything else, like `blog.db` or
+    /// `sqlite://blog.db`.
+    pub fn establish(url: &str) -> Result<Self> {
+        if is_postgres_url(url) {
+            #[cfg(feature = "postgres")]
+            return Ok(BlogConnection::Pg(PgConnection::establish(url)?));
+            #[cfg(not(feature = "postgres"))]
+            return Err(crate::BlogError::Config(
+                "DATABASE_URL is a Postgres URL, but this build has no postgres feature".to_string(),
+            ));
+        }
+
+        #[cfg(feature = "sqlite")]
+        {
+            let path = url.strip_prefix("sqlite://").unwrap_or(url);
+            let mut conn = SqliteConnection::establish(path)?;
+            // SQLite leaves foreign keys unchecked unless asked, per connection.
+            conn.batch_execute("PRAGMA foreign_keys = ON; PRAGMA busy_timeout = 5000;")?;
+            Ok(BlogConnection::Sqlite(conn))
+        }
+        #[cfg(not(feature = "sqlite"))]
+        Err(crate::BlogError::Config(format!(
+            "DATABASE_URL '{}' isn't a Postgres URL, and this build has no sqlite feature",
+            url
+        )))
+    }
+
+    /// `"postgres"` or `"sqlite"`.
+    pub fn backend(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "postgres")]
+            BlogConnection::Pg(_) => "postgres",
+            #[cfg(feature = "sqlite")]
+            BlogConnection::Sqlite(_) => "sqlite",
+        }
+    }
+
+    /// Runs `f` in a transaction, like [`Connection::transaction`]: it is
+    /// committed when `f` succeeds and rolled back when it fails. Nested
+    /// calls use savepoints.
+    pub fn transaction<T, E, F>(&mut self, f: F) -> std::result::Result<T, E>
+    where
+        F: FnOnce(&mut Self) -> std::result::Result<T, E>,
+        E: From<diesel::result::Error>,
+    {
+        dispatch!(self, conn => AnsiTransactionManager::begin_transaction(conn))?;
+        match f(self) {
+            Ok(value) => {
+                dispatch!(self, conn => AnsiTransactionManager::commit_transaction(conn))?;
+                Ok(value)
+            }
+            Err(err) => match dispatch!(self, conn => AnsiTransactionManager::rollback_transaction(conn)) {
+                Ok(()) | Err(diesel::result::Error::BrokenTransactionManager) => Err(err),
+                Err(rollback_error) => Err(rollback_error.into()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tells_backends_apart() {
+        assert!(is_postgres_url("postgres://localhost/blog"));
+        assert!(is_postgres_url("postgresql://user@localhost/blog"));
+        assert!(!is_postgres_url("blog.db"));
+        assert!(!is_postgres_url("sqlite://blog.db"));
+    }
+}
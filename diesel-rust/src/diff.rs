@@ -0,0 +1,69 @@
+//! Line diffs between post revisions.
+
+use crate::models::Revision;
+use similar::TextDiff;
+
+/// A unified diff from one revision to another, like `diff -u`. A changed
+/// title is shown as a header line above the body diff.
+pub fn diff_revisions(from: &Revision, to: &Revision) -> String {
+    let mut text = String::new();
+    if from.title != to.title {
+        text.push_str(&format!("title: {} -> {}\n", from.title, to.title));
+    }
+
+    text.push_str(&unified(
+        &from.body,
+        &to.body,
+        &format!("r{}", from.revision),
+        &format!("r{}", to.revision),
+    ));
+    text
+}
+
+/// A unified line diff of two texts with three lines of context. Empty when
+/// they are equal.
+pub fn unified(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    if old == new {
+        return String::new();
+    }
+
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(old_name, new_name)
+        .missing_newline_hint(false)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn revision(revision: i32, title: &str, body: &str) -> Revision {
+        Revision {
+            id: revision,
+            post_id: 1,
+            revision,
+            title: title.to_string(),
+            body: body.to_string(),
+            created_at: chrono::NaiveDateTime::default(),
+        }
+    }
+
+    #[test]
+    fn diffs_changed_lines() {
+        let diff = unified("one\ntwo\nthree\n", "one\n2\nthree\n", "r1", "r2");
+        assert_eq!("--- r1\n+++ r2\n@@ -1,3 +1,3 @@\n one\n-two\n+2\n three\n", diff);
+    }
+
+    #[test]
+    fn equal_texts_have_no_diff() {
+        assert_eq!("", unified("same\n", "same\n", "r1", "r2"));
+    }
+
+    #[test]
+    fn shows_a_changed_title() {
+        let diff = diff_revisions(&revision(1, "Draft", "Hello\n"), &revision(2, "Final", "Hello\n"));
+        assert_eq!("title: Draft -> Final\n", diff);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::models::{Post, ReviewState, Transition};
+use diesel::result::{ConnectionError, Error as DieselError};
+use std::error::Error;
+use std::path::PathBuf;
+use std::{fmt, io};
+
+/// Everything that can go wrong talking to the blog database.
+#[derive(Debug)]
+pub enum BlogError {
+    /// `DATABASE_URL` isn't set in the environment or `.env`.
+    MissingDatabaseUrl,
+    /// Some other setting is invalid.
+    Config(String),
+    /// The database couldn't be reached.
+    Connection(ConnectionError),
+    /// No pooled connection could be handed out.
+    Pool(diesel::r2d2::PoolError),
+    #[cfg(feature = "async")]
+    AsyncPool(diesel_async::pooled_connection::deadpool::PoolError),
+    /// There is no post with this id.
+    NotFound(i32),
+    /// There is no post with this slug.
+    SlugNotFound(String),
+    /// There is no author with this email.
+    AuthorNotFound(String),
+    /// The post has no revision with this number.
+    RevisionNotFound { post_id: i32, revision: i32 },
+    /// There is no comment with this id, or not on the post it was looked
+    /// for on.
+    CommentNotFound(i32),
+    /// There is no attachment with this id.
+    AttachmentNotFound(i32),
+    /// The post already has an attachment with this file name.
+    AttachmentExists { post_id: i32, filename: String },
+    /// This file name can't be referred to from a body.
+    InvalidAttachmentName(String),
+    /// The publishing workflow doesn't let the post take this step from
+    /// where it is, described by `stage`.
+    InvalidTransition { post_id: i32, stage: &'static str, review: ReviewState, transition: Transition },
+    /// A post to import couldn't be read from `source`, a file or an entry
+    /// of an export.
+    InvalidImport { source: String, message: String },
+    /// A query failed.
+    Database(DieselError),
+    /// Running or reverting a migration failed.
+    Migration(Box<dyn Error + Send + Sync>),
+    /// The database is missing these migrations, so its schema is older
+    /// than the one this build expects.
+    PendingMigrations(Vec<String>),
+    /// A file couldn't be read or written.
+    Io { path: PathBuf, source: io::Error },
+}
+
+pub type Result<T> = std::result::Result<T, BlogError>;
+
+impl fmt::Display for BlogError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BlogError::MissingDatabaseUrl => write!(f, "DATABASE_URL must be set"),
+            BlogError::Config(message) => write!(f, "{}", message),
+            BlogError::Connection(err) => write!(f, "Error connecting to the database: {}", err),
+            BlogError::Pool(err) => write!(f, "Error getting a pooled connection: {}", err),
+            #[cfg(feature = "async")]
+            BlogError::AsyncPool(err) => write!(f, "Error getting a pooled connection: {}", err),
+            BlogError::NotFound(id) => write!(f, "Unable to find post {}", id),
+            BlogError::SlugNotFound(slug) => write!(f, "Unable to find post '{}'", slug),
+            BlogError::AuthorNotFound(email) => write!(f, "Unable to find author {}", email),
+            BlogError::RevisionNotFound { post_id, revision } => {
+                write!(f, "Post {} has no revision {}", post_id, revision)
+            }
+            BlogError::CommentNotFound(id) => write!(f, "Unable to find comment {}", id),
+            BlogError::AttachmentNotFound(id) => write!(f, "Unable to find attachment {}", id),
+            BlogError::AttachmentExists { post_id, filename } => {
+                write!(f, "Post {} already has an attachment named {}", post_id, filename)
+            }
+            BlogError::InvalidAttachmentName(name) => write!(
+                f,
+                "'{}' can't be an attachment name: use one without spaces, slashes, quotes or brackets",
+                name
+            ),
+            BlogError::InvalidTransition { post_id, stage, review, transition } => {
+                write!(f, "Post {} can't be {} while it is {}", post_id, transition, stage)?;
+                match (transition, review) {
+                    (Transition::Publish | Transition::Schedule, ReviewState::Unreviewed) => {
+                        write!(f, ": send it for review and have it approved first")
+                    }
+                    (Transition::Publish | Transition::Schedule, ReviewState::Pending) => {
+                        write!(f, ": it has to be approved first")
+                    }
+                    _ => Ok(()),
+                }
+            }
+            BlogError::InvalidImport { source, message } => write!(f, "{}: {}", source, message),
+            BlogError::Database(err) => write!(f, "Database error: {}", err),
+            BlogError::Migration(err) => write!(f, "Migration error: {}", err),
+            BlogError::PendingMigrations(names) => write!(
+                f,
+                "The database schema is out of date, run `blog migrate up` to apply {}",
+                names.join(", ")
+            ),
+            BlogError::Io { path, source } => write!(f, "Error accessing {}: {}", path.display(), source),
+        }
+    }
+}
+
+impl Error for BlogError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BlogError::Connection(err) => Some(err),
+            BlogError::Pool(err) => Some(err),
+            #[cfg(feature = "async")]
+            BlogError::AsyncPool(err) => Some(err),
+            BlogError::Database(err) => Some(err),
+            BlogError::Migration(err) => Some(&**err),
+            BlogError::Io { source, .. } => Some(source),
+            BlogError::MissingDatabaseUrl
+            | BlogError::Config(_)
+            | BlogError::NotFound(_)
+            | BlogError::SlugNotFound(_)
+            | BlogError::AuthorNotFound(_)
+            | BlogError::RevisionNotFound { .. }
+            | BlogError::CommentNotFound(_)
+            | BlogError::AttachmentNotFound(_)
+            | BlogError::AttachmentExists { .. }
+            | BlogError::InvalidAttachmentName(_)
+            | BlogError::InvalidTransition { .. }
+            | BlogError::InvalidImport { .. }
+            | BlogError::PendingMigrations(_) => None,
+        }
+    }
+}
+
+impl From<ConnectionError> for BlogError {
+    fn from(err: ConnectionError) -> Self {
+        BlogError::Connection(err)
+    }
+}
+
+impl From<diesel::r2d2::PoolError> for BlogError {
+    fn from(err: diesel::r2d2::PoolError) -> Self {
+        BlogError::Pool(err)
+    }
+}
+
+#[cfg(feature = "async")]
+impl From<diesel_async::pooled_connection::deadpool::PoolError> for BlogError {
+    fn from(err: diesel_async::pooled_connection::deadpool::PoolError) -> Self {
+        BlogError::AsyncPool(err)
+    }
+}
+
+impl BlogError {
+    /// Wraps an I/O error with the path it happened on.
+    pub fn io(path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> BlogError {
+        let path = path.into();
+        move |source| BlogError::Io { path, source }
+    }
+
+    /// Checks that `post` may take `transition`, see [`Post::allows`].
+    pub(crate) fn check_transition(post: &Post, transition: Transition) -> Result<()> {
+        if post.allows(transition) {
+            Ok(())
+        } else {
+            Err(BlogError::InvalidTransition {
+                post_id: post.id,
+                stage: post.stage(),
+                review: post.review,
+                transition,
+            })
+        }
+    }
+}
+
+impl From<DieselError> for BlogError {
+    fn from(err: DieselError) -> Self {
+        BlogError::Database(err)
+    }
+}
+
+/// Turns an `Option` from an `.optional()` query into `NotFound`.
+pub(crate) trait OrNotFound<T> {
+    fn or_not_found(self, id: i32) -> Result<T>;
+
+    /// Like `or_not_found`, with any error for the missing row.
+    fn or_else_missing(self, missing: impl FnOnce() -> BlogError) -> Result<T>;
+}
+
+impl<T> OrNotFound<T> for std::result::Result<Option<T>, DieselError> {
+    fn or_not_found(self, id: i32) -> Result<T> {
+        self.or_else_missing(|| BlogError::NotFound(id))
+    }
+
+    fn or_else_missing(self, missing: impl FnOnce() -> BlogError) -> Result<T> {
+        self?.ok_or_else(missing)
+    }
+}
@@ -0,0 +1,497 @@
+//! Static site export: published posts rendered from Markdown to HTML, with
+//! an index, a page per tag, an RSS feed and a sitemap. Attached files are
+//! copied to `attachments/`, and the references to them in bodies point there.
+//!
+//! Pages are filled in from small templates where `{{name}}` is replaced by a
+//! value. The defaults live in `templates/`; a directory passed to
+//! [`Templates::load`] can override any of them by file name.
+
+use crate::attachments::rewrite_references;
+use crate::error::{BlogError, Result};
+use crate::models::{Attachment, PostDetails};
+use crate::storage::Storage;
+use chrono::NaiveDateTime;
+use pulldown_cmark::{html, Event, Options, Parser, Tag as MarkdownTag, TagEnd};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const TEMPLATES: [(&str, &str); 4] = [
+    ("layout.html", include_str!("../templates/layout.html")),
+    ("index.html", include_str!("../templates/index.html")),
+    ("post.html", include_str!("../templates/post.html")),
+    ("post_item.html", include_str!("../templates/post_item.html")),
+];
+
+pub struct Templates {
+    templates: BTreeMap<&'static str, String>,
+}
+
+impl Default for Templates {
+    fn default() -> Self {
+        Templates {
+            templates: TEMPLATES
+                .iter()
+                .map(|(name, template)| (*name, template.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl Templates {
+    /// The default templates, with the files found in `dir` used instead.
+    pub fn load(dir: Option<&Path>) -> Result<Self> {
+        let mut templates = Templates::default();
+        let Some(dir) = dir else {
+            return Ok(templates);
+        };
+        if !dir.is_dir() {
+            return Err(BlogError::Config(format!("{} is not a directory", dir.display())));
+        }
+
+        for (name, template) in templates.templates.iter_mut() {
+            let path = dir.join(name);
+            if path.exists() {
+                *template = fs::read_to_string(&path).map_err(BlogError::io(&path))?;
+            }
+        }
+        Ok(templates)
+    }
+
+    fn render(&self, name: &str, values: &[(&str, &str)]) -> String {
+        fill(&self.templates[name], values)
+    }
+}
+
+/// Replaces every `{{name}}` in `template` with its value. Values go in as
+/// they are, so text has to be escaped first. Unknown names are left alone.
+pub fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            out.push_str(&rest[start..]);
+            return out;
+        };
+
+        let name = after[..end].trim();
+        match values.iter().find(|(key, _)| *key == name) {
+            Some((_, value)) => out.push_str(value),
+            None => out.push_str(&rest[start..start + 2 + end + 2]),
+        }
+        rest = &after[end + 2..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Escapes text for HTML and XML, in content and in quoted attributes.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn markdown_options() -> Options {
+    Options::ENABLE_TABLES | Options::ENABLE_FOOTNOTES | Options::ENABLE_STRIKETHROUGH
+}
+
+/// Renders a post body from Markdown to HTML.
+pub fn render_markdown(markdown: &str) -> String {
+    let mut out = String::new();
+    html::push_html(&mut out, Parser::new_ext(markdown, markdown_options()));
+    out
+}
+
+/// The plain text of the first paragraph, cut to `max_chars` characters.
+pub fn summary(markdown: &str, max_chars: usize) -> String {
+    let mut text = String::new();
+    let mut in_paragraph = false;
+
+    for event in Parser::new_ext(markdown, markdown_options()) {
+        match event {
+            Event::Start(MarkdownTag::Paragraph) => in_paragraph = true,
+            Event::End(TagEnd::Paragraph) => break,
+            Event::Text(part) | Event::Code(part) if in_paragraph => text.push_str(&part),
+            Event::SoftBreak | Event::HardBreak if in_paragraph => text.push(' '),
+            _ => {}
+        }
+    }
+
+    if text.chars().count() > max_chars {
+        let cut: String = text.chars().take(max_chars).collect();
+        format!("{}…", cut.trim_end())
+    } else {
+        text
+    }
+}
+
+/// Settings of the exported site.
+pub struct Site {
+    pub title: String,
+    /// Absolute URL the site is served from, without a trailing slash.
+    pub base_url: String,
+}
+
+impl Site {
+    pub fn new(title: &str, base_url: &str) -> Self {
+        Site {
+            title: title.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn post_url(&self, details: &PostDetails) -> String {
+        format!("{}/{}", self.base_url, post_path(details))
+    }
+
+    /// The body of a post, with its attachments linked to their copies in
+    /// the site.
+    fn body(&self, details: &PostDetails) -> String {
+        rewrite_references(&details.post.body, &details.attachments, |attachment| {
+            format!("{}/{}", self.base_url, attachment_path(attachment))
+        })
+    }
+}
+
+/// Where an attached file goes in the site.
+pub fn attachment_path(attachment: &Attachment) -> String {
+    format!("attachments/{}", attachment.storage_key)
+}
+
+fn post_path(details: &PostDetails) -> String {
+    format!("posts/{}.html", details.post.slug)
+}
+
+fn tag_path(tag: &str) -> String {
+    format!("tags/{}.html", tag)
+}
+
+fn published(details: &PostDetails) -> NaiveDateTime {
+    details.post.published_at.unwrap_or(details.post.created_at)
+}
+
+fn page(site: &Site, templates: &Templates, page_title: &str, content: &str) -> String {
+    templates.render(
+        "layout.html",
+        &[
+            ("page_title", &escape(page_title)),
+            ("site_title", &escape(&site.title)),
+            ("base_url", &escape(&site.base_url)),
+            ("content", content),
+        ],
+    )
+}
+
+fn post_page(site: &Site, templates: &Templates, details: &PostDetails) -> String {
+    let byline = match &details.author {
+        Some(author) => format!(" by {}", escape(&author.name)),
+        None => String::new(),
+    };
+    let tags = if details.tags.is_empty() {
+        String::new()
+    } else {
+        let links: Vec<String> = details
+            .tags
+            .iter()
+            .map(|tag| {
+                format!(
+                    r#"<a href="{}/{}">{}</a>"#,
+                    escape(&site.base_url),
+                    tag_path(&tag.name),
+                    escape(&tag.name)
+                )
+            })
+            .collect();
+        format!(" · {}", links.join(", "))
+    };
+
+    let article = templates.render(
+        "post.html",
+        &[
+            ("title", &escape(&details.post.title)),
+            ("date", &published(details).format("%Y-%m-%d").to_string()),
+            ("byline", &byline),
+            ("tags", &tags),
+            ("content", &render_markdown(&site.body(details))),
+        ],
+    );
+    page(site, templates, &format!("{} - {}", details.post.title, site.title), &article)
+}
+
+fn list_page(
+    site: &Site,
+    templates: &Templates,
+    heading: &str,
+    posts: &[&PostDetails],
+) -> String {
+    let items: Vec<String> = posts
+        .iter()
+        .map(|details| {
+            templates.render(
+                "post_item.html",
+                &[
+                    ("url", &escape(&site.post_url(details))),
+                    ("title", &escape(&details.post.title)),
+                    ("date", &published(details).format("%Y-%m-%d").to_string()),
+                    ("summary", &escape(&summary(&details.post.body, 200))),
+                ],
+            )
+        })
+        .collect();
+
+    let content = templates.render(
+        "index.html",
+        &[("heading", &escape(heading)), ("posts", &items.join("\n"))],
+    );
+    page(site, templates, heading, &content)
+}
+
+fn rss(site: &Site, posts: &[&PostDetails]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rss version=\"2.0\">\n<channel>\n");
+    xml.push_str(&format!("  <title>{}</title>\n", escape(&site.title)));
+    xml.push_str(&format!("  <link>{}/</link>\n", escape(&site.base_url)));
+    xml.push_str(&format!("  <description>{}</description>\n", escape(&site.title)));
+
+    for details in posts {
+        let url = escape(&site.post_url(details));
+        xml.push_str("  <item>\n");
+        xml.push_str(&format!("    <title>{}</title>\n", escape(&details.post.title)));
+        xml.push_str(&format!("    <link>{}</link>\n", url));
+        xml.push_str(&format!("    <guid>{}</guid>\n", url));
+        xml.push_str(&format!("    <pubDate>{}</pubDate>\n", published(details).and_utc().to_rfc2822()));
+        if let Some(author) = &details.author {
+            xml.push_str(&format!(
+                "    <author>{} ({})</author>\n",
+                escape(&author.email),
+                escape(&author.name)
+            ));
+        }
+        for tag in &details.tags {
+            xml.push_str(&format!("    <category>{}</category>\n", escape(&tag.name)));
+        }
+        xml.push_str(&format!(
+            "    <description>{}</description>\n",
+            escape(&render_markdown(&site.body(details)))
+        ));
+        xml.push_str("  </item>\n");
+    }
+
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+fn sitemap(site: &Site, posts: &[&PostDetails], tags: &[&str]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    let mut url = |loc: String, lastmod: Option<NaiveDateTime>| {
+        xml.push_str(&format!("  <url><loc>{}</loc>", escape(&loc)));
+        if let Some(lastmod) = lastmod {
+            xml.push_str(&format!("<lastmod>{}</lastmod>", lastmod.format("%Y-%m-%d")));
+        }
+        xml.push_str("</url>\n");
+    };
+
+    url(format!("{}/", site.base_url), posts.iter().map(|details| details.post.updated_at).max());
+    for details in posts {
+        url(site.post_url(details), Some(details.post.updated_at));
+    }
+    for tag in tags {
+        url(format!("{}/{}", site.base_url, tag_path(tag)), None);
+    }
+
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+/// Builds every file of the site from the published posts, as paths
+/// relative to the output directory and their contents. Posts are listed
+/// newest first.
+pub fn build(site: &Site, templates: &Templates, posts: &[PostDetails]) -> Vec<(PathBuf, String)> {
+    let mut posts: Vec<&PostDetails> = posts.iter().filter(|details| details.post.is_published()).collect();
+    posts.sort_by(|a, b| published(b).cmp(&published(a)).then(b.post.id.cmp(&a.post.id)));
+
+    let mut by_tag: BTreeMap<&str, Vec<&PostDetails>> = BTreeMap::new();
+    for details in &posts {
+        for tag in &details.tags {
+            by_tag.entry(tag.name.as_str()).or_default().push(details);
+        }
+    }
+
+    let mut files = vec![(PathBuf::from("index.html"), list_page(site, templates, &site.title, &posts))];
+    for details in &posts {
+        files.push((PathBuf::from(post_path(details)), post_page(site, templates, details)));
+    }
+    for (tag, tagged) in &by_tag {
+        let heading = format!("Posts tagged {}", tag);
+        files.push((PathBuf::from(tag_path(tag)), list_page(site, templates, &heading, tagged)));
+    }
+    files.push((PathBuf::from("feed.xml"), rss(site, &posts)));
+
+    let tags: Vec<&str> = by_tag.keys().copied().collect();
+    files.push((PathBuf::from("sitemap.xml"), sitemap(site, &posts, &tags)));
+    files
+}
+
+/// Writes the files from [`build`] under `out`, creating directories as
+/// needed.
+pub fn write(out: &Path, files: &[(PathBuf, String)]) -> Result<()> {
+    for (path, contents) in files {
+        let path = out.join(path);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(BlogError::io(dir))?;
+        }
+        fs::write(&path, contents).map_err(BlogError::io(&path))?;
+    }
+    Ok(())
+}
+
+/// Copies the files attached to published posts from `storage` to the
+/// [`attachment_path`]s under `out`, returning the paths written.
+pub fn copy_attachments(out: &Path, storage: &dyn Storage, posts: &[PostDetails]) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    let attachments = posts
+        .iter()
+        .filter(|details| details.post.is_published())
+        .flat_map(|details| &details.attachments);
+    for attachment in attachments {
+        let path = out.join(attachment_path(attachment));
+        // Files are stored by contents, so posts may share one.
+        if paths.contains(&path) {
+            continue;
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(BlogError::io(dir))?;
+        }
+        fs::write(&path, storage.get(&attachment.storage_key)?).map_err(BlogError::io(&path))?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Author, Post, PostStatus, ReviewState, Tag};
+
+    fn details(id: i32, title: &str, body: &str, tags: &[&str]) -> PostDetails {
+        let at = chrono::NaiveDate::from_ymd_opt(2024, 4, id as u32)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap();
+        PostDetails {
+            post: Post {
+                id,
+                title: title.to_string(),
+                body: body.to_string(),
+                status: PostStatus::Published,
+                slug: crate::slug::slugify(title),
+                author_id: Some(1),
+                created_at: at,
+                updated_at: at,
+                published_at: Some(at),
+                publish_at: None,
+                review: ReviewState::Approved,
+            },
+            author: Some(Author {
+                id: 1,
+                name: "Ada".to_string(),
+                email: "ada@example.com".to_string(),
+                created_at: at,
+            }),
+            tags: tags
+                .iter()
+                .enumerate()
+                .map(|(n, name)| Tag { id: n as i32, name: name.to_string() })
+                .collect(),
+            attachments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn fills_placeholders() {
+        let filled = fill("<h1>{{ title }}</h1>{{missing}}{{body}}", &[("title", "Hi"), ("body", "!")]);
+        assert_eq!("<h1>Hi</h1>{{missing}}!", filled);
+        assert_eq!("open {{ end", fill("open {{ end", &[]));
+    }
+
+    #[test]
+    fn escapes_html() {
+        assert_eq!("&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;", escape("<a href=\"x\">Tom & Jerry's</a>"));
+    }
+
+    #[test]
+    fn renders_markdown() {
+        assert_eq!("<h1>Title</h1>\n<p>Some <em>text</em></p>\n", render_markdown("# Title\n\nSome *text*"));
+    }
+
+    #[test]
+    fn summarizes_the_first_paragraph() {
+        let body = "# Heading\n\nFirst *paragraph*\nwith `code`.\n\nSecond one.";
+        assert_eq!("First paragraph with code.", summary(body, 200));
+        assert_eq!("First…", summary(body, 6));
+    }
+
+    #[test]
+    fn builds_the_site() {
+        let site = Site::new("My <Blog>", "https://example.com/");
+        let mut draft = details(3, "Draft", "Not yet", &[]);
+        draft.post.status = PostStatus::Draft;
+        let mut hello = details(1, "Hello World", "Hi **there** ![Me](attachment:me.png)", &["rust"]);
+        hello.attachments.push(Attachment {
+            id: 1,
+            post_id: 1,
+            filename: "me.png".to_string(),
+            mime_type: "image/png".to_string(),
+            size: 3,
+            sha256: "ab12".to_string(),
+            storage_key: "ab/ab12.png".to_string(),
+            created_at: hello.post.created_at,
+        });
+        let posts = vec![
+            hello,
+            details(2, "Second Post", "More", &["rust", "diesel"]),
+            draft,
+        ];
+
+        let files = build(&site, &Templates::default(), &posts);
+        let paths: Vec<&str> = files.iter().map(|(path, _)| path.to_str().unwrap()).collect();
+        assert_eq!(
+            vec![
+                "index.html",
+                "posts/second-post.html",
+                "posts/hello-world.html",
+                "tags/diesel.html",
+                "tags/rust.html",
+                "feed.xml",
+                "sitemap.xml",
+            ],
+            paths
+        );
+
+        let file = |name: &str| &files.iter().find(|(path, _)| path.to_str() == Some(name)).unwrap().1;
+        let index = file("index.html");
+        assert!(index.contains("<title>My &lt;Blog&gt;</title>"));
+        assert!(index.find("Second Post").unwrap() < index.find("Hello World").unwrap());
+        assert!(file("posts/hello-world.html")
+            .contains(r#"<p>Hi <strong>there</strong> <img src="https://example.com/attachments/ab/ab12.png" alt="Me" /></p>"#));
+        assert!(file("posts/hello-world.html").contains(r#"<a href="https://example.com/tags/rust.html">rust</a>"#));
+        assert!(!file("tags/diesel.html").contains("Hello World"));
+        assert!(file("feed.xml").contains("<pubDate>Mon, 1 Apr 2024 09:00:00 +0000</pubDate>"));
+        assert!(file("sitemap.xml").contains("<loc>https://example.com/posts/second-post.html</loc><lastmod>2024-04-02</lastmod>"));
+    }
+}
@@ -0,0 +1,196 @@
+//! Posts as Markdown files with front matter, for editing in `$EDITOR` and
+//! importing posts written offline.
+//!
+//! The front matter is YAML between `---` lines or TOML between `+++` lines:
+//!
+//! ```text
+//! ---
+//! title: Hello
+//! tags: [rust, diesel]
+//! published: false
+//! ---
+//! The body, in Markdown.
+//! ```
+//!
+//! Saving a document can't review a post, so `published: true` only keeps a
+//! published post out or publishes one already approved. A draft is sent for
+//! review with `blog review` and published by `blog approve`.
+
+use crate::models::{Post, ReviewState};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FrontMatter {
+    pub title: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Only for posts that are published or approved, see
+    /// [`check_published`](Self::check_published).
+    #[serde(default)]
+    pub published: bool,
+    /// Email of the author.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+}
+
+impl FrontMatter {
+    /// Refuses `published: true` for a post saving can't publish: one that
+    /// isn't approved, or a new one, `None`.
+    pub fn check_published(&self, current: Option<&Post>) -> Result<(), String> {
+        let approved = current.is_some_and(|post| post.is_published() || post.review == ReviewState::Approved);
+        if !self.published || approved {
+            return Ok(());
+        }
+        Err(match current {
+            Some(post) => format!(
+                "post {} hasn't been approved, so 'published: true' can't publish it: \
+                 send it for review with `blog review {}`, then publish it with `blog approve`",
+                post.id, post.id
+            ),
+            None => "a new post can't be published straight away: leave out 'published: true', \
+                     then send it for review with `blog review` and publish it with `blog approve`"
+                .to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Document {
+    pub front_matter: FrontMatter,
+    pub body: String,
+}
+
+/// Splits `text` at a closing `fence` line, returning what comes before and
+/// after it.
+fn split_at_fence<'a>(text: &'a str, fence: &str) -> Option<(&'a str, &'a str)> {
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if line.trim_end() == fence {
+            return Some((&text[..offset], &text[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// Splits `text` into its front matter, read as `T`, and its body, telling
+/// YAML and TOML apart by the opening fence. A leading byte order mark and
+/// blank lines are skipped.
+pub fn parse_front_matter<T: DeserializeOwned>(text: &str) -> Result<(T, String), String> {
+    let text = text.trim_start_matches('\u{feff}').trim_start_matches(['\n', '\r']);
+    let (first, rest) = text.split_once('\n').unwrap_or((text, ""));
+
+    let (front_matter, body): (T, &str) = match first.trim_end() {
+        "---" => {
+            let (front, body) = split_at_fence(rest, "---")
+                .ok_or("the front matter has no closing '---' line")?;
+            let front_matter = serde_yaml::from_str(front)
+                .map_err(|err| format!("invalid YAML front matter: {}", err))?;
+            (front_matter, body)
+        }
+        "+++" => {
+            let (front, body) = split_at_fence(rest, "+++")
+                .ok_or("the front matter has no closing '+++' line")?;
+            let front_matter = toml::from_str(front)
+                .map_err(|err| format!("invalid TOML front matter: {}", err))?;
+            (front_matter, body)
+        }
+        _ => return Err("expected front matter starting with '---' or '+++'".to_string()),
+    };
+
+    // One blank line between the front matter and the body is layout.
+    Ok((front_matter, body.strip_prefix('\n').unwrap_or(body).to_string()))
+}
+
+impl Document {
+    /// Reads a document with [`parse_front_matter`], rejecting fields a
+    /// [`FrontMatter`] doesn't have.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (front_matter, body): (FrontMatter, String) = parse_front_matter(text)?;
+        if front_matter.title.trim().is_empty() {
+            return Err("a post needs a title".to_string());
+        }
+
+        Ok(Document { front_matter, body })
+    }
+
+    /// Writes the document with YAML front matter.
+    pub fn to_yaml(&self) -> String {
+        let front = serde_yaml::to_string(&self.front_matter).expect("Error serializing front matter");
+        format!("---\n{}---\n\n{}", front, self.body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(tags: &[&str]) -> Document {
+        Document {
+            front_matter: FrontMatter {
+                title: "Hello: World".to_string(),
+                tags: tags.iter().map(|tag| tag.to_string()).collect(),
+                published: true,
+                author: None,
+            },
+            body: "Some *text*\n\n---\n\nAfter a rule\n".to_string(),
+        }
+    }
+
+    #[test]
+    fn yaml_round_trips() {
+        let doc = document(&["rust", "diesel"]);
+        assert_eq!(Ok(doc.clone()), Document::parse(&doc.to_yaml()));
+    }
+
+    #[test]
+    fn parses_toml() {
+        let text = "+++\ntitle = \"Hello: World\"\ntags = [\"rust\"]\npublished = true\n+++\nSome *text*\n\n---\n\nAfter a rule\n";
+        assert_eq!(Ok(document(&["rust"])), Document::parse(text));
+    }
+
+    #[test]
+    fn defaults_optional_fields() {
+        let parsed = Document::parse("---\ntitle: Draft\n---\nBody").unwrap();
+        assert_eq!(Vec::<String>::new(), parsed.front_matter.tags);
+        assert!(!parsed.front_matter.published);
+        assert_eq!("Body", parsed.body);
+    }
+
+    #[test]
+    fn only_publishes_approved_posts() {
+        let at = chrono::NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(9, 0, 0).unwrap();
+        let mut post = Post {
+            id: 1,
+            title: "Hello: World".to_string(),
+            body: String::new(),
+            status: crate::models::PostStatus::Draft,
+            slug: "hello-world".to_string(),
+            author_id: None,
+            created_at: at,
+            updated_at: at,
+            published_at: None,
+            publish_at: None,
+            review: ReviewState::Pending,
+        };
+        let front = document(&[]).front_matter;
+        assert!(front.check_published(None).is_err());
+        assert!(front.check_published(Some(&post)).unwrap_err().contains("blog review 1"));
+
+        post.review = ReviewState::Approved;
+        assert_eq!(Ok(()), front.check_published(Some(&post)));
+        let draft = FrontMatter { published: false, ..front };
+        assert_eq!(Ok(()), draft.check_published(None));
+    }
+
+    #[test]
+    fn rejects_bad_documents() {
+        assert!(Document::parse("Just a body").is_err());
+        assert!(Document::parse("---\ntitle: Unclosed\n").is_err());
+        assert!(Document::parse("---\ntitle: ''\n---\n").is_err());
+        assert!(Document::parse("---\ntitle: Typo\npublshed: true\n---\n").is_err());
+        assert!(Document::parse("+++\ntitle = \n+++\n").is_err());
+    }
+}
@@ -0,0 +1,694 @@
+//! Importing posts from another blog: a directory of Markdown files with
+//! front matter, a WordPress export (WXR) or a JSON list of posts.
+//!
+//! Imported posts are matched to the ones already here by slug. New slugs
+//! are created, posts that differ are updated to match and the rest are
+//! skipped, so importing the same export twice changes nothing the second
+//! time.
+//!
+//! Published posts the import creates come out without a review, since they
+//! were published elsewhere. An import can't publish a post already here
+//! unless it was approved: that fails like `blog publish` does.
+
+use crate::error::{BlogError, Result};
+use crate::front_matter::parse_front_matter;
+use crate::models::{Post, PostChanges};
+use crate::repository::PostRepository;
+use crate::slug::{normalize_tag, slugify};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// A post read from an export, before it is saved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedPost {
+    /// Where the post came from, like its file, for messages.
+    pub source: String,
+    pub slug: String,
+    pub title: String,
+    pub body: String,
+    pub tags: Vec<String>,
+    pub author: Option<ImportedAuthor>,
+    pub published: bool,
+    /// When the post was first published; now when left out.
+    pub published_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedAuthor {
+    pub email: String,
+    /// The name to create the author with, if they are new. The start of
+    /// their email otherwise.
+    pub name: Option<String>,
+}
+
+/// What an import did, or would do on a dry run. Posts are listed by slug.
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    /// Posts that are already here as they are in the import.
+    pub skipped: Vec<String>,
+    /// Emails of the authors that were created.
+    pub authors: Vec<String>,
+    pub dry_run: bool,
+}
+
+/// The kinds of exports posts can be imported from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// A directory of `.md` files with front matter, as Jekyll and Hugo keep
+    /// them.
+    Markdown,
+    /// A WordPress export, from Tools > Export.
+    Wxr,
+    /// A JSON list of posts, or an object with a `posts` list like
+    /// `blog dump` writes.
+    Json,
+}
+
+impl ImportFormat {
+    /// Guesses the format from the path: a directory is Markdown, a file goes
+    /// by its extension.
+    pub fn detect(path: &Path) -> Option<ImportFormat> {
+        if path.is_dir() {
+            return Some(ImportFormat::Markdown);
+        }
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "xml" | "wxr" => Some(ImportFormat::Wxr),
+            "json" => Some(ImportFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for ImportFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        match name {
+            "markdown" => Ok(ImportFormat::Markdown),
+            "wxr" | "wordpress" => Ok(ImportFormat::Wxr),
+            "json" => Ok(ImportFormat::Json),
+            _ => Err(format!("unknown format '{}', expected markdown, wxr or json", name)),
+        }
+    }
+}
+
+impl fmt::Display for ImportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ImportFormat::Markdown => "markdown",
+            ImportFormat::Wxr => "wxr",
+            ImportFormat::Json => "json",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Reads the posts at `path`, a directory for Markdown and a file otherwise.
+pub fn read(path: &Path, format: ImportFormat) -> Result<Vec<ImportedPost>> {
+    let source = path.display().to_string();
+    match format {
+        ImportFormat::Markdown => read_markdown_dir(path),
+        ImportFormat::Wxr => read_wxr(&source, &fs::read_to_string(path).map_err(BlogError::io(path))?),
+        ImportFormat::Json => read_json(&source, &fs::read_to_string(path).map_err(BlogError::io(path))?),
+    }
+}
+
+fn invalid(source: &str, message: impl Into<String>) -> BlogError {
+    BlogError::InvalidImport { source: source.to_string(), message: message.into() }
+}
+
+impl ImportedPost {
+    /// A post with the slug given, or one made from the title.
+    fn new(source: &str, slug: Option<&str>, title: &str, body: String) -> Result<Self> {
+        let title = title.trim();
+        if title.is_empty() {
+            return Err(invalid(source, "a post needs a title"));
+        }
+        let slug = match slug.map(str::trim).filter(|slug| !slug.is_empty()) {
+            Some(slug) => slugify(slug),
+            None => slugify(title),
+        };
+        Ok(ImportedPost {
+            source: source.to_string(),
+            slug,
+            title: title.to_string(),
+            body,
+            tags: Vec::new(),
+            author: None,
+            published: false,
+            published_at: None,
+        })
+    }
+}
+
+/// Reads a date as exports write them: RFC 3339, `2024-04-01 09:00:00`
+/// with or without a UTC offset, or just `2024-04-01`. Times with an offset
+/// are converted to UTC.
+pub fn parse_date(text: &str) -> Option<NaiveDateTime> {
+    let text = text.trim();
+    if let Ok(at) = DateTime::parse_from_rfc3339(text) {
+        return Some(at.naive_utc());
+    }
+    if let Ok(at) = DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S %z") {
+        return Some(at.naive_utc());
+    }
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .or_else(|| Some(NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()?.and_time(NaiveTime::MIN)))
+}
+
+/// Reads an author given as `email` or `Name <email>`.
+fn parse_author(source: &str, text: &str) -> Result<ImportedAuthor> {
+    let text = text.trim();
+    let (name, email) = match text.strip_suffix('>').and_then(|text| text.split_once('<')) {
+        Some((name, email)) => (Some(name.trim()).filter(|name| !name.is_empty()), email.trim()),
+        None => (None, text),
+    };
+    if !email.contains('@') {
+        return Err(invalid(source, format!("the author '{}' has no email", text)));
+    }
+    Ok(ImportedAuthor { email: email.to_string(), name: name.map(str::to_string) })
+}
+
+// Markdown
+
+#[derive(Deserialize)]
+struct MarkdownFrontMatter {
+    title: String,
+    slug: Option<String>,
+    date: Option<DateValue>,
+    #[serde(default)]
+    tags: Words,
+    #[serde(default)]
+    categories: Words,
+    published: Option<bool>,
+    draft: Option<bool>,
+    author: Option<String>,
+}
+
+/// A date in front matter: TOML has a type for them, YAML doesn't.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DateValue {
+    Toml(toml::value::Datetime),
+    Text(String),
+}
+
+/// Tags as a list, or as Jekyll also allows, words in one string.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Words {
+    List(Vec<String>),
+    Text(String),
+}
+
+impl Default for Words {
+    fn default() -> Self {
+        Words::List(Vec::new())
+    }
+}
+
+impl Words {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            Words::List(words) => words,
+            Words::Text(text) => text.split_whitespace().map(str::to_string).collect(),
+        }
+    }
+}
+
+/// Reads the `.md` and `.markdown` files under `dir` and its
+/// subdirectories, in path order. Hidden files and directories are left out.
+pub fn read_markdown_dir(dir: &Path) -> Result<Vec<ImportedPost>> {
+    let mut files = Vec::new();
+    markdown_files(dir, &mut files)?;
+    files.sort();
+    files
+        .iter()
+        .map(|path| {
+            let text = fs::read_to_string(path).map_err(BlogError::io(path))?;
+            read_markdown(&path.display().to_string(), &text)
+        })
+        .collect()
+}
+
+fn markdown_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).map_err(BlogError::io(dir))? {
+        let path = entry.map_err(BlogError::io(dir))?.path();
+        if path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.starts_with('.')) {
+            continue;
+        }
+        if path.is_dir() {
+            markdown_files(&path, files)?;
+        } else if matches!(path.extension().and_then(|extension| extension.to_str()), Some("md" | "markdown")) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Reads a Markdown file with YAML or TOML front matter. Besides the fields
+/// of [`FrontMatter`](crate::front_matter::FrontMatter) it takes `slug`,
+/// `date` and `categories`, which become tags, and ignores the rest. As in
+/// Jekyll and Hugo, a post is published unless it says `published: false`
+/// or `draft: true`.
+pub fn read_markdown(source: &str, text: &str) -> Result<ImportedPost> {
+    let (front, body): (MarkdownFrontMatter, String) =
+        parse_front_matter(text).map_err(|message| invalid(source, message))?;
+
+    let mut post = ImportedPost::new(source, front.slug.as_deref(), &front.title, body)?;
+    post.tags = front.tags.into_vec();
+    post.tags.extend(front.categories.into_vec());
+    post.author = front.author.map(|author| parse_author(source, &author)).transpose()?;
+    post.published = front.published.unwrap_or(true) && !front.draft.unwrap_or(false);
+    if let Some(date) = front.date {
+        let text = match date {
+            DateValue::Toml(date) => date.to_string(),
+            DateValue::Text(text) => text,
+        };
+        let at = parse_date(&text).ok_or_else(|| invalid(source, format!("unknown date '{}'", text)))?;
+        post.published_at = post.published.then_some(at);
+    }
+    Ok(post)
+}
+
+// WordPress
+
+const WP: &str = "http://wordpress.org/export/";
+const CONTENT: &str = "http://purl.org/rss/1.0/modules/content/";
+const DC: &str = "http://purl.org/dc/elements/1.1/";
+
+/// Whether `node` is the element `name` in a namespace starting with
+/// `namespace`, or in none. WXR versions differ in the end of theirs.
+fn is_element(node: roxmltree::Node, namespace: Option<&str>, name: &str) -> bool {
+    node.is_element()
+        && node.tag_name().name() == name
+        && match (namespace, node.tag_name().namespace()) {
+            (None, None) => true,
+            (Some(prefix), Some(uri)) => uri.starts_with(prefix),
+            _ => false,
+        }
+}
+
+fn children<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    namespace: Option<&'static str>,
+    name: &'static str,
+) -> impl Iterator<Item = roxmltree::Node<'a, 'input>> {
+    node.children().filter(move |child| is_element(*child, namespace, name))
+}
+
+/// The text of the first child element `name`; empty when there is none.
+fn child_text(node: roxmltree::Node, namespace: Option<&'static str>, name: &'static str) -> String {
+    children(node, namespace, name)
+        .next()
+        .map(|child| child.children().filter_map(|text| text.text()).collect::<String>())
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// Reads the posts of a WordPress export. Pages, attachments and trashed
+/// posts are left out, as are drafts without a title; published posts
+/// without one are titled by their slug, or their WordPress id. Bodies stay
+/// HTML, which Markdown passes through.
+pub fn read_wxr(source: &str, xml: &str) -> Result<Vec<ImportedPost>> {
+    let doc = roxmltree::Document::parse(xml).map_err(|err| invalid(source, err.to_string()))?;
+    let channel = doc
+        .root_element()
+        .children()
+        .find(|node| is_element(*node, None, "channel"))
+        .ok_or_else(|| invalid(source, "no <channel>, so not a WordPress export"))?;
+
+    let mut authors = BTreeMap::new();
+    for author in children(channel, Some(WP), "author") {
+        let email = child_text(author, Some(WP), "author_email");
+        if !email.is_empty() {
+            let name = Some(child_text(author, Some(WP), "author_display_name")).filter(|name| !name.is_empty());
+            authors.insert(child_text(author, Some(WP), "author_login"), ImportedAuthor { email, name });
+        }
+    }
+
+    let mut posts = Vec::new();
+    for item in children(channel, None, "item") {
+        let status = child_text(item, Some(WP), "status");
+        let title = child_text(item, None, "title");
+        if child_text(item, Some(WP), "post_type") != "post" || status == "trash" || (title.is_empty() && status != "publish") {
+            continue;
+        }
+
+        let post_id = child_text(item, Some(WP), "post_id");
+        let entry = format!("{} post {}", source, post_id);
+        let slug = child_text(item, Some(WP), "post_name");
+        // WordPress publishes untitled posts, so name them after their slug.
+        let title = match (title.is_empty(), slug.is_empty()) {
+            (false, _) => title,
+            (true, false) => slug.clone(),
+            (true, true) => format!("Post {}", post_id),
+        };
+        let mut post = ImportedPost::new(&entry, Some(&slug), &title, child_text(item, Some(CONTENT), "encoded"))?;
+        post.tags = children(item, None, "category")
+            .filter(|category| matches!(category.attribute("domain"), Some("category" | "post_tag")))
+            .filter(|category| category.attribute("nicename") != Some("uncategorized"))
+            .filter_map(|category| category.text())
+            .map(str::to_string)
+            .collect();
+        post.author = authors.get(&child_text(item, Some(DC), "creator")).cloned();
+        post.published = status == "publish";
+        if post.published {
+            // Drafts have a GMT date of all zeros, which doesn't parse.
+            post.published_at = parse_date(&child_text(item, Some(WP), "post_date_gmt"))
+                .or_else(|| parse_date(&child_text(item, Some(WP), "post_date")));
+        }
+        posts.push(post);
+    }
+    Ok(posts)
+}
+
+// JSON
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonAuthor {
+    email: String,
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonAuthorValue {
+    Text(String),
+    Author(JsonAuthor),
+}
+
+#[derive(Deserialize)]
+struct JsonPost {
+    title: String,
+    slug: Option<String>,
+    #[serde(default, alias = "content")]
+    body: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    author: Option<JsonAuthorValue>,
+    published: Option<bool>,
+    status: Option<String>,
+    #[serde(alias = "date")]
+    published_at: Option<String>,
+}
+
+/// Reads a JSON list of posts, or an object with them in `posts`. A post
+/// has a `title` and `body` (or `content`), and can have a `slug`, `tags`,
+/// an `author` as an email or `{"email", "name"}`, and `published_at` (or
+/// `date`). It is published with `"published": true` or a `status` of
+/// `published`.
+pub fn read_json(source: &str, json: &str) -> Result<Vec<ImportedPost>> {
+    let value: serde_json::Value = serde_json::from_str(json).map_err(|err| invalid(source, err.to_string()))?;
+    let items = match value {
+        serde_json::Value::Array(items) => items,
+        serde_json::Value::Object(mut object) => match object.remove("posts") {
+            Some(serde_json::Value::Array(items)) => items,
+            _ => return Err(invalid(source, "expected a list of posts, or an object with a \"posts\" list")),
+        },
+        _ => return Err(invalid(source, "expected a list of posts, or an object with a \"posts\" list")),
+    };
+
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(n, item)| {
+            let entry = format!("{} post {}", source, n + 1);
+            let json: JsonPost = serde_json::from_value(item).map_err(|err| invalid(&entry, err.to_string()))?;
+
+            let mut post = ImportedPost::new(&entry, json.slug.as_deref(), &json.title, json.body)?;
+            post.tags = json.tags;
+            post.author = match json.author {
+                Some(JsonAuthorValue::Text(text)) => Some(parse_author(&entry, &text)?),
+                Some(JsonAuthorValue::Author(JsonAuthor { email, name })) => Some(ImportedAuthor { email, name }),
+                None => None,
+            };
+            let status_published = matches!(json.status.as_deref(), Some("published" | "publish"));
+            post.published = json.published.unwrap_or(status_published);
+            if let (true, Some(text)) = (post.published, &json.published_at) {
+                let at = parse_date(text).ok_or_else(|| invalid(&entry, format!("unknown date '{}'", text)))?;
+                post.published_at = Some(at);
+            }
+            Ok(post)
+        })
+        .collect()
+}
+
+// Saving
+
+/// Saves `posts` in one transaction, or with `dry_run` only works out what
+/// saving them would do. Authors that don't exist yet are created. Fails
+/// without saving anything if two posts have the same slug.
+pub fn import(repo: &mut PostRepository, posts: &[ImportedPost], dry_run: bool) -> Result<ImportReport> {
+    let mut sources: BTreeMap<&str, &str> = BTreeMap::new();
+    for post in posts {
+        if let Some(other) = sources.insert(&post.slug, &post.source) {
+            return Err(invalid(&post.source, format!("{} has the same slug, {}", other, post.slug)));
+        }
+    }
+
+    repo.transaction(|repo| {
+        let mut report = ImportReport { dry_run, ..ImportReport::default() };
+        for post in posts {
+            let author_id = match &post.author {
+                Some(author) => author_id(repo, author, dry_run, &mut report)?,
+                None => None,
+            };
+            let existing = match repo.get_by_slug(&post.slug) {
+                Ok(existing) => Some(existing),
+                Err(BlogError::SlugNotFound(_)) => None,
+                Err(err) => return Err(err),
+            };
+
+            let unchanged = match &existing {
+                Some(existing) => is_unchanged(repo, existing, post, author_id)?,
+                None => false,
+            };
+            let outcome = match (&existing, unchanged) {
+                (None, _) => &mut report.created,
+                (Some(_), true) => &mut report.skipped,
+                (Some(_), false) => &mut report.updated,
+            };
+            outcome.push(post.slug.clone());
+            if !dry_run && !unchanged {
+                save(repo, existing, post, author_id)?;
+            }
+        }
+        Ok(report)
+    })
+}
+
+/// The id of the author, created unless this is a dry run. `None` for an
+/// author a dry run would create.
+fn author_id(
+    repo: &mut PostRepository,
+    author: &ImportedAuthor,
+    dry_run: bool,
+    report: &mut ImportReport,
+) -> Result<Option<i32>> {
+    match repo.find_author(&author.email) {
+        Ok(found) => Ok(Some(found.id)),
+        Err(BlogError::AuthorNotFound(_)) if dry_run => {
+            if !report.authors.contains(&author.email) {
+                report.authors.push(author.email.clone());
+            }
+            Ok(None)
+        }
+        Err(BlogError::AuthorNotFound(_)) => {
+            let name = match &author.name {
+                Some(name) => name.as_str(),
+                None => author.email.split('@').next().unwrap_or(&author.email),
+            };
+            report.authors.push(author.email.clone());
+            Ok(Some(repo.create_author(name, &author.email)?.id))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Tag names as [`PostRepository::set_tags`] saves them, sorted.
+fn tag_names(names: &[String]) -> Vec<String> {
+    let mut tags: Vec<String> = names.iter().filter_map(|name| normalize_tag(name)).collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+fn is_unchanged(repo: &mut PostRepository, existing: &Post, post: &ImportedPost, author_id: Option<i32>) -> Result<bool> {
+    let tags: Vec<String> = repo.tags_of(existing.id)?.into_iter().map(|tag| tag.name).collect();
+    let same_date = post.published_at.is_none() || existing.published_at == post.published_at;
+    Ok(existing.title == post.title
+        && existing.body == post.body
+        && existing.author_id == author_id
+        && tags == tag_names(&post.tags)
+        && existing.is_published() == post.published
+        && (!post.published || same_date))
+}
+
+fn save(repo: &mut PostRepository, existing: Option<Post>, post: &ImportedPost, author_id: Option<i32>) -> Result<()> {
+    let created = existing.is_none();
+    let current = match existing {
+        Some(current) if current.title != post.title || current.body != post.body => {
+            let changes = PostChanges { title: Some(&post.title), body: Some(&post.body) };
+            repo.update(current.id, &changes)?
+        }
+        Some(current) => current,
+        None => repo.create_with_slug(&post.title, &post.body, &post.slug)?,
+    };
+
+    if current.author_id != author_id {
+        repo.set_author(current.id, author_id)?;
+    }
+    repo.set_tags(current.id, &post.tags.iter().map(String::as_str).collect::<Vec<_>>())?;
+    match (post.published, post.published_at) {
+        (true, Some(at)) if !current.is_published() || current.published_at != Some(at) => {
+            repo.import_published(current.id, at, created)?;
+        }
+        (true, None) if !current.is_published() => {
+            repo.import_published(current.id, Utc::now().naive_utc(), created)?;
+        }
+        (false, _) if current.is_published() => {
+            repo.unpublish(current.id)?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> Option<NaiveDateTime> {
+        Some(NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").unwrap())
+    }
+
+    #[test]
+    fn parses_export_dates() {
+        assert_eq!(at("2024-04-01 08:30:00"), parse_date("2024-04-01T10:30:00+02:00"));
+        assert_eq!(at("2024-04-01 08:30:00"), parse_date("2024-04-01 10:30:00 +0200"));
+        assert_eq!(at("2024-04-01 10:30:00"), parse_date("2024-04-01 10:30:00"));
+        assert_eq!(at("2024-04-01 00:00:00"), parse_date("2024-04-01"));
+        assert_eq!(None, parse_date("0000-00-00 00:00:00"));
+    }
+
+    #[test]
+    fn reads_jekyll_and_hugo_front_matter() {
+        let jekyll = "---\nlayout: post\ntitle: Hello World\ndate: 2024-04-01 10:30:00 +0200\ntags: rust diesel\n\
+                      categories: [Notes]\nauthor: Ada <ada@example.com>\n---\n\nHi\n";
+        let post = read_markdown("hello.md", jekyll).unwrap();
+        assert_eq!("hello-world", post.slug);
+        assert_eq!(vec!["rust", "diesel", "Notes"], post.tags);
+        assert_eq!(Some(ImportedAuthor { email: "ada@example.com".into(), name: Some("Ada".into()) }), post.author);
+        assert_eq!((true, at("2024-04-01 08:30:00")), (post.published, post.published_at));
+        assert_eq!("Hi\n", post.body);
+
+        let hugo = "+++\ntitle = \"Later\"\nslug = \"Not Yet\"\ndate = 2024-04-02T09:00:00Z\ndraft = true\n+++\nSoon";
+        let post = read_markdown("later.md", hugo).unwrap();
+        assert_eq!("not-yet", post.slug);
+        assert_eq!((false, None), (post.published, post.published_at));
+
+        assert!(read_markdown("bad.md", "---\ntitle: Hi\ndate: someday\n---\n").is_err());
+        assert!(read_markdown("bad.md", "---\ntitle: Hi\nauthor: Ada\n---\n").is_err());
+    }
+
+    #[test]
+    fn reads_wordpress_exports() {
+        let wxr = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:content="http://purl.org/rss/1.0/modules/content/"
+     xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:wp="http://wordpress.org/export/1.2/">
+<channel>
+  <wp:author>
+    <wp:author_login><![CDATA[ada]]></wp:author_login>
+    <wp:author_email><![CDATA[ada@example.com]]></wp:author_email>
+    <wp:author_display_name><![CDATA[Ada Lovelace]]></wp:author_display_name>
+  </wp:author>
+  <item>
+    <title>Hello &amp; welcome</title>
+    <dc:creator><![CDATA[ada]]></dc:creator>
+    <content:encoded><![CDATA[<p>First!</p>]]></content:encoded>
+    <wp:post_id>7</wp:post_id>
+    <wp:post_date_gmt><![CDATA[2024-04-01 09:00:00]]></wp:post_date_gmt>
+    <wp:post_name><![CDATA[hello]]></wp:post_name>
+    <wp:status><![CDATA[publish]]></wp:status>
+    <wp:post_type><![CDATA[post]]></wp:post_type>
+    <category domain="category" nicename="uncategorized"><![CDATA[Uncategorized]]></category>
+    <category domain="post_tag" nicename="rust"><![CDATA[Rust]]></category>
+  </item>
+  <item>
+    <title>Draft</title>
+    <wp:post_id>8</wp:post_id>
+    <wp:post_date_gmt>0000-00-00 00:00:00</wp:post_date_gmt>
+    <wp:status>draft</wp:status>
+    <wp:post_type>post</wp:post_type>
+  </item>
+  <item>
+    <title></title>
+    <wp:post_id>9</wp:post_id>
+    <wp:post_name>a-quick-note</wp:post_name>
+    <wp:status>publish</wp:status>
+    <wp:post_type>post</wp:post_type>
+  </item>
+  <item>
+    <title></title>
+    <wp:post_id>10</wp:post_id>
+    <wp:status>publish</wp:status>
+    <wp:post_type>post</wp:post_type>
+  </item>
+  <item>
+    <title></title>
+    <wp:post_id>11</wp:post_id>
+    <wp:status>draft</wp:status>
+    <wp:post_type>post</wp:post_type>
+  </item>
+  <item>
+    <title>About</title>
+    <wp:status>publish</wp:status>
+    <wp:post_type>page</wp:post_type>
+  </item>
+</channel>
+</rss>"#;
+        let posts = read_wxr("export.xml", wxr).unwrap();
+        assert_eq!(4, posts.len());
+        assert_eq!(("hello", "Hello & welcome", "<p>First!</p>"), (posts[0].slug.as_str(), posts[0].title.as_str(), posts[0].body.as_str()));
+        assert_eq!(vec!["Rust"], posts[0].tags);
+        assert_eq!("Ada Lovelace", posts[0].author.as_ref().unwrap().name.as_deref().unwrap());
+        assert_eq!((true, at("2024-04-01 09:00:00")), (posts[0].published, posts[0].published_at));
+        assert_eq!(("draft", false, None), (posts[1].slug.as_str(), posts[1].published, posts[1].published_at));
+        assert_eq!("export.xml post 8", posts[1].source);
+        assert_eq!(("a-quick-note", "a-quick-note"), (posts[2].slug.as_str(), posts[2].title.as_str()));
+        assert_eq!(("post-10", "Post 10"), (posts[3].slug.as_str(), posts[3].title.as_str()));
+
+        assert!(read_wxr("export.xml", "<rss><item/></rss>").is_err());
+        assert!(read_wxr("export.xml", "<rss>").is_err());
+    }
+
+    #[test]
+    fn reads_json_lists() {
+        let json = r#"{"posts": [
+            {"title": "Hello", "content": "Hi", "tags": ["rust"], "author": "ada@example.com",
+             "status": "published", "date": "2024-04-01T09:00:00"},
+            {"title": "Draft", "slug": "my-draft", "body": "", "author": {"email": "bob@example.com", "name": "Bob"}}
+        ]}"#;
+        let posts = read_json("posts.json", json).unwrap();
+        assert_eq!(("hello", "Hi", true), (posts[0].slug.as_str(), posts[0].body.as_str(), posts[0].published));
+        assert_eq!(at("2024-04-01 09:00:00"), posts[0].published_at);
+        assert_eq!(("my-draft", false), (posts[1].slug.as_str(), posts[1].published));
+        assert_eq!(Some("Bob"), posts[1].author.as_ref().unwrap().name.as_deref());
+
+        let err = read_json("posts.json", r#"[{"title": "Hi"}, {"body": "untitled"}]"#).unwrap_err();
+        assert!(err.to_string().starts_with("posts.json post 2: missing field `title`"), "{}", err);
+        assert!(read_json("posts.json", r#"{"items": []}"#).is_err());
+    }
+}
@@ -1,28 +1,110 @@
-use diesel::pg::PgConnection;
-use diesel::prelude::*;
 use dotenvy::dotenv;
 use std::env;
+
+#[cfg(not(any(feature = "postgres", feature = "sqlite")))]
+compile_error!("enable the postgres or sqlite feature, or both");
+
+#[cfg(feature = "async")]
+pub mod async_repository;
+#[cfg(feature = "server")]
+pub mod api;
+pub mod attachments;
+pub mod comments;
+pub mod connection;
+pub mod diff;
+pub mod error;
+pub mod export;
+pub mod front_matter;
+pub mod import;
+pub mod migrations;
 pub mod models;
+pub mod pool;
+pub mod query;
+pub mod repository;
 pub mod schema;
+pub mod search;
+pub mod seed;
+pub mod slug;
+pub mod snapshot;
+pub mod storage;
 
-use self::models::{NewPost, Post};
+#[cfg(feature = "async")]
+pub use self::async_repository::AsyncPostRepository;
+pub use self::connection::BlogConnection;
+pub use self::error::BlogError;
+#[cfg(feature = "postgres")]
+pub use self::pool::{establish_pool, PgPool};
+pub use self::pool::{establish_blog_pool, BlogPool, PoolConfig};
+pub use self::query::{PostPage, PostQuery};
+pub use self::repository::PostRepository;
+use self::models::{Post, PostChanges};
 
-pub fn establish_connection() -> PgConnection {
+/// Reads `DATABASE_URL` from the environment or `.env`.
+pub fn database_url() -> error::Result<String> {
     dotenv().ok();
 
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    PgConnection::establish(&database_url)
-        .unwrap_or_else(|_| panic!("Error connecting to {}", database_url))
+    env::var("DATABASE_URL").map_err(|_| BlogError::MissingDatabaseUrl)
 }
 
-pub fn create_post(conn: &mut PgConnection, title: &str, body: &str) -> Post {
-    use crate::schema::posts;
+/// Connects to `DATABASE_URL`, a Postgres URL or a SQLite database file,
+/// and checks that it has every migration this build was made with.
+pub fn try_establish_connection() -> error::Result<BlogConnection> {
+    let mut conn = BlogConnection::establish(&database_url()?)?;
+    migrations::check(&mut conn)?;
+    Ok(conn)
+}
 
-    let new_post = NewPost { title, body };
+/// Like [`try_establish_connection`], but panics if the database can't be
+/// reached or isn't migrated.
+pub fn establish_connection() -> BlogConnection {
+    try_establish_connection().unwrap_or_else(|err| panic!("{}", err))
+}
 
-    diesel::insert_into(posts::table)
-        .values(&new_post)
-        .returning(Post::as_returning())
-        .get_result(conn)
+/// Saves a new draft, panicking if that fails. See [`PostRepository::create`].
+pub fn create_post(conn: &mut BlogConnection, title: &str, body: &str) -> Post {
+    PostRepository::new(conn)
+        .create(title, body)
         .expect("Error saving new post")
 }
+
+/// Lists posts by id, only published ones unless `include_drafts` is set.
+#[deprecated(note = "use `PostRepository::list`")]
+pub fn list_posts(conn: &mut BlogConnection, include_drafts: bool, limit: i64) -> error::Result<Vec<Post>> {
+    PostRepository::new(conn).list(include_drafts, limit)
+}
+
+#[deprecated(note = "use `PostRepository::get`")]
+pub fn get_post(conn: &mut BlogConnection, post_id: i32) -> error::Result<Option<Post>> {
+    found(PostRepository::new(conn).get(post_id))
+}
+
+/// Publishes or unpublishes a post. Returns `None` if there is no such post.
+/// Publishing follows the review rules of [`PostRepository::publish`].
+#[deprecated(note = "use `PostRepository::publish` or `PostRepository::unpublish`")]
+pub fn set_published(conn: &mut BlogConnection, post_id: i32, value: bool) -> error::Result<Option<Post>> {
+    let mut repo = PostRepository::new(conn);
+    found(if value { repo.publish(post_id) } else { repo.unpublish(post_id) })
+}
+
+/// Changes the title and/or body of a post. Returns `None` if there is no
+/// such post.
+#[deprecated(note = "use `PostRepository::update`")]
+pub fn update_post(conn: &mut BlogConnection, post_id: i32, changes: &PostChanges) -> error::Result<Option<Post>> {
+    found(PostRepository::new(conn).update(post_id, changes))
+}
+
+/// Deletes every post whose title contains `target`.
+#[deprecated(note = "use `PostRepository::delete_matching`")]
+pub fn delete_posts_matching(conn: &mut BlogConnection, target: &str) -> error::Result<usize> {
+    PostRepository::new(conn).delete_matching(target)
+}
+
+/// Turns the repository's `NotFound` back into the `None` the wrappers above
+/// used to return.
+fn found(result: error::Result<Post>) -> error::Result<Option<Post>> {
+    match result {
+        Ok(post) => Ok(Some(post)),
+        Err(BlogError::NotFound(_)) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
@@ -0,0 +1,160 @@
+//! The schema migrations, built into the library so the `diesel` CLI isn't
+//! needed to set up or upgrade a database.
+//!
+//! Each backend has its own directory, `migrations/postgres` and
+//! `migrations/sqlite`, embedded with `embed_migrations!`. They are recorded
+//! in the same `__diesel_schema_migrations` table the CLI uses, so databases
+//! migrated either way can be carried on with the other.
+
+use crate::connection::BlogConnection;
+use crate::error::{BlogError, Result};
+use diesel::backend::Backend;
+use diesel::migration::{MigrationSource, MigrationVersion};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use serde::Serialize;
+
+#[cfg(feature = "postgres")]
+pub const POSTGRES_MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/postgres");
+#[cfg(feature = "sqlite")]
+pub const SQLITE_MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/sqlite");
+
+/// Evaluates `$body` with `$conn` bound to the concrete connection and the
+/// constant `$source` to the migrations of its backend.
+macro_rules! with_migrations {
+    ($connection:expr, $conn:ident, $source:ident => $body:expr) => {
+        match $connection {
+            #[cfg(feature = "postgres")]
+            BlogConnection::Pg($conn) => {
+                const $source: EmbeddedMigrations = POSTGRES_MIGRATIONS;
+                $body
+            }
+            #[cfg(feature = "sqlite")]
+            BlogConnection::Sqlite($conn) => {
+                const $source: EmbeddedMigrations = SQLITE_MIGRATIONS;
+                $body
+            }
+        }
+    };
+}
+
+/// A migration and whether the database has it.
+#[derive(Debug, Serialize)]
+pub struct MigrationStatus {
+    pub name: String,
+    pub applied: bool,
+}
+
+/// Every migration, oldest first.
+pub fn status(conn: &mut BlogConnection) -> Result<Vec<MigrationStatus>> {
+    with_migrations!(conn, conn, SOURCE => statuses(conn, SOURCE))
+}
+
+/// The names of the migrations the database doesn't have yet.
+pub fn pending(conn: &mut BlogConnection) -> Result<Vec<String>> {
+    with_migrations!(conn, conn, SOURCE => pending_names(conn, SOURCE))
+}
+
+/// Fails with [`BlogError::PendingMigrations`] unless the database is up to
+/// date.
+pub fn check(conn: &mut BlogConnection) -> Result<()> {
+    with_migrations!(conn, conn, SOURCE => check_with(conn, SOURCE))
+}
+
+/// Runs the pending migrations, each in its own transaction, and returns
+/// their names.
+pub fn run_pending(conn: &mut BlogConnection) -> Result<Vec<String>> {
+    with_migrations!(conn, conn, SOURCE => {
+        let pending = pending_names(conn, SOURCE)?;
+        conn.run_pending_migrations(SOURCE).map_err(BlogError::Migration)?;
+        Ok(pending)
+    })
+}
+
+/// Reverts the newest applied migration and returns its name, or `None` if
+/// there is none.
+pub fn revert_last(conn: &mut BlogConnection) -> Result<Option<String>> {
+    with_migrations!(conn, conn, SOURCE => {
+        let Some((name, _)) = last_applied(conn, SOURCE)? else {
+            return Ok(None);
+        };
+        conn.revert_last_migration(SOURCE).map_err(BlogError::Migration)?;
+        Ok(Some(name))
+    })
+}
+
+/// Reverts the newest applied migration and runs it again, for trying out
+/// changes to its `down.sql`. Returns its name, or `None` if there is none.
+pub fn redo(conn: &mut BlogConnection) -> Result<Option<String>> {
+    with_migrations!(conn, conn, SOURCE => {
+        let Some((name, version)) = last_applied(conn, SOURCE)? else {
+            return Ok(None);
+        };
+        conn.revert_last_migration(SOURCE).map_err(BlogError::Migration)?;
+        let migrations = conn.pending_migrations(SOURCE).map_err(BlogError::Migration)?;
+        if let Some(migration) = migrations.iter().find(|migration| migration.name().version() == version) {
+            conn.run_migration(&**migration).map_err(BlogError::Migration)?;
+        }
+        Ok(Some(name))
+    })
+}
+
+pub(crate) fn check_with<DB: Backend>(conn: &mut impl MigrationHarness<DB>, source: EmbeddedMigrations) -> Result<()>
+where
+    EmbeddedMigrations: MigrationSource<DB>,
+{
+    let pending = pending_names(conn, source)?;
+    if pending.is_empty() {
+        Ok(())
+    } else {
+        Err(BlogError::PendingMigrations(pending))
+    }
+}
+
+fn pending_names<DB: Backend>(conn: &mut impl MigrationHarness<DB>, source: EmbeddedMigrations) -> Result<Vec<String>>
+where
+    EmbeddedMigrations: MigrationSource<DB>,
+{
+    Ok(conn
+        .pending_migrations(source)
+        .map_err(BlogError::Migration)?
+        .iter()
+        .map(|migration| migration.name().to_string())
+        .collect())
+}
+
+fn statuses<DB: Backend>(conn: &mut impl MigrationHarness<DB>, source: EmbeddedMigrations) -> Result<Vec<MigrationStatus>>
+where
+    EmbeddedMigrations: MigrationSource<DB>,
+{
+    let applied = conn.applied_migrations().map_err(BlogError::Migration)?;
+    Ok(names::<DB>(source)?
+        .into_iter()
+        .map(|(name, version)| MigrationStatus { applied: applied.contains(&version), name })
+        .collect())
+}
+
+/// The names and versions of all the migrations in `source`, oldest first.
+fn names<DB: Backend>(source: EmbeddedMigrations) -> Result<Vec<(String, MigrationVersion<'static>)>>
+where
+    EmbeddedMigrations: MigrationSource<DB>,
+{
+    Ok(MigrationSource::<DB>::migrations(&source)
+        .map_err(BlogError::Migration)?
+        .iter()
+        .map(|migration| (migration.name().to_string(), migration.name().version().as_owned()))
+        .collect())
+}
+
+/// The newest applied migration this build knows about.
+fn last_applied<DB: Backend>(
+    conn: &mut impl MigrationHarness<DB>,
+    source: EmbeddedMigrations,
+) -> Result<Option<(String, MigrationVersion<'static>)>>
+where
+    EmbeddedMigrations: MigrationSource<DB>,
+{
+    let applied = conn.applied_migrations().map_err(BlogError::Migration)?;
+    Ok(names::<DB>(source)?
+        .into_iter()
+        .rfind(|(_, version)| applied.contains(version)))
+}
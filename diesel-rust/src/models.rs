@@ -1,14 +1,234 @@
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
 use diesel::prelude::*;
-use crate::schema::posts;
+use diesel::serialize::{self, Output, ToSql};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use crate::schema::{attachments, authors, comments, post_reviews, post_revisions, posts, posts_tags, tags};
 
-#[derive(Queryable, Selectable)]
+/// Where a post is in its life: drafts are written, scheduled posts wait for
+/// `publish_at`, published posts are public and archived ones are kept out
+/// of listings without being deleted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, FromSqlRow, AsExpression, Serialize, Deserialize)]
+#[diesel(sql_type = crate::schema::sql_types::PostStatus)]
+#[serde(rename_all = "lowercase")]
+pub enum PostStatus {
+    Draft,
+    Scheduled,
+    Published,
+    Archived,
+}
+
+impl PostStatus {
+    pub const ALL: [PostStatus; 4] = [
+        PostStatus::Draft,
+        PostStatus::Scheduled,
+        PostStatus::Published,
+        PostStatus::Archived,
+    ];
+
+    /// The label used in the database and on the command line.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PostStatus::Draft => "draft",
+            PostStatus::Scheduled => "scheduled",
+            PostStatus::Published => "published",
+            PostStatus::Archived => "archived",
+        }
+    }
+}
+
+impl fmt::Display for PostStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for PostStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PostStatus::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .ok_or_else(|| format!("unknown post status '{}'", s))
+    }
+}
+
+/// Where a post is in review. A draft is sent for review, which leaves it
+/// pending until a reviewer approves it, and only approved posts are
+/// published or scheduled. A rejected draft goes back to unreviewed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, FromSqlRow, AsExpression, Serialize, Deserialize)]
+#[diesel(sql_type = crate::schema::sql_types::ReviewState)]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewState {
+    #[default]
+    Unreviewed,
+    Pending,
+    Approved,
+}
+
+impl ReviewState {
+    pub const ALL: [ReviewState; 3] = [ReviewState::Unreviewed, ReviewState::Pending, ReviewState::Approved];
+
+    /// The label used in the database and on the command line.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReviewState::Unreviewed => "unreviewed",
+            ReviewState::Pending => "pending",
+            ReviewState::Approved => "approved",
+        }
+    }
+}
+
+impl fmt::Display for ReviewState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ReviewState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ReviewState::ALL
+            .into_iter()
+            .find(|state| state.as_str() == s)
+            .ok_or_else(|| format!("unknown review state '{}'", s))
+    }
+}
+
+// On Postgres these are enums (`post_status`, `review_state`), which travel
+// as their labels; on SQLite they are text columns with a CHECK constraint.
+macro_rules! label_sql {
+    ($type:ident) => {
+        #[cfg(feature = "postgres")]
+        impl ToSql<crate::schema::sql_types::$type, diesel::pg::Pg> for $type {
+            fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, diesel::pg::Pg>) -> serialize::Result {
+                use std::io::Write;
+                out.write_all(self.as_str().as_bytes())?;
+                Ok(serialize::IsNull::No)
+            }
+        }
+
+        #[cfg(feature = "postgres")]
+        impl FromSql<crate::schema::sql_types::$type, diesel::pg::Pg> for $type {
+            fn from_sql(bytes: diesel::pg::PgValue<'_>) -> deserialize::Result<Self> {
+                Ok(std::str::from_utf8(bytes.as_bytes())?.parse()?)
+            }
+        }
+
+        #[cfg(feature = "sqlite")]
+        impl ToSql<crate::schema::sql_types::$type, diesel::sqlite::Sqlite> for $type {
+            fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, diesel::sqlite::Sqlite>) -> serialize::Result {
+                out.set_value(self.as_str());
+                Ok(serialize::IsNull::No)
+            }
+        }
+
+        #[cfg(feature = "sqlite")]
+        impl FromSql<crate::schema::sql_types::$type, diesel::sqlite::Sqlite> for $type {
+            fn from_sql(value: diesel::sqlite::SqliteValue<'_, '_, '_>) -> deserialize::Result<Self> {
+                let label = <String as FromSql<diesel::sql_types::Text, diesel::sqlite::Sqlite>>::from_sql(value)?;
+                Ok(label.parse()?)
+            }
+        }
+    };
+}
+
+label_sql!(PostStatus);
+label_sql!(ReviewState);
+
+/// A step of the publishing workflow, checked with [`Post::allows`] before
+/// it is taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    RequestReview,
+    Approve,
+    Reject,
+    Publish,
+    Schedule,
+    Unpublish,
+    Archive,
+}
+
+impl fmt::Display for Transition {
+    /// What the post would become, as in "can't be approved".
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Transition::RequestReview => "sent for review",
+            Transition::Approve => "approved",
+            Transition::Reject => "rejected",
+            Transition::Publish => "published",
+            Transition::Schedule => "scheduled",
+            Transition::Unpublish => "unpublished",
+            Transition::Archive => "archived",
+        })
+    }
+}
+
+#[derive(Queryable, QueryableByName, Selectable, Identifiable, Associations, Insertable, Serialize, Deserialize)]
+#[diesel(belongs_to(Author))]
 #[diesel(table_name = crate::schema::posts)]
-#[diesel(check_for_backend(diesel::pg::Pg))]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
+#[cfg_attr(feature = "sqlite", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
 pub struct Post {
     pub id: i32,
     pub title: String,
     pub body: String,
-    pub published: bool,
+    pub status: PostStatus,
+    pub slug: String,
+    pub author_id: Option<i32>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub published_at: Option<NaiveDateTime>,
+    /// When a scheduled post is due to be published.
+    pub publish_at: Option<NaiveDateTime>,
+    /// Missing from version 1 snapshots, which
+    /// [`Snapshot::from_json`](crate::snapshot::Snapshot::from_json) upgrades.
+    #[serde(default)]
+    pub review: ReviewState,
+}
+
+impl Post {
+    pub fn is_published(&self) -> bool {
+        self.status == PostStatus::Published
+    }
+
+    /// Whether the workflow lets the post take `transition` from where it
+    /// is: drafts are sent for review, pending ones approved or rejected, and
+    /// only approved posts go out.
+    pub fn allows(&self, transition: Transition) -> bool {
+        match transition {
+            Transition::RequestReview => self.status == PostStatus::Draft && self.review != ReviewState::Pending,
+            Transition::Approve | Transition::Reject => self.review == ReviewState::Pending,
+            Transition::Publish | Transition::Schedule => {
+                self.review == ReviewState::Approved && self.status != PostStatus::Published
+            }
+            Transition::Unpublish => self.status != PostStatus::Draft || self.review == ReviewState::Pending,
+            Transition::Archive => self.status != PostStatus::Archived,
+        }
+    }
+
+    /// Whether `edited`, this post with a new title or body, has to be
+    /// reviewed again: a post that isn't out yet may only go out with text a
+    /// reviewer approved.
+    pub fn edit_needs_review(&self, edited: &Post) -> bool {
+        !self.is_published()
+            && self.review != ReviewState::Unreviewed
+            && (self.title != edited.title || self.body != edited.body)
+    }
+
+    /// Where the post is in the workflow, as in "while it is a draft".
+    pub fn stage(&self) -> &'static str {
+        match (self.status, self.review) {
+            (PostStatus::Draft, ReviewState::Pending) => "waiting for review",
+            (PostStatus::Draft, _) => "a draft",
+            (status, _) => status.as_str(),
+        }
+    }
 }
 
 #[derive(Insertable)]
@@ -16,4 +236,176 @@ pub struct Post {
 pub struct NewPost<'a> {
     pub title: &'a str,
     pub body: &'a str,
-}
\ No newline at end of file
+    pub slug: &'a str,
+    pub author_id: Option<i32>,
+}
+
+/// Fields to change on an existing post; `None` leaves a field as it is.
+#[derive(AsChangeset)]
+#[diesel(table_name = posts)]
+pub struct PostChanges<'a> {
+    pub title: Option<&'a str>,
+    pub body: Option<&'a str>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Insertable, Serialize, Deserialize, Clone)]
+#[diesel(table_name = authors)]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
+#[cfg_attr(feature = "sqlite", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+pub struct Author {
+    pub id: i32,
+    pub name: String,
+    pub email: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = authors)]
+pub struct NewAuthor<'a> {
+    pub name: &'a str,
+    pub email: &'a str,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = tags)]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
+#[cfg_attr(feature = "sqlite", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+pub struct Tag {
+    pub id: i32,
+    pub name: String,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Associations, Insertable, Serialize, Deserialize)]
+#[diesel(belongs_to(Post))]
+#[diesel(belongs_to(Tag))]
+#[diesel(table_name = posts_tags)]
+#[diesel(primary_key(post_id, tag_id))]
+pub struct PostTag {
+    pub post_id: i32,
+    pub tag_id: i32,
+}
+
+/// An approval or rejection of a post, by one of the blog's authors.
+#[derive(Queryable, Selectable, Identifiable, Associations, Insertable, Serialize, Deserialize)]
+#[diesel(belongs_to(Post))]
+#[diesel(table_name = post_reviews)]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
+#[cfg_attr(feature = "sqlite", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+pub struct PostReview {
+    pub id: i32,
+    pub post_id: i32,
+    pub reviewer_id: i32,
+    pub approved: bool,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = post_reviews)]
+pub struct NewPostReview {
+    pub post_id: i32,
+    pub reviewer_id: i32,
+    pub approved: bool,
+}
+
+/// A post with its author and tags, as shown by `blog show`.
+#[derive(Serialize)]
+pub struct PostDetails {
+    #[serde(flatten)]
+    pub post: Post,
+    pub author: Option<Author>,
+    pub tags: Vec<Tag>,
+    pub attachments: Vec<Attachment>,
+}
+
+/// A saved version of a post's title and body. The newest revision is the
+/// post as it is now.
+#[derive(Queryable, Selectable, Identifiable, Associations, Insertable, Serialize, Deserialize)]
+#[diesel(belongs_to(Post))]
+#[diesel(table_name = post_revisions)]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
+#[cfg_attr(feature = "sqlite", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+pub struct Revision {
+    pub id: i32,
+    pub post_id: i32,
+    pub revision: i32,
+    pub title: String,
+    pub body: String,
+    pub created_at: NaiveDateTime,
+}
+
+/// A reader's comment. It is only shown once approved.
+#[derive(Queryable, Selectable, Identifiable, Associations, Insertable, Serialize, Deserialize, Clone)]
+#[diesel(belongs_to(Post))]
+#[diesel(table_name = comments)]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
+#[cfg_attr(feature = "sqlite", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+pub struct Comment {
+    pub id: i32,
+    pub post_id: i32,
+    /// The comment this one replies to.
+    pub parent_id: Option<i32>,
+    pub author_name: String,
+    pub author_email: String,
+    pub body: String,
+    pub approved: bool,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = comments)]
+pub struct NewComment<'a> {
+    pub post_id: i32,
+    pub parent_id: Option<i32>,
+    pub author_name: &'a str,
+    pub author_email: &'a str,
+    pub body: &'a str,
+}
+
+/// A file attached to a post. Post bodies refer to it as
+/// `attachment:<filename>`.
+#[derive(Queryable, Selectable, Identifiable, Associations, Insertable, Serialize, Deserialize, Clone, Debug)]
+#[diesel(belongs_to(Post))]
+#[diesel(table_name = attachments)]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
+#[cfg_attr(feature = "sqlite", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+pub struct Attachment {
+    pub id: i32,
+    pub post_id: i32,
+    pub filename: String,
+    pub mime_type: String,
+    /// In bytes.
+    pub size: i64,
+    /// Hex digest of the contents.
+    pub sha256: String,
+    /// Where the contents are kept in the [`Storage`](crate::storage::Storage).
+    pub storage_key: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = attachments)]
+pub struct NewAttachment<'a> {
+    pub post_id: i32,
+    pub filename: &'a str,
+    pub mime_type: &'a str,
+    pub size: i64,
+    pub sha256: &'a str,
+    pub storage_key: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statuses_round_trip_through_their_labels() {
+        for status in PostStatus::ALL {
+            assert_eq!(Ok(status), status.as_str().parse());
+        }
+        assert!("live".parse::<PostStatus>().is_err());
+        assert_eq!("\"scheduled\"", serde_json::to_string(&PostStatus::Scheduled).unwrap());
+        for state in ReviewState::ALL {
+            assert_eq!(Ok(state), state.as_str().parse());
+        }
+    }
+}
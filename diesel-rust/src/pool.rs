@@ -0,0 +1,184 @@
+use crate::connection::{dispatch, BlogConnection};
+use crate::error::{BlogError, Result};
+use crate::migrations;
+use diesel::connection::{AnsiTransactionManager, SimpleConnection, TransactionManager};
+use diesel::r2d2::{ManageConnection, Pool, PooledConnection};
+use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[cfg(feature = "postgres")]
+use diesel::{pg::PgConnection, r2d2::ConnectionManager};
+
+#[cfg(feature = "postgres")]
+pub type PgPool = Pool<ConnectionManager<PgConnection>>;
+#[cfg(feature = "postgres")]
+pub type PgPooledConnection = PooledConnection<ConnectionManager<PgConnection>>;
+
+/// A pool of connections to whichever database `DATABASE_URL` names.
+pub type BlogPool = Pool<BlogConnectionManager>;
+pub type BlogPooledConnection = PooledConnection<BlogConnectionManager>;
+
+/// Size and timeouts of a connection pool.
+///
+/// [`PoolConfig::from_env`] reads these from `DATABASE_POOL_MAX_SIZE`,
+/// `DATABASE_POOL_MIN_IDLE`, `DATABASE_POOL_TIMEOUT_SECS`,
+/// `DATABASE_POOL_IDLE_TIMEOUT_SECS` and `DATABASE_POOL_MAX_LIFETIME_SECS`,
+/// falling back to the defaults for anything unset.
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    pub max_size: u32,
+    /// Idle connections to keep open; `None` keeps `max_size` of them.
+    pub min_idle: Option<u32>,
+    /// How long to wait for a free connection.
+    pub connection_timeout: Duration,
+    /// Close connections idle for longer than this.
+    pub idle_timeout: Option<Duration>,
+    /// Close connections older than this.
+    pub max_lifetime: Option<Duration>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            max_size: 10,
+            min_idle: None,
+            connection_timeout: Duration::from_secs(30),
+            idle_timeout: Some(Duration::from_secs(10 * 60)),
+            max_lifetime: Some(Duration::from_secs(30 * 60)),
+        }
+    }
+}
+
+fn env_var<T: FromStr>(name: &str) -> Result<Option<T>> {
+    match env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| BlogError::Config(format!("{} must be a number, got '{}'", name, value))),
+        Err(_) => Ok(None),
+    }
+}
+
+impl PoolConfig {
+    pub fn from_env() -> Result<Self> {
+        dotenvy::dotenv().ok();
+        let defaults = PoolConfig::default();
+
+        Ok(PoolConfig {
+            max_size: env_var("DATABASE_POOL_MAX_SIZE")?.unwrap_or(defaults.max_size),
+            min_idle: env_var("DATABASE_POOL_MIN_IDLE")?.or(defaults.min_idle),
+            connection_timeout: env_var("DATABASE_POOL_TIMEOUT_SECS")?
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.connection_timeout),
+            idle_timeout: env_var("DATABASE_POOL_IDLE_TIMEOUT_SECS")?
+                .map(Duration::from_secs)
+                .or(defaults.idle_timeout),
+            max_lifetime: env_var("DATABASE_POOL_MAX_LIFETIME_SECS")?
+                .map(Duration::from_secs)
+                .or(defaults.max_lifetime),
+        })
+    }
+}
+
+/// Opens [`BlogConnection`]s for r2d2, like diesel's `ConnectionManager`
+/// does for a single connection type.
+#[derive(Debug)]
+pub struct BlogConnectionManager {
+    database_url: String,
+}
+
+impl BlogConnectionManager {
+    pub fn new(database_url: impl Into<String>) -> Self {
+        BlogConnectionManager { database_url: database_url.into() }
+    }
+}
+
+impl ManageConnection for BlogConnectionManager {
+    type Connection = BlogConnection;
+    type Error = BlogError;
+
+    fn connect(&self) -> Result<BlogConnection> {
+        BlogConnection::establish(&self.database_url)
+    }
+
+    fn is_valid(&self, conn: &mut BlogConnection) -> Result<()> {
+        Ok(dispatch!(conn, conn => conn.batch_execute("SELECT 1"))?)
+    }
+
+    fn has_broken(&self, conn: &mut BlogConnection) -> bool {
+        dispatch!(conn, conn => AnsiTransactionManager::is_broken_transaction_manager(conn))
+    }
+}
+
+fn builder<M: ManageConnection>(config: &PoolConfig) -> diesel::r2d2::Builder<M> {
+    Pool::builder()
+        .max_size(config.max_size)
+        .min_idle(config.min_idle)
+        .connection_timeout(config.connection_timeout)
+        .idle_timeout(config.idle_timeout)
+        .max_lifetime(config.max_lifetime)
+}
+
+/// Builds an r2d2 pool, opening `min_idle` connections up front.
+#[cfg(feature = "postgres")]
+pub fn build_pool(database_url: &str, config: &PoolConfig) -> Result<PgPool> {
+    builder(config)
+        .build(ConnectionManager::<PgConnection>::new(database_url))
+        .map_err(BlogError::Pool)
+}
+
+/// Like [`build_pool`], for a Postgres URL or a SQLite database file.
+pub fn build_blog_pool(database_url: &str, config: &PoolConfig) -> Result<BlogPool> {
+    builder(config)
+        .build(BlogConnectionManager::new(database_url))
+        .map_err(BlogError::Pool)
+}
+
+/// Builds a pool for `DATABASE_URL` configured from the environment, and
+/// checks that the database has every migration this build was made with.
+#[cfg(feature = "postgres")]
+pub fn establish_pool() -> Result<PgPool> {
+    let pool = build_pool(&crate::database_url()?, &PoolConfig::from_env()?)?;
+    migrations::check_with(&mut *pool.get()?, migrations::POSTGRES_MIGRATIONS)?;
+    Ok(pool)
+}
+
+/// Like [`establish_pool`], for a Postgres URL or a SQLite database file.
+pub fn establish_blog_pool() -> Result<BlogPool> {
+    let pool = build_blog_pool(&crate::database_url()?, &PoolConfig::from_env()?)?;
+    migrations::check(&mut *pool.get()?)?;
+    Ok(pool)
+}
+
+#[cfg(feature = "async")]
+pub mod r#async {
+    use super::PoolConfig;
+    use crate::error::{BlogError, Result};
+    use diesel_async::pooled_connection::deadpool::Pool;
+    use diesel_async::pooled_connection::AsyncDieselConnectionManager;
+    use diesel_async::AsyncPgConnection;
+
+    pub type AsyncPgPool = Pool<AsyncPgConnection>;
+
+    /// Builds a deadpool pool of diesel-async connections. Connections are
+    /// opened on demand; deadpool only uses `max_size` and
+    /// `connection_timeout` from the config.
+    pub fn build_pool(database_url: &str, config: &PoolConfig) -> Result<AsyncPgPool> {
+        let manager = AsyncDieselConnectionManager::<AsyncPgConnection>::new(database_url);
+
+        Pool::builder(manager)
+            .max_size(config.max_size as usize)
+            .wait_timeout(Some(config.connection_timeout))
+            .create_timeout(Some(config.connection_timeout))
+            .runtime(deadpool::Runtime::Tokio1)
+            .build()
+            .map_err(|err| BlogError::Config(format!("Error building connection pool: {}", err)))
+    }
+
+    /// Builds an async pool for `DATABASE_URL` configured from the environment.
+    pub fn establish_pool() -> Result<AsyncPgPool> {
+        build_pool(&crate::database_url()?, &PoolConfig::from_env()?)
+    }
+}
@@ -0,0 +1,418 @@
+//! Filtering, sorting and paging posts.
+//!
+//! [`PostQuery`] only describes the listing; the repositories run it with
+//! [`PostRepository::query`](crate::PostRepository::query), so the CLI and a
+//! web front end share one set of filters.
+
+use crate::models::{Post, PostStatus};
+use crate::schema::posts::dsl::*;
+use crate::schema::{posts_tags, tags};
+use crate::slug::normalize_tag;
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::backend::Backend;
+use diesel::prelude::*;
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+pub type BoxedPostQuery<DB> = crate::schema::posts::BoxedQuery<'static, DB>;
+
+/// Which posts a listing includes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    Published,
+    /// Drafts and scheduled posts: everything not out yet.
+    Drafts,
+    Archived,
+    All,
+}
+
+impl Visibility {
+    /// The statuses of the posts included; `None` for all of them.
+    pub fn statuses(self) -> Option<&'static [PostStatus]> {
+        match self {
+            Visibility::Published => Some(&[PostStatus::Published]),
+            Visibility::Drafts => Some(&[PostStatus::Draft, PostStatus::Scheduled]),
+            Visibility::Archived => Some(&[PostStatus::Archived]),
+            Visibility::All => None,
+        }
+    }
+}
+
+/// Order of a listing. Ties are broken by id so pages never overlap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sort {
+    /// Oldest first, which is also the order posts were written in.
+    Oldest,
+    Newest,
+    /// Most recently edited first.
+    Updated,
+    Title,
+}
+
+impl FromStr for Sort {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "oldest" => Ok(Sort::Oldest),
+            "newest" => Ok(Sort::Newest),
+            "updated" => Ok(Sort::Updated),
+            "title" => Ok(Sort::Title),
+            _ => Err(format!(
+                "unknown sort '{}', expected oldest, newest, updated or title",
+                name
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Sort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Sort::Oldest => "oldest",
+            Sort::Newest => "newest",
+            Sort::Updated => "updated",
+            Sort::Title => "title",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Parses `--since`: a date (`2024-04-01`, midnight) or a date and time
+/// (`2024-04-01T12:30` or `2024-04-01 12:30:15`).
+pub fn parse_since(value: &str) -> Result<NaiveDateTime, String> {
+    let value = value.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap());
+    }
+
+    ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .ok_or_else(|| format!("expected a date like 2024-04-01 or 2024-04-01T12:30, got '{}'", value))
+}
+
+/// Where a listing starts: a page number, or right after a given post
+/// (keyset paging, which stays stable while posts are being added).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Start {
+    Page(i64),
+    After(i32),
+}
+
+// Leaves room for the extra row `paged` loads.
+fn clamp_per_page(per_page: i64) -> i64 {
+    per_page.clamp(1, i64::MAX - 1)
+}
+
+/// A listing of posts, built up with chained calls:
+///
+/// ```
+/// use diesel_rust::query::{PostQuery, Sort};
+///
+/// let query = PostQuery::new().tag("rust").sort(Sort::Newest).page(2, 10);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PostQuery {
+    visibility: Visibility,
+    tag: Option<String>,
+    author: Option<i32>,
+    since: Option<NaiveDateTime>,
+    sort: Sort,
+    start: Start,
+    per_page: i64,
+}
+
+impl Default for PostQuery {
+    fn default() -> Self {
+        PostQuery {
+            visibility: Visibility::Published,
+            tag: None,
+            author: None,
+            since: None,
+            sort: Sort::Oldest,
+            start: Start::Page(1),
+            per_page: 5,
+        }
+    }
+}
+
+/// One page of a listing.
+#[derive(Serialize)]
+pub struct PostPage {
+    pub posts: Vec<Post>,
+    /// Number of posts matching the filters, on all pages.
+    pub total: i64,
+    /// The page number, when paging by number.
+    pub page: Option<i64>,
+    /// Pass to [`PostQuery::after`] for the next page; `None` on the last.
+    pub next_after: Option<i32>,
+}
+
+impl PostQuery {
+    /// The first five published posts, oldest first.
+    pub fn new() -> Self {
+        PostQuery::default()
+    }
+
+    pub fn visibility(mut self, visibility: Visibility) -> Self {
+        self.visibility = visibility;
+        self
+    }
+
+    /// Only posts with this tag; the name is normalized like a slug.
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    /// Only posts by this author.
+    pub fn author(mut self, author: i32) -> Self {
+        self.author = Some(author);
+        self
+    }
+
+    /// Only posts created at or after `since`.
+    pub fn since(mut self, since: NaiveDateTime) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    pub fn sort(mut self, sort: Sort) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Page `page` (from 1) of `per_page` posts. Pages past
+    /// [`max_page`](Self::max_page) are taken as that page.
+    pub fn page(mut self, page: i64, per_page: i64) -> Self {
+        self.per_page = clamp_per_page(per_page);
+        self.start = Start::Page(page.clamp(1, PostQuery::max_page(self.per_page)));
+        self
+    }
+
+    /// The `per_page` posts that come after post `post_id` in this order.
+    pub fn after(mut self, post_id: i32, per_page: i64) -> Self {
+        self.start = Start::After(post_id);
+        self.per_page = clamp_per_page(per_page);
+        self
+    }
+
+    /// The last page number whose offset fits in an `i64` with pages of
+    /// `per_page` posts.
+    pub fn max_page(per_page: i64) -> i64 {
+        i64::MAX / clamp_per_page(per_page)
+    }
+
+    pub fn start(&self) -> Start {
+        self.start
+    }
+
+    /// All posts matching the filters, unordered and unpaged.
+    pub fn filtered<DB: QueryBackend>(&self) -> BoxedPostQuery<DB> {
+        DB::filtered(self)
+    }
+
+    /// The posts of this page, plus one more to tell whether there is a next
+    /// page. `cursor` is the post named by [`after`](Self::after).
+    pub fn paged<DB: QueryBackend>(&self, cursor: Option<&Post>) -> BoxedPostQuery<DB> {
+        DB::paged(self, cursor)
+    }
+
+    /// Turns the rows loaded with [`paged`](Self::paged) into a page.
+    pub fn finish(&self, mut rows: Vec<Post>, total: i64) -> PostPage {
+        let more = rows.len() as i64 > self.per_page;
+        rows.truncate(self.per_page as usize);
+
+        PostPage {
+            next_after: if more { rows.last().map(|post| post.id) } else { None },
+            page: match self.start {
+                Start::Page(page) => Some(page),
+                Start::After(_) => None,
+            },
+            total,
+            posts: rows,
+        }
+    }
+}
+
+/// Backends a [`PostQuery`] can be built for. The query is the same on every
+/// backend; only the boxed type differs, so the impls come from one macro.
+pub trait QueryBackend: Backend + Sized {
+    fn filtered(query: &PostQuery) -> BoxedPostQuery<Self>;
+
+    fn paged(query: &PostQuery, cursor: Option<&Post>) -> BoxedPostQuery<Self>;
+}
+
+macro_rules! query_backend {
+    ($backend:ty) => {
+        impl QueryBackend for $backend {
+            fn filtered(q: &PostQuery) -> BoxedPostQuery<Self> {
+                let mut query = posts.into_boxed();
+
+                if let Some(statuses) = q.visibility.statuses() {
+                    query = query.filter(status.eq_any(statuses));
+                }
+                if let Some(tag) = &q.tag {
+                    let tag_ids = tags::table
+                        .filter(tags::name.eq(normalize_tag(tag).unwrap_or_default()))
+                        .select(tags::id);
+                    let tagged = posts_tags::table
+                        .filter(posts_tags::tag_id.eq_any(tag_ids))
+                        .select(posts_tags::post_id);
+                    query = query.filter(id.eq_any(tagged));
+                }
+                if let Some(author) = q.author {
+                    query = query.filter(author_id.eq(author));
+                }
+                if let Some(since) = q.since {
+                    query = query.filter(created_at.ge(since));
+                }
+
+                query
+            }
+
+            fn paged(q: &PostQuery, cursor: Option<&Post>) -> BoxedPostQuery<Self> {
+                let mut query = Self::filtered(q);
+
+                query = match q.sort {
+                    Sort::Oldest => query.order((created_at.asc(), id.asc())),
+                    Sort::Newest => query.order((created_at.desc(), id.desc())),
+                    Sort::Updated => query.order((updated_at.desc(), id.desc())),
+                    Sort::Title => query.order((title.asc(), id.asc())),
+                };
+
+                if let Some(cursor) = cursor {
+                    query = match q.sort {
+                        Sort::Oldest => query.filter(
+                            created_at
+                                .gt(cursor.created_at)
+                                .or(created_at.eq(cursor.created_at).and(id.gt(cursor.id))),
+                        ),
+                        Sort::Newest => query.filter(
+                            created_at
+                                .lt(cursor.created_at)
+                                .or(created_at.eq(cursor.created_at).and(id.lt(cursor.id))),
+                        ),
+                        Sort::Updated => query.filter(
+                            updated_at
+                                .lt(cursor.updated_at)
+                                .or(updated_at.eq(cursor.updated_at).and(id.lt(cursor.id))),
+                        ),
+                        Sort::Title => query.filter(
+                            title
+                                .gt(cursor.title.clone())
+                                .or(title.eq(cursor.title.clone()).and(id.gt(cursor.id))),
+                        ),
+                    };
+                }
+
+                if let Start::Page(page) = q.start {
+                    query = query.offset((page - 1) * q.per_page);
+                }
+                query.limit(q.per_page + 1)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "postgres")]
+query_backend!(diesel::pg::Pg);
+#[cfg(feature = "sqlite")]
+query_backend!(diesel::sqlite::Sqlite);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ReviewState;
+    #[cfg(feature = "postgres")]
+    use diesel::{debug_query, pg::Pg};
+
+    #[cfg(feature = "postgres")]
+    fn sql(query: BoxedPostQuery<Pg>) -> String {
+        debug_query::<Pg, _>(&query).to_string()
+    }
+
+    #[test]
+    fn parses_sorts() {
+        assert_eq!(Ok(Sort::Newest), "newest".parse());
+        assert_eq!("title", Sort::Title.to_string());
+        assert!("random".parse::<Sort>().is_err());
+    }
+
+    #[test]
+    fn parses_since() {
+        let midnight = NaiveDate::from_ymd_opt(2024, 4, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        assert_eq!(Ok(midnight), parse_since("2024-04-01"));
+        let noon = NaiveDate::from_ymd_opt(2024, 4, 1).unwrap().and_hms_opt(12, 30, 0).unwrap();
+        assert_eq!(Ok(noon), parse_since("2024-04-01T12:30"));
+        assert_eq!(Ok(noon), parse_since("2024-04-01 12:30:00"));
+        assert!(parse_since("yesterday").is_err());
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn default_query_lists_published_posts() {
+        let sql = sql(PostQuery::new().paged::<Pg>(None));
+        assert!(sql.contains(r#"WHERE ("posts"."status" = ANY($1))"#), "{}", sql);
+        assert!(sql.contains(r#"ORDER BY "posts"."created_at" ASC, "posts"."id" ASC"#), "{}", sql);
+        assert!(sql.contains("LIMIT $2 OFFSET $3"), "{}", sql);
+        assert!(sql.contains("[[Published], 6, 0]"), "{}", sql);
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn filters_combine() {
+        let query = PostQuery::new()
+            .visibility(Visibility::All)
+            .tag("Rust Lang")
+            .author(7)
+            .page(3, 10);
+        let sql = sql(query.paged::<Pg>(None));
+        assert!(!sql.contains(r#""status" ="#), "{}", sql);
+        assert!(sql.contains(r#""posts_tags"."tag_id" = ANY(SELECT"#), "{}", sql);
+        assert!(sql.contains(r#""posts"."author_id" = $"#), "{}", sql);
+        assert!(sql.contains(r#"["rust-lang", 7, 11, 20]"#), "{}", sql);
+    }
+
+    #[test]
+    fn caps_pages_so_the_offset_fits() {
+        let query = PostQuery::new().page(i64::MAX, 10);
+        assert_eq!(Start::Page(i64::MAX / 10), query.start());
+        assert_eq!(Start::Page(1), PostQuery::new().page(-3, i64::MAX).start());
+        #[cfg(feature = "postgres")]
+        {
+            let sql = sql(query.paged::<Pg>(None));
+            assert!(sql.contains(&format!("[[Published], 11, {}]", (i64::MAX / 10 - 1) * 10)), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn finish_keeps_one_page_and_points_at_the_next() {
+        let stamp = parse_since("2024-04-01").unwrap();
+        let post = |post_id: i32| Post {
+            id: post_id,
+            title: format!("Post {}", post_id),
+            body: String::new(),
+            status: PostStatus::Published,
+            slug: format!("post-{}", post_id),
+            author_id: None,
+            created_at: stamp,
+            updated_at: stamp,
+            published_at: Some(stamp),
+            publish_at: None,
+            review: ReviewState::Approved,
+        };
+
+        let query = PostQuery::new().page(1, 2);
+        let page = query.finish(vec![post(1), post(2), post(3)], 3);
+        assert_eq!(vec![1, 2], page.posts.iter().map(|post| post.id).collect::<Vec<_>>());
+        assert_eq!(Some(2), page.next_after);
+        assert_eq!(Some(1), page.page);
+
+        let last = PostQuery::new().after(2, 2).finish(vec![post(3)], 3);
+        assert_eq!(None, last.next_after);
+        assert_eq!(None, last.page);
+    }
+}
@@ -0,0 +1,963 @@
+use crate::connection::{dispatch, BlogConnection};
+use crate::error::{BlogError, OrNotFound, Result};
+use crate::comments::{thread, CommentThread};
+use crate::models::{
+    Attachment, Author, Comment, NewAttachment, NewAuthor, NewComment, NewPost, NewPostReview, Post, PostChanges,
+    PostDetails, PostReview, PostStatus, PostTag, ReviewState, Revision, Tag, Transition,
+};
+use crate::query::{PostPage, PostQuery, Start, Visibility};
+use crate::schema::posts::dsl::*;
+use crate::schema::{attachments, authors, comments, post_reviews, post_revisions, posts_tags, tags};
+use crate::search::SearchHit;
+use crate::snapshot::{Snapshot, FORMAT_VERSION};
+use crate::slug::{next_free_slug, normalize_tag, slugify};
+use chrono::NaiveDateTime;
+use diesel::dsl::now;
+use diesel::prelude::*;
+
+/// Rows per `INSERT` when loading a snapshot, well under the bind parameter
+/// limits of both backends.
+const LOAD_CHUNK: usize = 500;
+
+/// Escapes `%`, `_` and the escape character itself so `input` only matches
+/// literally inside a `LIKE ... ESCAPE '\'` pattern.
+pub fn escape_like(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// `LIKE` pattern matching titles that contain `text`.
+pub(crate) fn contains_pattern(text: &str) -> String {
+    format!("%{}%", escape_like(text))
+}
+
+/// `LIKE` pattern matching `base` and its numbered variants.
+pub(crate) fn slug_pattern(base: &str) -> String {
+    format!("{}%", escape_like(base))
+}
+
+/// The first `limit` posts, with drafts or without.
+pub(crate) fn listing(include_drafts: bool, limit: i64) -> PostQuery {
+    let visibility = if include_drafts { Visibility::All } else { Visibility::Published };
+    PostQuery::new().visibility(visibility).page(1, limit)
+}
+
+/// Normalized, deduplicated tag names; names without letters or digits are
+/// dropped.
+pub(crate) fn tag_names(names: &[&str]) -> Vec<String> {
+    let mut normalized: Vec<String> = names.iter().filter_map(|name| normalize_tag(name)).collect();
+    normalized.sort();
+    normalized.dedup();
+    normalized
+}
+
+/// Search over the generated `tsvector` column, see [`crate::search`].
+#[cfg(feature = "postgres")]
+fn search_postgres(
+    conn: &mut diesel::pg::PgConnection,
+    text: &str,
+    visibility: Visibility,
+    limit: i64,
+) -> QueryResult<Vec<SearchHit>> {
+    use crate::search::{
+        ts_headline, ts_rank_cd, websearch_to_tsquery, English, TsvectorExpressionMethods, HEADLINE_OPTIONS,
+    };
+
+    let query = || websearch_to_tsquery(English, text.to_string());
+
+    let mut matching = posts.filter(search.matches(query())).into_boxed();
+    if let Some(statuses) = visibility.statuses() {
+        matching = matching.filter(status.eq_any(statuses));
+    }
+
+    let rank = || ts_rank_cd(search, query());
+    let hits: Vec<(Post, f32, String)> = matching
+        .select((
+            Post::as_select(),
+            rank(),
+            ts_headline(English, body, query(), HEADLINE_OPTIONS),
+        ))
+        .order((rank().desc(), id))
+        .limit(limit)
+        .load(conn)?;
+
+    Ok(hits
+        .into_iter()
+        .map(|(post, rank, snippet)| SearchHit { post, rank, snippet })
+        .collect())
+}
+
+/// Search over the `posts_fts` FTS5 table. Diesel has no DSL for virtual
+/// tables, so this one is written in SQL.
+#[cfg(feature = "sqlite")]
+fn search_sqlite(
+    conn: &mut diesel::sqlite::SqliteConnection,
+    text: &str,
+    visibility: Visibility,
+    limit: i64,
+) -> QueryResult<Vec<SearchHit>> {
+    use crate::search::{fts5_query, FtsHit};
+    use diesel::sql_types::{BigInt, Text};
+
+    let Some(query) = fts5_query(text) else {
+        return Ok(Vec::new());
+    };
+    // The statuses are our own labels, so they can go in the SQL as they are.
+    let only = match visibility.statuses() {
+        Some(statuses) => {
+            let labels: Vec<String> = statuses.iter().map(|wanted| format!("'{}'", wanted)).collect();
+            format!("AND posts.status IN ({})", labels.join(", "))
+        }
+        None => String::new(),
+    };
+    // bm25 scores better matches lower; titles weigh ten times the body.
+    let sql = format!(
+        "SELECT posts.*, -bm25(posts_fts, 10.0, 1.0) AS rank, \
+                snippet(posts_fts, 1, '<mark>', '</mark>', ' … ', 20) AS snippet \
+         FROM posts_fts JOIN posts ON posts.id = posts_fts.rowid \
+         WHERE posts_fts MATCH ? {} \
+         ORDER BY rank DESC, posts.id \
+         LIMIT ?",
+        only
+    );
+
+    let hits: Vec<FtsHit> = diesel::sql_query(sql)
+        .bind::<Text, _>(query)
+        .bind::<BigInt, _>(limit)
+        .load(conn)?;
+    Ok(hits.into_iter().map(SearchHit::from).collect())
+}
+
+/// Post queries over a borrowed connection, to Postgres or SQLite.
+///
+/// Every operation returns a `Result`; looking up a post that doesn't exist
+/// gives `BlogError::NotFound` rather than a database error.
+pub struct PostRepository<'a> {
+    conn: &'a mut BlogConnection,
+}
+
+impl<'a> PostRepository<'a> {
+    pub fn new(conn: &'a mut BlogConnection) -> Self {
+        PostRepository { conn }
+    }
+
+    /// Runs `f` in a transaction: when it fails, nothing it did is kept.
+    pub fn transaction<T>(&mut self, f: impl FnOnce(&mut PostRepository) -> Result<T>) -> Result<T> {
+        self.conn.transaction(|conn| f(&mut PostRepository { conn }))
+    }
+
+    /// Saves a new draft. Its slug is made from the title, numbered when
+    /// another post already uses it. Editing the title later keeps the slug,
+    /// so links to the post stay valid.
+    pub fn create(&mut self, new_title: &str, new_body: &str) -> Result<Post> {
+        let base = slugify(new_title);
+        Ok(dispatch!(self.conn, conn => conn.transaction(|conn| {
+            let taken: Vec<String> = posts
+                .select(slug)
+                .filter(slug.like(slug_pattern(&base)).escape('\\'))
+                .load(conn)?;
+            let new_slug = next_free_slug(&base, &taken);
+
+            let new_post = NewPost {
+                title: new_title,
+                body: new_body,
+                slug: &new_slug,
+                author_id: None,
+            };
+            diesel::insert_into(posts)
+                .values(&new_post)
+                .returning(Post::as_returning())
+                .get_result(conn)
+        }))?)
+    }
+
+    /// Saves a new draft under `new_slug` as it is, like a post imported
+    /// with the address it had elsewhere. Fails if the slug is taken.
+    pub fn create_with_slug(&mut self, new_title: &str, new_body: &str, new_slug: &str) -> Result<Post> {
+        let new_post = NewPost {
+            title: new_title,
+            body: new_body,
+            slug: new_slug,
+            author_id: None,
+        };
+        Ok(dispatch!(self.conn, conn => diesel::insert_into(posts)
+            .values(&new_post)
+            .returning(Post::as_returning())
+            .get_result(conn))?)
+    }
+
+    pub fn get(&mut self, post_id: i32) -> Result<Post> {
+        dispatch!(self.conn, conn => posts
+            .find(post_id)
+            .select(Post::as_select())
+            .first(conn)
+            .optional())
+        .or_not_found(post_id)
+    }
+
+    pub fn get_by_slug(&mut self, post_slug: &str) -> Result<Post> {
+        dispatch!(self.conn, conn => posts
+            .filter(slug.eq(post_slug))
+            .select(Post::as_select())
+            .first(conn)
+            .optional())
+        .or_else_missing(|| BlogError::SlugNotFound(post_slug.to_string()))
+    }
+
+    /// The post named on a command line: by slug, or by id when no post has
+    /// that slug. `#<id>` is always an id, for when a slug like `1984` is in
+    /// the way.
+    pub fn find(&mut self, post: &str) -> Result<Post> {
+        if let Some(post_id) = post.strip_prefix('#').and_then(|number| number.parse().ok()) {
+            return self.get(post_id);
+        }
+        match (self.get_by_slug(post), post.parse::<i32>()) {
+            (Err(BlogError::SlugNotFound(_)), Ok(post_id)) => self.get(post_id),
+            (found, _) => found,
+        }
+    }
+
+    /// A post together with its author, tags and attachments.
+    pub fn details(&mut self, post_id: i32) -> Result<PostDetails> {
+        let post = self.get(post_id)?;
+        let author = match post.author_id {
+            Some(author) => dispatch!(self.conn, conn => authors::table
+                .find(author)
+                .select(Author::as_select())
+                .first(conn)
+                .optional())?,
+            None => None,
+        };
+        let tags = self.tags_of(post_id)?;
+        let attachments = self.attachments(post_id)?;
+
+        Ok(PostDetails { post, author, tags, attachments })
+    }
+
+    /// Full-text search over titles and bodies, best matches first. On
+    /// Postgres the query is read like a web search box: `"a phrase"`, `or`,
+    /// `-word`; on SQLite every word has to match.
+    pub fn search(&mut self, text: &str, visibility: Visibility, limit: i64) -> Result<Vec<SearchHit>> {
+        if text.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(match self.conn {
+            #[cfg(feature = "postgres")]
+            BlogConnection::Pg(conn) => search_postgres(conn, text, visibility, limit)?,
+            #[cfg(feature = "sqlite")]
+            BlogConnection::Sqlite(conn) => search_sqlite(conn, text, visibility, limit)?,
+        })
+    }
+
+    /// Every published post with its author, tags and attachments, for
+    /// exporting.
+    pub fn published_details(&mut self) -> Result<Vec<PostDetails>> {
+        let (published_posts, post_tags, post_authors, post_attachments) = dispatch!(self.conn, conn => {
+            let published_posts: Vec<Post> = posts
+                .filter(status.eq(PostStatus::Published))
+                .order(id)
+                .select(Post::as_select())
+                .load(conn)?;
+
+            let post_tags: Vec<(PostTag, Tag)> = PostTag::belonging_to(&published_posts)
+                .inner_join(tags::table)
+                .order(tags::name)
+                .select((PostTag::as_select(), Tag::as_select()))
+                .load(conn)?;
+            let author_ids: Vec<i32> = published_posts.iter().filter_map(|post| post.author_id).collect();
+            let post_authors: Vec<Author> = authors::table
+                .filter(authors::id.eq_any(author_ids))
+                .select(Author::as_select())
+                .load(conn)?;
+            let post_attachments: Vec<Attachment> = Attachment::belonging_to(&published_posts)
+                .order(attachments::filename)
+                .select(Attachment::as_select())
+                .load(conn)?;
+            (published_posts, post_tags, post_authors, post_attachments)
+        });
+
+        let tags_per_post = post_tags.grouped_by(&published_posts);
+        let attachments_per_post = post_attachments.grouped_by(&published_posts);
+        Ok(published_posts
+            .into_iter()
+            .zip(tags_per_post)
+            .zip(attachments_per_post)
+            .map(|((post, post_tags), attachments)| PostDetails {
+                author: post
+                    .author_id
+                    .and_then(|author| post_authors.iter().find(|a| a.id == author).cloned()),
+                tags: post_tags.into_iter().map(|(_, tag)| tag).collect(),
+                attachments,
+                post,
+            })
+            .collect())
+    }
+
+    /// Runs a listing built with [`PostQuery`].
+    pub fn query(&mut self, query: &PostQuery) -> Result<PostPage> {
+        let cursor = match query.start() {
+            Start::After(post_id) => Some(self.get(post_id)?),
+            Start::Page(_) => None,
+        };
+        let (total, rows) = dispatch!(self.conn, conn => {
+            let total = query.filtered().count().get_result(conn)?;
+            let rows = query.paged(cursor.as_ref()).select(Post::as_select()).load(conn)?;
+            (total, rows)
+        });
+
+        Ok(query.finish(rows, total))
+    }
+
+    /// The first `limit` posts, only published ones unless `include_drafts`
+    /// is set.
+    pub fn list(&mut self, include_drafts: bool, limit: i64) -> Result<Vec<Post>> {
+        Ok(self.query(&listing(include_drafts, limit))?.posts)
+    }
+
+    /// Like [`list`](Self::list), for the posts with this tag.
+    pub fn list_by_tag(&mut self, tag: &str, include_drafts: bool, limit: i64) -> Result<Vec<Post>> {
+        Ok(self.query(&listing(include_drafts, limit).tag(tag))?.posts)
+    }
+
+    /// Like [`list`](Self::list), for the posts of an author.
+    pub fn list_by_author(&mut self, author: i32, include_drafts: bool, limit: i64) -> Result<Vec<Post>> {
+        Ok(self.query(&listing(include_drafts, limit).author(author))?.posts)
+    }
+
+    /// Loads a post for a step of the workflow, failing unless it
+    /// [allows](Post::allows) `transition`. On Postgres the row stays locked
+    /// until the transaction ends, so two reviewers can't both take it.
+    fn for_transition(&mut self, post_id: i32, transition: Transition) -> Result<Post> {
+        let post = self.locked(post_id)?;
+        BlogError::check_transition(&post, transition)?;
+        Ok(post)
+    }
+
+    // Loads a post, locked until the transaction ends on Postgres. SQLite
+    // writes one transaction at a time anyway.
+    fn locked(&mut self, post_id: i32) -> Result<Post> {
+        match &mut *self.conn {
+            #[cfg(feature = "postgres")]
+            BlogConnection::Pg(conn) => posts
+                .find(post_id)
+                .select(Post::as_select())
+                .for_update()
+                .first(conn)
+                .optional(),
+            #[cfg(feature = "sqlite")]
+            BlogConnection::Sqlite(conn) => posts.find(post_id).select(Post::as_select()).first(conn).optional(),
+        }
+        .or_not_found(post_id)
+    }
+
+    // Sends `edited` back to review when its new text needs one (see
+    // `Post::edit_needs_review`), cancelling its schedule.
+    fn withdraw_review(&mut self, before: &Post, edited: Post) -> Result<Post> {
+        if !before.edit_needs_review(&edited) {
+            return Ok(edited);
+        }
+        let new_status = match edited.status {
+            PostStatus::Scheduled => PostStatus::Draft,
+            other => other,
+        };
+        Ok(dispatch!(self.conn, conn => diesel::update(posts.find(edited.id))
+            .set((
+                status.eq(new_status),
+                review.eq(ReviewState::Unreviewed),
+                publish_at.eq(None::<NaiveDateTime>),
+            ))
+            .returning(Post::as_returning())
+            .get_result(conn))?)
+    }
+
+    /// Sends a draft for review. It stays a draft, waiting for
+    /// [`approve`](Self::approve) or [`reject`](Self::reject).
+    pub fn request_review(&mut self, post_id: i32) -> Result<Post> {
+        self.transaction(|repo| {
+            repo.for_transition(post_id, Transition::RequestReview)?;
+            Ok(dispatch!(repo.conn, conn => diesel::update(posts.find(post_id))
+                .set(review.eq(ReviewState::Pending))
+                .returning(Post::as_returning())
+                .get_result(conn))?)
+        })
+    }
+
+    /// Approves a post waiting for review on behalf of `reviewer_id`, an
+    /// author, and publishes it now or schedules it for `at`.
+    pub fn approve(&mut self, post_id: i32, reviewer_id: i32, at: Option<NaiveDateTime>) -> Result<Post> {
+        self.transaction(|repo| {
+            repo.for_transition(post_id, Transition::Approve)?;
+            repo.record_review(post_id, reviewer_id, true)?;
+            dispatch!(repo.conn, conn => diesel::update(posts.find(post_id))
+                .set(review.eq(ReviewState::Approved))
+                .execute(conn))?;
+            match at {
+                Some(at) => repo.schedule(post_id, at),
+                None => repo.publish(post_id),
+            }
+        })
+    }
+
+    /// Sends a post waiting for review back to its author as a draft, with
+    /// `reviewer_id` recorded as having rejected it.
+    pub fn reject(&mut self, post_id: i32, reviewer_id: i32) -> Result<Post> {
+        self.transaction(|repo| {
+            repo.for_transition(post_id, Transition::Reject)?;
+            repo.record_review(post_id, reviewer_id, false)?;
+            Ok(dispatch!(repo.conn, conn => diesel::update(posts.find(post_id))
+                .set(review.eq(ReviewState::Unreviewed))
+                .returning(Post::as_returning())
+                .get_result(conn))?)
+        })
+    }
+
+    fn record_review(&mut self, post_id: i32, reviewer_id: i32, approved: bool) -> Result<PostReview> {
+        let new_review = NewPostReview { post_id, reviewer_id, approved };
+        Ok(dispatch!(self.conn, conn => diesel::insert_into(post_reviews::table)
+            .values(&new_review)
+            .returning(PostReview::as_returning())
+            .get_result(conn))?)
+    }
+
+    /// The approvals and rejections of a post with their reviewers, oldest
+    /// first.
+    pub fn reviews(&mut self, post_id: i32) -> Result<Vec<(PostReview, Author)>> {
+        self.get(post_id)?;
+        Ok(dispatch!(self.conn, conn => post_reviews::table
+            .inner_join(authors::table)
+            .filter(post_reviews::post_id.eq(post_id))
+            .order(post_reviews::id)
+            .select((PostReview::as_select(), Author::as_select()))
+            .load(conn))?)
+    }
+
+    /// Publishes an approved post now, also one that was scheduled or
+    /// archived. Drafts go through [`approve`](Self::approve) instead.
+    pub fn publish(&mut self, post_id: i32) -> Result<Post> {
+        self.transaction(|repo| {
+            repo.for_transition(post_id, Transition::Publish)?;
+            Ok(dispatch!(repo.conn, conn => diesel::update(posts.find(post_id))
+                .set((
+                    status.eq(PostStatus::Published),
+                    published_at.eq(now.nullable()),
+                    publish_at.eq(None::<NaiveDateTime>),
+                ))
+                .returning(Post::as_returning())
+                .get_result(conn))?)
+        })
+    }
+
+    /// Publishes a post an import brought in, dated `at` as it was
+    /// published elsewhere. Only a post the import `created` skips review;
+    /// it counts as approved. Any other post must already be approved.
+    pub(crate) fn import_published(&mut self, post_id: i32, at: NaiveDateTime, created: bool) -> Result<Post> {
+        self.transaction(|repo| {
+            let post = repo.get(post_id)?;
+            if !created && post.review != ReviewState::Approved {
+                BlogError::check_transition(&post, Transition::Publish)?;
+            }
+            Ok(dispatch!(repo.conn, conn => diesel::update(posts.find(post_id))
+                .set((
+                    status.eq(PostStatus::Published),
+                    review.eq(ReviewState::Approved),
+                    published_at.eq(at),
+                    publish_at.eq(None::<NaiveDateTime>),
+                ))
+                .returning(Post::as_returning())
+                .get_result(conn))?)
+        })
+    }
+
+    /// Schedules an approved post to be published at `at`, which
+    /// [`publish_due`](Self::publish_due) does once the time has come.
+    pub fn schedule(&mut self, post_id: i32, at: NaiveDateTime) -> Result<Post> {
+        self.transaction(|repo| {
+            repo.for_transition(post_id, Transition::Schedule)?;
+            Ok(dispatch!(repo.conn, conn => diesel::update(posts.find(post_id))
+                .set((
+                    status.eq(PostStatus::Scheduled),
+                    published_at.eq(None::<NaiveDateTime>),
+                    publish_at.eq(at),
+                ))
+                .returning(Post::as_returning())
+                .get_result(conn))?)
+        })
+    }
+
+    /// Turns a post back into a draft, cancelling its schedule. It has to be
+    /// reviewed again before it goes back out; a draft waiting for review
+    /// is withdrawn from it.
+    pub fn unpublish(&mut self, post_id: i32) -> Result<Post> {
+        self.transaction(|repo| {
+            repo.for_transition(post_id, Transition::Unpublish)?;
+            Ok(dispatch!(repo.conn, conn => diesel::update(posts.find(post_id))
+                .set((
+                    status.eq(PostStatus::Draft),
+                    review.eq(ReviewState::Unreviewed),
+                    published_at.eq(None::<NaiveDateTime>),
+                    publish_at.eq(None::<NaiveDateTime>),
+                ))
+                .returning(Post::as_returning())
+                .get_result(conn))?)
+        })
+    }
+
+    /// Takes a post out of listings without deleting it. It keeps the time it
+    /// was published, if it was, and its review.
+    pub fn archive(&mut self, post_id: i32) -> Result<Post> {
+        self.transaction(|repo| {
+            repo.for_transition(post_id, Transition::Archive)?;
+            Ok(dispatch!(repo.conn, conn => diesel::update(posts.find(post_id))
+                .set((status.eq(PostStatus::Archived), publish_at.eq(None::<NaiveDateTime>)))
+                .returning(Post::as_returning())
+                .get_result(conn))?)
+        })
+    }
+
+    /// Publishes the scheduled posts due by `until`, dated when they were
+    /// due rather than when this runs.
+    pub fn publish_due(&mut self, until: NaiveDateTime) -> Result<Vec<Post>> {
+        let due = posts.filter(status.eq(PostStatus::Scheduled)).filter(publish_at.le(until));
+        Ok(dispatch!(self.conn, conn => diesel::update(due)
+            .set((
+                status.eq(PostStatus::Published),
+                published_at.eq(publish_at),
+                publish_at.eq(None::<NaiveDateTime>),
+            ))
+            .returning(Post::as_returning())
+            .get_results(conn))?)
+    }
+
+    /// Changes the title and/or body of a post. New text on a post that
+    /// isn't published has to be reviewed again: the post goes back to
+    /// unreviewed and a scheduled one back to a draft.
+    pub fn update(&mut self, post_id: i32, changes: &PostChanges) -> Result<Post> {
+        self.transaction(|repo| {
+            let before = repo.locked(post_id)?;
+            let edited = dispatch!(repo.conn, conn => diesel::update(posts.find(post_id))
+                .set(changes)
+                .returning(Post::as_returning())
+                .get_result(conn))?;
+            repo.withdraw_review(&before, edited)
+        })
+    }
+
+    /// Deletes a post, returning what was deleted.
+    pub fn delete(&mut self, post_id: i32) -> Result<Post> {
+        dispatch!(self.conn, conn => diesel::delete(posts.find(post_id))
+            .returning(Post::as_returning())
+            .get_result(conn)
+            .optional())
+        .or_not_found(post_id)
+    }
+
+    /// Posts whose title contains `text`, taken literally.
+    pub fn find_by_title(&mut self, text: &str) -> Result<Vec<Post>> {
+        Ok(dispatch!(self.conn, conn => posts
+            .filter(title.like(contains_pattern(text)).escape('\\'))
+            .order(id)
+            .select(Post::as_select())
+            .load(conn))?)
+    }
+
+    /// Deletes the posts with these ids in one transaction, returning how
+    /// many were deleted.
+    pub fn delete_many(&mut self, ids: &[i32]) -> Result<usize> {
+        Ok(dispatch!(self.conn, conn => conn.transaction(|conn| {
+            diesel::delete(posts.filter(id.eq_any(ids))).execute(conn)
+        }))?)
+    }
+
+    /// Deletes every post whose title contains `text`, taken literally.
+    pub fn delete_matching(&mut self, text: &str) -> Result<usize> {
+        Ok(dispatch!(self.conn, conn => {
+            diesel::delete(posts.filter(title.like(contains_pattern(text)).escape('\\'))).execute(conn)
+        })?)
+    }
+
+    /// Every revision of a post, oldest first. Revisions are recorded by a
+    /// trigger whenever the title or body changes.
+    pub fn history(&mut self, post_id: i32) -> Result<Vec<Revision>> {
+        self.get(post_id)?;
+
+        Ok(dispatch!(self.conn, conn => post_revisions::table
+            .filter(post_revisions::post_id.eq(post_id))
+            .order(post_revisions::revision)
+            .select(Revision::as_select())
+            .load(conn))?)
+    }
+
+    pub fn revision(&mut self, post_id: i32, number: i32) -> Result<Revision> {
+        dispatch!(self.conn, conn => post_revisions::table
+            .filter(post_revisions::post_id.eq(post_id))
+            .filter(post_revisions::revision.eq(number))
+            .select(Revision::as_select())
+            .first(conn)
+            .optional())
+        .or_else_missing(|| BlogError::RevisionNotFound { post_id, revision: number })
+    }
+
+    /// Puts back the title and body of an earlier revision, which is saved
+    /// as a new revision in turn. Like [`update`](Self::update), this sends
+    /// a post that isn't published back to review.
+    pub fn revert(&mut self, post_id: i32, number: i32) -> Result<Post> {
+        self.transaction(|repo| {
+            let before = repo.locked(post_id)?;
+            let old = repo.revision(post_id, number)?;
+            let reverted = dispatch!(repo.conn, conn => diesel::update(posts.find(post_id))
+                .set((title.eq(old.title), body.eq(old.body)))
+                .returning(Post::as_returning())
+                .get_result(conn))?;
+            repo.withdraw_review(&before, reverted)
+        })
+    }
+
+    /// Sets or clears the author of a post.
+    pub fn set_author(&mut self, post_id: i32, author: Option<i32>) -> Result<Post> {
+        dispatch!(self.conn, conn => diesel::update(posts.find(post_id))
+            .set(author_id.eq(author))
+            .returning(Post::as_returning())
+            .get_result(conn)
+            .optional())
+        .or_not_found(post_id)
+    }
+
+    /// Replaces the tags of a post, creating the tags that don't exist yet.
+    /// Tag names are normalized like slugs.
+    pub fn set_tags(&mut self, post_id: i32, names: &[&str]) -> Result<Vec<Tag>> {
+        let names = tag_names(names);
+        self.get(post_id)?;
+
+        Ok(dispatch!(self.conn, conn => conn.transaction(|conn| {
+            // One row at a time: SQLite can't batch inserts with ON CONFLICT.
+            for name in &names {
+                diesel::insert_into(tags::table)
+                    .values(tags::name.eq(name))
+                    .on_conflict_do_nothing()
+                    .execute(conn)?;
+            }
+            let post_tags: Vec<Tag> = tags::table
+                .filter(tags::name.eq_any(&names))
+                .order(tags::name)
+                .select(Tag::as_select())
+                .load(conn)?;
+
+            diesel::delete(posts_tags::table.filter(posts_tags::post_id.eq(post_id))).execute(conn)?;
+            let links: Vec<PostTag> = post_tags
+                .iter()
+                .map(|tag| PostTag { post_id, tag_id: tag.id })
+                .collect();
+            diesel::insert_into(posts_tags::table).values(&links).execute(conn)?;
+
+            Ok::<_, diesel::result::Error>(post_tags)
+        }))?)
+    }
+
+    /// The tags of a post, by name.
+    pub fn tags_of(&mut self, post_id: i32) -> Result<Vec<Tag>> {
+        Ok(dispatch!(self.conn, conn => tags::table
+            .inner_join(posts_tags::table)
+            .filter(posts_tags::post_id.eq(post_id))
+            .order(tags::name)
+            .select(Tag::as_select())
+            .load(conn))?)
+    }
+
+    pub fn create_author(&mut self, name: &str, email: &str) -> Result<Author> {
+        Ok(dispatch!(self.conn, conn => diesel::insert_into(authors::table)
+            .values(&NewAuthor { name, email })
+            .returning(Author::as_returning())
+            .get_result(conn))?)
+    }
+
+    pub fn find_author(&mut self, email: &str) -> Result<Author> {
+        dispatch!(self.conn, conn => authors::table
+            .filter(authors::email.eq(email))
+            .select(Author::as_select())
+            .first(conn)
+            .optional())
+        .or_else_missing(|| BlogError::AuthorNotFound(email.to_string()))
+    }
+
+    /// Lists authors by name.
+    pub fn authors(&mut self) -> Result<Vec<Author>> {
+        Ok(dispatch!(self.conn, conn => authors::table
+            .order((authors::name, authors::id))
+            .select(Author::as_select())
+            .load(conn))?)
+    }
+
+    /// Adds a comment to a post, as a reply when `parent_id` is set. New
+    /// comments wait for approval before they are shown.
+    pub fn add_comment(&mut self, comment: &NewComment) -> Result<Comment> {
+        self.get(comment.post_id)?;
+        if let Some(parent) = comment.parent_id {
+            if self.comment(parent)?.post_id != comment.post_id {
+                return Err(BlogError::CommentNotFound(parent));
+            }
+        }
+
+        Ok(dispatch!(self.conn, conn => diesel::insert_into(comments::table)
+            .values(comment)
+            .returning(Comment::as_returning())
+            .get_result(conn))?)
+    }
+
+    pub fn comment(&mut self, comment_id: i32) -> Result<Comment> {
+        dispatch!(self.conn, conn => comments::table
+            .find(comment_id)
+            .select(Comment::as_select())
+            .first(conn)
+            .optional())
+        .or_else_missing(|| BlogError::CommentNotFound(comment_id))
+    }
+
+    /// The comments on a post as threads, oldest first. Comments waiting for
+    /// approval are only included with `include_pending`.
+    pub fn comments(&mut self, post_id: i32, include_pending: bool) -> Result<Vec<CommentThread>> {
+        let post = self.get(post_id)?;
+
+        let all = dispatch!(self.conn, conn => {
+            let mut query = Comment::belonging_to(&post).into_boxed();
+            if !include_pending {
+                query = query.filter(comments::approved.eq(true));
+            }
+            query
+                .order((comments::created_at, comments::id))
+                .select(Comment::as_select())
+                .load(conn)
+        })?;
+
+        Ok(thread(all))
+    }
+
+    /// Comments waiting for approval on any post, oldest first.
+    pub fn pending_comments(&mut self, limit: i64) -> Result<Vec<Comment>> {
+        Ok(dispatch!(self.conn, conn => comments::table
+            .filter(comments::approved.eq(false))
+            .order((comments::created_at, comments::id))
+            .limit(limit)
+            .select(Comment::as_select())
+            .load(conn))?)
+    }
+
+    pub fn approve_comment(&mut self, comment_id: i32) -> Result<Comment> {
+        dispatch!(self.conn, conn => diesel::update(comments::table.find(comment_id))
+            .set(comments::approved.eq(true))
+            .returning(Comment::as_returning())
+            .get_result(conn)
+            .optional())
+        .or_else_missing(|| BlogError::CommentNotFound(comment_id))
+    }
+
+    /// Deletes a comment and the replies to it, returning the comment.
+    pub fn delete_comment(&mut self, comment_id: i32) -> Result<Comment> {
+        dispatch!(self.conn, conn => diesel::delete(comments::table.find(comment_id))
+            .returning(Comment::as_returning())
+            .get_result(conn)
+            .optional())
+        .or_else_missing(|| BlogError::CommentNotFound(comment_id))
+    }
+
+    /// Records a file attached to a post. The file itself has to be in the
+    /// storage already; see [`attachments::attach`](crate::attachments::attach).
+    pub fn add_attachment(&mut self, attachment: &NewAttachment) -> Result<Attachment> {
+        self.get(attachment.post_id)?;
+        let taken = dispatch!(self.conn, conn => attachments::table
+            .filter(attachments::post_id.eq(attachment.post_id))
+            .filter(attachments::filename.eq(attachment.filename))
+            .count()
+            .get_result::<i64>(conn))?;
+        if taken > 0 {
+            return Err(BlogError::AttachmentExists {
+                post_id: attachment.post_id,
+                filename: attachment.filename.to_string(),
+            });
+        }
+
+        Ok(dispatch!(self.conn, conn => diesel::insert_into(attachments::table)
+            .values(attachment)
+            .returning(Attachment::as_returning())
+            .get_result(conn))?)
+    }
+
+    pub fn attachment(&mut self, attachment_id: i32) -> Result<Attachment> {
+        dispatch!(self.conn, conn => attachments::table
+            .find(attachment_id)
+            .select(Attachment::as_select())
+            .first(conn)
+            .optional())
+        .or_else_missing(|| BlogError::AttachmentNotFound(attachment_id))
+    }
+
+    /// The files attached to a post, by name.
+    pub fn attachments(&mut self, post_id: i32) -> Result<Vec<Attachment>> {
+        self.get(post_id)?;
+        self.attachments_of(&[post_id])
+    }
+
+    /// The files attached to any of these posts, by post and name.
+    pub fn attachments_of(&mut self, post_ids: &[i32]) -> Result<Vec<Attachment>> {
+        Ok(dispatch!(self.conn, conn => attachments::table
+            .filter(attachments::post_id.eq_any(post_ids))
+            .order((attachments::post_id, attachments::filename))
+            .select(Attachment::as_select())
+            .load(conn))?)
+    }
+
+    /// The attachments stored under `key`: more than one when the same file
+    /// is attached twice.
+    pub fn attachments_by_key(&mut self, key: &str) -> Result<Vec<Attachment>> {
+        Ok(dispatch!(self.conn, conn => attachments::table
+            .filter(attachments::storage_key.eq(key))
+            .order(attachments::id)
+            .select(Attachment::as_select())
+            .load(conn))?)
+    }
+
+    /// Forgets an attachment, returning it. Its file is left in the storage.
+    pub fn delete_attachment(&mut self, attachment_id: i32) -> Result<Attachment> {
+        dispatch!(self.conn, conn => diesel::delete(attachments::table.find(attachment_id))
+            .returning(Attachment::as_returning())
+            .get_result(conn)
+            .optional())
+        .or_else_missing(|| BlogError::AttachmentNotFound(attachment_id))
+    }
+
+    /// True when the blog has no posts and no authors.
+    pub fn is_empty(&mut self) -> Result<bool> {
+        Ok(dispatch!(self.conn, conn => {
+            let post_count: i64 = posts.count().get_result(conn)?;
+            let author_count: i64 = authors::table.count().get_result(conn)?;
+            post_count == 0 && author_count == 0
+        }))
+    }
+
+    /// Deletes every post, author, tag, comment, attachment and review.
+    pub fn clear(&mut self) -> Result<()> {
+        Ok(dispatch!(self.conn, conn => conn.transaction(|conn| {
+            diesel::delete(post_reviews::table).execute(conn)?;
+            diesel::delete(attachments::table).execute(conn)?;
+            diesel::delete(comments::table).execute(conn)?;
+            diesel::delete(posts_tags::table).execute(conn)?;
+            diesel::delete(post_revisions::table).execute(conn)?;
+            diesel::delete(posts).execute(conn)?;
+            diesel::delete(tags::table).execute(conn)?;
+            diesel::delete(authors::table).execute(conn)?;
+            Ok::<_, diesel::result::Error>(())
+        }))?)
+    }
+
+    /// Every row of the blog, by id.
+    pub fn dump(&mut self) -> Result<Snapshot> {
+        Ok(dispatch!(self.conn, conn => conn.transaction(|conn| {
+            Ok::<_, diesel::result::Error>(Snapshot {
+                version: FORMAT_VERSION,
+                authors: authors::table.order(authors::id).select(Author::as_select()).load(conn)?,
+                posts: posts.order(id).select(Post::as_select()).load(conn)?,
+                tags: tags::table.order(tags::id).select(Tag::as_select()).load(conn)?,
+                posts_tags: posts_tags::table
+                    .order((posts_tags::post_id, posts_tags::tag_id))
+                    .select(PostTag::as_select())
+                    .load(conn)?,
+                revisions: post_revisions::table
+                    .order(post_revisions::id)
+                    .select(Revision::as_select())
+                    .load(conn)?,
+                comments: comments::table.order(comments::id).select(Comment::as_select()).load(conn)?,
+                attachments: attachments::table
+                    .order(attachments::id)
+                    .select(Attachment::as_select())
+                    .load(conn)?,
+                reviews: post_reviews::table
+                    .order(post_reviews::id)
+                    .select(PostReview::as_select())
+                    .load(conn)?,
+            })
+        }))?)
+    }
+
+    /// Loads a snapshot into an empty blog, keeping the ids of its rows.
+    /// Nothing is loaded if any of it fails.
+    pub fn load(&mut self, snapshot: &Snapshot) -> Result<()> {
+        self.transaction(|repo| {
+            dispatch!(repo.conn, conn => {
+                for chunk in snapshot.authors.chunks(LOAD_CHUNK) {
+                    diesel::insert_into(authors::table).values(chunk).execute(conn)?;
+                }
+                for chunk in snapshot.posts.chunks(LOAD_CHUNK) {
+                    diesel::insert_into(posts).values(chunk).execute(conn)?;
+                }
+                // Inserting the posts recorded a first revision of each; the
+                // snapshot has the real history.
+                diesel::delete(post_revisions::table).execute(conn)?;
+                for chunk in snapshot.revisions.chunks(LOAD_CHUNK) {
+                    diesel::insert_into(post_revisions::table).values(chunk).execute(conn)?;
+                }
+                for chunk in snapshot.tags.chunks(LOAD_CHUNK) {
+                    diesel::insert_into(tags::table).values(chunk).execute(conn)?;
+                }
+                for chunk in snapshot.posts_tags.chunks(LOAD_CHUNK) {
+                    diesel::insert_into(posts_tags::table).values(chunk).execute(conn)?;
+                }
+                for chunk in snapshot.comments.chunks(LOAD_CHUNK) {
+                    diesel::insert_into(comments::table).values(chunk).execute(conn)?;
+                }
+                for chunk in snapshot.attachments.chunks(LOAD_CHUNK) {
+                    diesel::insert_into(attachments::table).values(chunk).execute(conn)?;
+                }
+                for chunk in snapshot.reviews.chunks(LOAD_CHUNK) {
+                    diesel::insert_into(post_reviews::table).values(chunk).execute(conn)?;
+                }
+            });
+
+            match &mut *repo.conn {
+                #[cfg(feature = "postgres")]
+                BlogConnection::Pg(conn) => reset_sequences(conn)?,
+                #[cfg(feature = "sqlite")]
+                BlogConnection::Sqlite(_) => {}
+            }
+            Ok(())
+        })
+    }
+}
+
+// Postgres hands out ids from sequences that don't know about the rows
+// `load` inserted with their own ids. SQLite takes the next id from the table.
+#[cfg(feature = "postgres")]
+fn reset_sequences(conn: &mut diesel::PgConnection) -> QueryResult<()> {
+    for table in ["authors", "posts", "tags", "post_revisions", "comments", "attachments", "post_reviews"] {
+        diesel::sql_query(format!(
+            "SELECT setval(pg_get_serial_sequence('{0}', 'id'), COALESCE(MAX(id), 0) + 1, false) FROM {0}",
+            table
+        ))
+        .execute(conn)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_like_wildcards() {
+        assert_eq!("hello", escape_like("hello"));
+        assert_eq!("100\\%", escape_like("100%"));
+        assert_eq!("a\\_b", escape_like("a_b"));
+        assert_eq!("c:\\\\temp", escape_like("c:\\temp"));
+        assert_eq!("%\\%\\_%", contains_pattern("%_"));
+        assert_eq!("my\\_post%", slug_pattern("my_post"));
+    }
+
+    #[test]
+    fn normalizes_and_deduplicates_tag_names() {
+        assert_eq!(vec!["diesel", "rust-lang"], tag_names(&["Rust Lang", "diesel", "rust-lang", "!!"]));
+    }
+}
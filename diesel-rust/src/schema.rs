@@ -1,10 +1,131 @@
 // @generated automatically by Diesel CLI.
 
+pub mod sql_types {
+    // Regenerating this file drops the cfg_attrs on these types; they are
+    // there so a build with one backend doesn't ask for the other.
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[cfg_attr(feature = "postgres", diesel(postgres_type(name = "post_status")))]
+    #[cfg_attr(feature = "sqlite", diesel(sqlite_type(name = "Text")))]
+    pub struct PostStatus;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[cfg_attr(feature = "postgres", diesel(postgres_type(name = "review_state")))]
+    #[cfg_attr(feature = "sqlite", diesel(sqlite_type(name = "Text")))]
+    pub struct ReviewState;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[cfg_attr(feature = "postgres", diesel(postgres_type(name = "tsvector", schema = "pg_catalog")))]
+    pub struct Tsvector;
+}
+
+diesel::table! {
+    attachments (id) {
+        id -> Int4,
+        post_id -> Int4,
+        filename -> Varchar,
+        mime_type -> Varchar,
+        size -> Int8,
+        sha256 -> Varchar,
+        storage_key -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    authors (id) {
+        id -> Int4,
+        name -> Varchar,
+        email -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    comments (id) {
+        id -> Int4,
+        post_id -> Int4,
+        parent_id -> Nullable<Int4>,
+        author_name -> Varchar,
+        author_email -> Varchar,
+        body -> Text,
+        approved -> Bool,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    post_revisions (id) {
+        id -> Int4,
+        post_id -> Int4,
+        revision -> Int4,
+        title -> Varchar,
+        body -> Text,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
+    post_reviews (id) {
+        id -> Int4,
+        post_id -> Int4,
+        reviewer_id -> Int4,
+        approved -> Bool,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::PostStatus;
+    use super::sql_types::Tsvector;
+    use super::sql_types::ReviewState;
+
     posts (id) {
         id -> Int4,
         title -> Varchar,
         body -> Text,
-        published -> Bool,
+        slug -> Varchar,
+        author_id -> Nullable<Int4>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        published_at -> Nullable<Timestamp>,
+        search -> Tsvector,
+        status -> PostStatus,
+        publish_at -> Nullable<Timestamp>,
+        review -> ReviewState,
     }
 }
+
+diesel::table! {
+    posts_tags (post_id, tag_id) {
+        post_id -> Int4,
+        tag_id -> Int4,
+    }
+}
+
+diesel::table! {
+    tags (id) {
+        id -> Int4,
+        name -> Varchar,
+    }
+}
+
+diesel::joinable!(attachments -> posts (post_id));
+diesel::joinable!(comments -> posts (post_id));
+diesel::joinable!(post_reviews -> authors (reviewer_id));
+diesel::joinable!(post_reviews -> posts (post_id));
+diesel::joinable!(post_revisions -> posts (post_id));
+diesel::joinable!(posts -> authors (author_id));
+diesel::joinable!(posts_tags -> posts (post_id));
+diesel::joinable!(posts_tags -> tags (tag_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    attachments,
+    authors,
+    comments,
+    post_reviews,
+    post_revisions,
+    posts,
+    posts_tags,
+    tags,
+);
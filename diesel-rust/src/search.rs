@@ -0,0 +1,190 @@
+//! Full-text search over posts.
+//!
+//! On Postgres, the `posts.search` column is a `tsvector` generated from the
+//! title and body with the `english` configuration (see the `add_post_search`
+//! migration). The Postgres functions used on it are mapped with
+//! `define_sql_function!`, so queries over it are type-checked like the rest
+//! of `schema.rs`.
+//!
+//! On SQLite, the `posts_fts` FTS5 table indexes the same two columns and is
+//! kept up to date by triggers. Its queries are built with [`fts5_query`].
+
+use crate::models::Post;
+#[cfg(feature = "sqlite")]
+use diesel::QueryableByName;
+use serde::Serialize;
+
+#[cfg(feature = "postgres")]
+pub use self::postgres::*;
+
+pub const HIGHLIGHT_START: &str = "<mark>";
+pub const HIGHLIGHT_STOP: &str = "</mark>";
+
+/// A post found by a search.
+#[derive(Serialize)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub post: Post,
+    pub rank: f32,
+    /// Parts of the body around the matches, with the matched words between
+    /// [`HIGHLIGHT_START`] and [`HIGHLIGHT_STOP`].
+    pub snippet: String,
+}
+
+/// A row of the SQLite search query.
+#[cfg(feature = "sqlite")]
+#[derive(QueryableByName)]
+pub(crate) struct FtsHit {
+    #[diesel(embed)]
+    post: Post,
+    #[diesel(sql_type = diesel::sql_types::Double)]
+    rank: f64,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    snippet: String,
+}
+
+#[cfg(feature = "sqlite")]
+impl From<FtsHit> for SearchHit {
+    fn from(hit: FtsHit) -> Self {
+        SearchHit {
+            post: hit.post,
+            rank: hit.rank as f32,
+            snippet: hit.snippet,
+        }
+    }
+}
+
+/// Turns what was typed in a search box into an FTS5 query matching posts
+/// that contain every word. Words are quoted, so FTS5 operators and column
+/// filters in the input are taken literally. `None` if there are no words.
+pub fn fts5_query(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+#[cfg(feature = "postgres")]
+mod postgres {
+    use crate::schema::sql_types::Tsvector;
+    use diesel::expression::{AppearsOnTable, Expression, SelectableExpression, ValidGrouping};
+    use diesel::pg::Pg;
+    use diesel::query_builder::{AstPass, QueryFragment, QueryId};
+    use diesel::sql_types::{SqlType, Text};
+    use diesel::QueryResult;
+
+    #[derive(QueryId, SqlType)]
+    #[diesel(postgres_type(name = "tsquery", schema = "pg_catalog"))]
+    pub struct Tsquery;
+
+    #[derive(QueryId, SqlType)]
+    #[diesel(postgres_type(name = "regconfig", schema = "pg_catalog"))]
+    pub struct Regconfig;
+
+    /// Options for `ts_headline`: up to two fragments of the body around the
+    /// matches, with the matched words wrapped in the highlight markers.
+    pub const HEADLINE_OPTIONS: &str =
+        "StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MaxWords=20, MinWords=8, FragmentDelimiter=\" … \"";
+
+    /// The text search configuration the `search` column is built with.
+    /// Queries have to be parsed with the same one for their words to be
+    /// stemmed alike.
+    #[derive(Debug, Clone, Copy, QueryId, ValidGrouping)]
+    pub struct English;
+
+    impl Expression for English {
+        type SqlType = Regconfig;
+    }
+
+    impl<QS> AppearsOnTable<QS> for English {}
+
+    impl<QS> SelectableExpression<QS> for English {}
+
+    impl QueryFragment<Pg> for English {
+        fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, Pg>) -> QueryResult<()> {
+            out.push_sql("'english'::regconfig");
+            Ok(())
+        }
+    }
+
+    diesel::define_sql_function! {
+        /// Parses a query the way web search boxes do: words are all required,
+        /// `"quoted phrases"` match in order, `or` gives alternatives and `-word`
+        /// excludes a word.
+        fn websearch_to_tsquery(config: Regconfig, query: Text) -> Tsquery;
+    }
+
+    diesel::define_sql_function! {
+        /// Ranks a match by how close together the matched words are, weighing
+        /// title words over body words.
+        fn ts_rank_cd(vector: Tsvector, query: Tsquery) -> Float4;
+    }
+
+    diesel::define_sql_function! {
+        /// Fragments of `document` with the words matching `query` highlighted.
+        fn ts_headline(config: Regconfig, document: Text, query: Tsquery, options: Text) -> Text;
+    }
+
+    diesel::infix_operator!(Matches, " @@ ", backend: Pg);
+
+    /// The `@@` operator, which is what the GIN index on `search` serves.
+    pub trait TsvectorExpressionMethods: Expression<SqlType = Tsvector> + Sized {
+        fn matches<Q>(self, query: Q) -> Matches<Self, Q>
+        where
+            Q: Expression<SqlType = Tsquery>,
+        {
+            Matches::new(self, query)
+        }
+    }
+
+    impl<T: Expression<SqlType = Tsvector>> TsvectorExpressionMethods for T {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn maps_the_search_functions() {
+        use crate::schema::posts::dsl::*;
+        use diesel::debug_query;
+        use diesel::pg::Pg;
+        use diesel::prelude::*;
+
+        let query = posts
+            .filter(search.matches(websearch_to_tsquery(English, "diesel")))
+            .select((
+                id,
+                ts_rank_cd(search, websearch_to_tsquery(English, "diesel")),
+                ts_headline(English, body, websearch_to_tsquery(English, "diesel"), HEADLINE_OPTIONS),
+            ));
+        let sql = debug_query::<Pg, _>(&query).to_string();
+
+        assert!(
+            sql.contains(r#"ts_rank_cd("posts"."search", websearch_to_tsquery('english'::regconfig, $1))"#),
+            "{}",
+            sql
+        );
+        assert!(sql.contains(r#"ts_headline('english'::regconfig, "posts"."body", "#), "{}", sql);
+        assert!(
+            sql.contains(r#"WHERE "posts"."search" @@ websearch_to_tsquery('english'::regconfig, $4)"#),
+            "{}",
+            sql
+        );
+    }
+
+    #[test]
+    fn quotes_fts5_terms() {
+        assert_eq!(Some("\"rust\" \"diesel\"".to_string()), fts5_query("rust  diesel"));
+        assert_eq!(Some("\"title:x\" \"\"\"a\" \"OR\"".to_string()), fts5_query("title:x \"a OR -"));
+        assert_eq!(None, fts5_query("  - * "));
+    }
+}
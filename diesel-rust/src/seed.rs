@@ -0,0 +1,196 @@
+//! Fake but realistic blogs for trying out listings, paging and search.
+//!
+//! [`generate`] builds a [`Snapshot`] from a seed: the same seed always gives
+//! the same authors, posts, tags and comments. Only the dates depend on
+//! `until`, which the newest post was written just before.
+
+use crate::models::{Author, Comment, Post, PostStatus, PostTag, ReviewState, Revision, Tag};
+use crate::slug::{next_free_slug, slugify};
+use crate::snapshot::{Snapshot, FORMAT_VERSION};
+use chrono::{Duration, NaiveDateTime};
+use fake::faker::internet::en::FreeEmailProvider;
+use fake::faker::lorem::en::{Paragraph, Sentence};
+use fake::faker::name::en::Name;
+use fake::Fake;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// The tags posts are given, a few each.
+const TAGS: [&str; 12] = [
+    "rust", "diesel", "postgres", "sqlite", "web", "cli", "testing", "performance", "async", "tutorial", "release",
+    "opinion",
+];
+
+/// A blog of `count` posts made from `seed`, written over the time up to
+/// `until`. About one post in ten is a draft and one in ten is scheduled
+/// after `until`; the rest are published, most of them with comments.
+pub fn generate(count: usize, seed: u64, until: NaiveDateTime) -> Snapshot {
+    // Unlike StdRng, ChaCha8 is portable and its output is fixed, so a seed
+    // makes the same blog on every machine.
+    let rng = &mut ChaCha8Rng::seed_from_u64(seed);
+
+    let mut authors: Vec<Author> = Vec::new();
+    let mut emails: Vec<String> = Vec::new();
+    for id in 1..=(count / 8 + 1) as i32 {
+        let name: String = Name().fake_with_rng(rng);
+        let provider: String = FreeEmailProvider().fake_with_rng(rng);
+        let local = next_free_slug(&slugify(&name).replace('-', "."), &emails);
+        emails.push(local.clone());
+        authors.push(Author {
+            id,
+            name,
+            email: format!("{}@{}", local, provider),
+            created_at: until - Duration::days(count as i64 + 30),
+        });
+    }
+
+    let tags: Vec<Tag> = TAGS
+        .iter()
+        .zip(1..)
+        .map(|(name, id)| Tag { id, name: name.to_string() })
+        .collect();
+
+    let mut snapshot = Snapshot {
+        version: FORMAT_VERSION,
+        authors,
+        posts: Vec::new(),
+        tags,
+        posts_tags: Vec::new(),
+        revisions: Vec::new(),
+        comments: Vec::new(),
+        attachments: Vec::new(),
+        reviews: Vec::new(),
+    };
+
+    // Walk forward from the first post so ids and dates go up together.
+    let mut written = until - Duration::days(count as i64 + 1);
+    let mut slugs: Vec<String> = Vec::new();
+    for id in 1..=count as i32 {
+        written += Duration::minutes(rng.gen_range(60..60 * 24));
+
+        let sentence: String = Sentence(3..8).fake_with_rng(rng);
+        let title = sentence.trim_end_matches('.').to_string();
+        let paragraphs: Vec<String> = (0..rng.gen_range(2..6))
+            .map(|_| Paragraph(3..8).fake_with_rng(rng))
+            .collect();
+        let body = paragraphs.join("\n\n");
+        let slug = next_free_slug(&slugify(&title), &slugs);
+        slugs.push(slug.clone());
+
+        let (status, published_at, publish_at) = match rng.gen_range(0..10) {
+            0 => (PostStatus::Draft, None, None),
+            1 => (PostStatus::Scheduled, None, Some(until + Duration::hours(rng.gen_range(1..24 * 30)))),
+            _ => (PostStatus::Published, Some(written + Duration::minutes(rng.gen_range(5..120))), None),
+        };
+        let author_id = snapshot.authors.choose(rng).map(|author| author.id);
+
+        let tag_count = rng.gen_range(0..4);
+        let mut tag_ids: Vec<i32> = snapshot.tags.choose_multiple(rng, tag_count).map(|tag| tag.id).collect();
+        tag_ids.sort();
+        snapshot.posts_tags.extend(tag_ids.into_iter().map(|tag_id| PostTag { post_id: id, tag_id }));
+
+        snapshot.revisions.push(Revision {
+            id,
+            post_id: id,
+            revision: 1,
+            title: title.clone(),
+            body: body.clone(),
+            created_at: written,
+        });
+
+        if let Some(published_at) = published_at {
+            add_comments(rng, &mut snapshot.comments, id, published_at);
+        }
+
+        snapshot.posts.push(Post {
+            id,
+            title,
+            body,
+            status,
+            slug,
+            author_id,
+            created_at: written,
+            updated_at: published_at.unwrap_or(written),
+            published_at,
+            publish_at,
+            // Whoever approved them isn't part of the fake blog.
+            review: if status == PostStatus::Draft { ReviewState::Unreviewed } else { ReviewState::Approved },
+        });
+    }
+
+    snapshot
+}
+
+/// Up to five comments on a published post, some of them replies.
+fn add_comments(rng: &mut ChaCha8Rng, comments: &mut Vec<Comment>, post_id: i32, published_at: NaiveDateTime) {
+    let first = comments.len();
+    let mut at = published_at;
+    for _ in 0..rng.gen_range(0..6) {
+        at += Duration::minutes(rng.gen_range(10..60 * 48));
+        let name: String = Name().fake_with_rng(rng);
+        let parent_id = if comments.len() > first && rng.gen_bool(0.3) {
+            Some(comments[rng.gen_range(first..comments.len())].id)
+        } else {
+            None
+        };
+
+        comments.push(Comment {
+            id: comments.len() as i32 + 1,
+            post_id,
+            parent_id,
+            author_email: format!("{}@example.com", slugify(&name).replace('-', ".")),
+            author_name: name,
+            body: Sentence(4..16).fake_with_rng(rng),
+            approved: rng.gen_bool(0.8),
+            created_at: at,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use std::collections::HashSet;
+
+    fn until() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_blog() {
+        assert_eq!(generate(20, 7, until()).to_json(), generate(20, 7, until()).to_json());
+        assert_ne!(generate(20, 7, until()).to_json(), generate(20, 8, until()).to_json());
+    }
+
+    #[test]
+    fn generated_rows_fit_together() {
+        let blog = generate(60, 1, until());
+        assert_eq!(60, blog.posts.len());
+        assert_eq!(60, blog.revisions.len());
+
+        let slugs: HashSet<&str> = blog.posts.iter().map(|post| post.slug.as_str()).collect();
+        assert_eq!(blog.posts.len(), slugs.len());
+        let emails: HashSet<&str> = blog.authors.iter().map(|author| author.email.as_str()).collect();
+        assert_eq!(blog.authors.len(), emails.len());
+
+        assert!(blog.posts.windows(2).all(|pair| pair[0].created_at < pair[1].created_at));
+        assert!(blog.posts.iter().all(|post| post.created_at < until()));
+        for post in &blog.posts {
+            match post.status {
+                PostStatus::Published => assert!(post.published_at.is_some()),
+                PostStatus::Scheduled => assert!(post.publish_at > Some(until())),
+                _ => assert!(post.published_at.is_none() && post.publish_at.is_none()),
+            }
+        }
+        // Replies come after what they reply to, on the same post.
+        for comment in &blog.comments {
+            if let Some(parent) = comment.parent_id {
+                let parent = &blog.comments[parent as usize - 1];
+                assert!(parent.id < comment.id && parent.post_id == comment.post_id);
+            }
+        }
+        assert!(!blog.comments.is_empty());
+    }
+}
@@ -0,0 +1,70 @@
+//! URL slugs for posts and tag names.
+
+// Lowercase ASCII words joined by dashes; empty when there are none.
+fn dashed(text: &str) -> String {
+    let mut slug = String::new();
+    for c in deunicode::deunicode(text).chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Turns a title into a lowercase ASCII slug: "Hello, Wörld!" becomes
+/// "hello-world". Titles without any letters or digits give "post".
+pub fn slugify(title: &str) -> String {
+    let slug = dashed(title);
+    if slug.is_empty() {
+        "post".to_string()
+    } else {
+        slug
+    }
+}
+
+/// Picks `base`, or `base-2`, `base-3`, ... whichever isn't taken yet.
+pub fn next_free_slug(base: &str, taken: &[String]) -> String {
+    if !taken.iter().any(|slug| slug == base) {
+        return base.to_string();
+    }
+
+    (2..)
+        .map(|n| format!("{}-{}", base, n))
+        .find(|candidate| !taken.contains(candidate))
+        .unwrap()
+}
+
+/// Tags are kept as slugs too, so "Rust Lang" and "rust-lang" are one tag.
+/// Returns `None` for names without any letters or digits.
+pub fn normalize_tag(name: &str) -> Option<String> {
+    Some(dashed(name)).filter(|tag| !tag.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugifies_titles() {
+        assert_eq!("hello-world", slugify("Hello, World!"));
+        assert_eq!("creme-brulee-recipe", slugify("  Crème brûlée -- recipe "));
+        assert_eq!("rust-2024", slugify("Rust 2024"));
+        assert_eq!("post", slugify("!!!"));
+    }
+
+    #[test]
+    fn finds_a_free_slug() {
+        let taken = vec!["hello".to_string(), "hello-2".to_string(), "hello-world".to_string()];
+        assert_eq!("hello-3", next_free_slug("hello", &taken));
+        assert_eq!("fresh", next_free_slug("fresh", &taken));
+    }
+
+    #[test]
+    fn normalizes_tags() {
+        assert_eq!(Some("rust-lang".to_string()), normalize_tag("Rust Lang"));
+        assert_eq!(Some("posts".to_string()), normalize_tag("Posts"));
+        assert_eq!(None, normalize_tag(" ?! "));
+    }
+}
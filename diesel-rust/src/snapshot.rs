@@ -0,0 +1,116 @@
+//! The whole blog as one JSON document, for `blog dump` and `blog load`.
+//!
+//! Rows keep their ids, so a snapshot loads into an empty database as the
+//! same blog, on Postgres or SQLite alike.
+
+use crate::models::{Attachment, Author, Comment, Post, PostReview, PostStatus, PostTag, ReviewState, Revision, Tag};
+use serde::{Deserialize, Serialize};
+
+/// Bumped when the layout of a snapshot changes. Version 2 added reviews.
+pub const FORMAT_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub authors: Vec<Author>,
+    pub posts: Vec<Post>,
+    pub tags: Vec<Tag>,
+    pub posts_tags: Vec<PostTag>,
+    pub revisions: Vec<Revision>,
+    pub comments: Vec<Comment>,
+    /// Only the rows: the files stay in the storage. Snapshots from before
+    /// attachments have none.
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    /// Version 1 snapshots have none, and no review state on their posts.
+    #[serde(default)]
+    pub reviews: Vec<PostReview>,
+}
+
+impl Snapshot {
+    /// Reads a snapshot, refusing ones written in a newer format version.
+    /// Version 1 snapshots are upgraded like the migration that added
+    /// reviews did: posts that aren't drafts count as approved.
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let mut snapshot: Snapshot =
+            serde_json::from_str(text).map_err(|err| format!("invalid snapshot: {}", err))?;
+        if !(1..=FORMAT_VERSION).contains(&snapshot.version) {
+            return Err(format!(
+                "snapshot format version {} isn't supported, expected {}",
+                snapshot.version, FORMAT_VERSION
+            ));
+        }
+        if snapshot.version == 1 {
+            for post in &mut snapshot.posts {
+                if post.status != PostStatus::Draft {
+                    post.review = ReviewState::Approved;
+                }
+            }
+            snapshot.version = FORMAT_VERSION;
+        }
+        Ok(snapshot)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Error serializing snapshot")
+    }
+
+    /// A one-line count of what the snapshot holds.
+    pub fn summary(&self) -> String {
+        format!(
+            "{} posts, {} authors, {} tags, {} revisions, {} comments, {} attachments and {} reviews",
+            self.posts.len(),
+            self.authors.len(),
+            self.tags.len(),
+            self.revisions.len(),
+            self.comments.len(),
+            self.attachments.len(),
+            self.reviews.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty(version: u32) -> Snapshot {
+        Snapshot {
+            version,
+            authors: Vec::new(),
+            posts: Vec::new(),
+            tags: Vec::new(),
+            posts_tags: Vec::new(),
+            revisions: Vec::new(),
+            comments: Vec::new(),
+            attachments: Vec::new(),
+            reviews: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn checks_the_format_version() {
+        assert!(Snapshot::from_json(&empty(FORMAT_VERSION).to_json()).is_ok());
+        assert!(Snapshot::from_json(&empty(FORMAT_VERSION + 1).to_json()).is_err());
+        assert!(Snapshot::from_json("{}").is_err());
+    }
+
+    #[test]
+    fn upgrades_version_1_posts_to_approved() {
+        let post = |id: i32, status: &str| {
+            serde_json::json!({
+                "id": id, "title": "Hello", "body": "", "status": status, "slug": format!("hello-{}", id),
+                "author_id": null, "created_at": "2024-04-01T09:00:00", "updated_at": "2024-04-01T09:00:00",
+                "published_at": null, "publish_at": null,
+            })
+        };
+        let mut json = serde_json::to_value(empty(1)).unwrap();
+        json["posts"] = serde_json::json!([post(1, "draft"), post(2, "published"), post(3, "scheduled")]);
+        json.as_object_mut().unwrap().remove("reviews");
+
+        let snapshot = Snapshot::from_json(&json.to_string()).unwrap();
+        assert_eq!(FORMAT_VERSION, snapshot.version);
+        let reviews: Vec<ReviewState> = snapshot.posts.iter().map(|post| post.review).collect();
+        assert_eq!(vec![ReviewState::Unreviewed, ReviewState::Approved, ReviewState::Approved], reviews);
+    }
+}
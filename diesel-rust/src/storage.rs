@@ -0,0 +1,120 @@
+//! Where the files attached to posts are kept.
+//!
+//! The database only records attachments; their bytes go to a [`Storage`],
+//! under keys made by [`attachments::storage_key`](crate::attachments::storage_key).
+//! [`LocalStorage`] keeps them in a directory. Other places, like an object
+//! store, only need another implementation of the trait.
+
+use crate::error::{BlogError, Result};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+pub trait Storage: Send + Sync {
+    /// Saves `bytes` under `key`, replacing whatever was there.
+    fn put(&self, key: &str, bytes: &[u8]) -> Result<()>;
+
+    fn get(&self, key: &str) -> Result<Vec<u8>>;
+
+    /// Removes the file under `key`; a missing one is not an error.
+    fn delete(&self, key: &str) -> Result<()>;
+
+    /// The URL readers fetch the file under `key` from.
+    fn url(&self, key: &str) -> String;
+}
+
+/// Files in a directory, served from `base_url`.
+#[derive(Debug, Clone)]
+pub struct LocalStorage {
+    root: PathBuf,
+    base_url: String,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>, base_url: &str) -> Self {
+        LocalStorage {
+            root: root.into(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Storage in `BLOG_ATTACHMENTS_DIR`, `attachments` by default, with URLs
+    /// under `BLOG_ATTACHMENTS_URL`, by default `/attachments` where
+    /// `blog-server` serves them.
+    pub fn from_env() -> Self {
+        dotenvy::dotenv().ok();
+        LocalStorage::new(
+            env::var("BLOG_ATTACHMENTS_DIR").unwrap_or_else(|_| "attachments".to_string()),
+            &env::var("BLOG_ATTACHMENTS_URL").unwrap_or_else(|_| "/attachments".to_string()),
+        )
+    }
+
+    /// The path of the file under `key`. Keys are relative paths that stay
+    /// inside the directory.
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        let relative = Path::new(key);
+        let inside = !key.is_empty()
+            && !key.contains('\\')
+            && relative.components().all(|part| matches!(part, Component::Normal(_)));
+        if !inside {
+            return Err(BlogError::Config(format!("Invalid storage key '{}'", key)));
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+impl Storage for LocalStorage {
+    fn put(&self, key: &str, bytes: &[u8]) -> Result<()> {
+        let path = self.path(key)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(BlogError::io(dir))?;
+        }
+        // Write to a temporary file first, so a failed write never leaves
+        // half a file under the key.
+        let mut partial = path.clone().into_os_string();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+        fs::write(&partial, bytes).map_err(BlogError::io(&partial))?;
+        fs::rename(&partial, &path).map_err(BlogError::io(&path))
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let path = self.path(key)?;
+        fs::read(&path).map_err(BlogError::io(&path))
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        let path = self.path(key)?;
+        match fs::remove_file(&path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(BlogError::io(&path)(err)),
+            _ => Ok(()),
+        }
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("{}/{}", self.base_url, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_files_in_its_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = LocalStorage::new(dir.path(), "https://blog.example.com/files/");
+
+        storage.put("ab/abc.png", b"png").unwrap();
+        assert_eq!(b"png".to_vec(), storage.get("ab/abc.png").unwrap());
+        assert_eq!("https://blog.example.com/files/ab/abc.png", storage.url("ab/abc.png"));
+        storage.delete("ab/abc.png").unwrap();
+        storage.delete("ab/abc.png").unwrap();
+        assert!(storage.get("ab/abc.png").is_err());
+
+        for key in ["", "../x", "/etc/passwd", "a/../../x", "a\\..\\x", "./x"] {
+            assert!(storage.put(key, b"").is_err(), "{}", key);
+        }
+    }
+}
@@ -0,0 +1,277 @@
+// The JSON API, called through actix's test service on a fresh database:
+// cargo test -p diesel-rust --test api
+#![cfg(feature = "server")]
+
+mod common;
+
+use actix_web::http::{header, StatusCode};
+use actix_web::{test, web, App};
+use diesel_rust::api::{self, ApiConfig};
+use diesel_rust::pool::build_blog_pool;
+use diesel_rust::storage::{LocalStorage, Storage};
+use diesel_rust::{PoolConfig, PostRepository};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+const TOKEN: &str = "secret";
+
+macro_rules! app {
+    // For tests that don't touch attachments.
+    ($db:expr) => {
+        app!($db, "attachments-not-used")
+    };
+    ($db:expr, $attachments_dir:expr) => {{
+        let config = PoolConfig { max_size: 2, ..PoolConfig::default() };
+        let pool = build_blog_pool(&$db.url, &config).expect("Error building the pool");
+        let storage: Arc<dyn Storage> = Arc::new(LocalStorage::new($attachments_dir, "/attachments"));
+        test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool))
+                .app_data(web::Data::new(ApiConfig { token: Some(TOKEN.to_string()) }))
+                .app_data(web::Data::from(storage))
+                .configure(api::configure),
+        )
+        .await
+    }};
+}
+
+fn ids(page: &Value) -> Vec<i64> {
+    page["posts"].as_array().unwrap().iter().map(|post| post["id"].as_i64().unwrap()).collect()
+}
+
+fn admin(request: test::TestRequest) -> test::TestRequest {
+    request.insert_header((header::AUTHORIZATION, format!("Bearer {}", TOKEN)))
+}
+
+#[actix_web::test]
+async fn lists_published_posts_a_page_at_a_time() {
+    let mut db = common::setup();
+    let repo = &mut PostRepository::new(&mut db.conn);
+    for n in 1..=5 {
+        let post = repo.create(&format!("Post {}", n), "").unwrap();
+        if n != 3 {
+            common::publish(repo, post.id);
+        }
+    }
+    repo.set_tags(1, &["rust"]).unwrap();
+    let app = app!(db);
+
+    let request = test::TestRequest::get().uri("/posts?per_page=3").to_request();
+    let page: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(vec![1, 2, 4], ids(&page));
+    assert_eq!(json!({"total": 4, "page": 1, "per_page": 3, "pages": 2, "next_after": 4}), json!({
+        "total": page["total"],
+        "page": page["page"],
+        "per_page": page["per_page"],
+        "pages": page["pages"],
+        "next_after": page["next_after"],
+    }));
+
+    let request = test::TestRequest::get().uri("/posts?per_page=3&after=4").to_request();
+    let page: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(vec![5], ids(&page));
+    assert_eq!(Value::Null, page["next_after"]);
+
+    let request = test::TestRequest::get().uri("/posts?tag=rust&sort=newest").to_request();
+    let page: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(json!(1), page["total"]);
+
+    let bad = [
+        "/posts?per_page=0",
+        "/posts?sort=sideways",
+        "/posts?page=2&after=1",
+        "/posts?page=0",
+        "/posts?page=9223372036854775807",
+    ];
+    for uri in bad {
+        let response = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(StatusCode::BAD_REQUEST, response.status(), "{}", uri);
+    }
+}
+
+#[actix_web::test]
+async fn shows_drafts_only_to_admins() {
+    let mut db = common::setup();
+    let repo = &mut PostRepository::new(&mut db.conn);
+    let post = repo.create("Hello", "World").unwrap();
+    repo.set_tags(post.id, &["rust"]).unwrap();
+    let app = app!(db);
+
+    let response = test::call_service(&app, test::TestRequest::get().uri("/posts/hello").to_request()).await;
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!("Unable to find post 'hello'", body["error"]);
+
+    let request = admin(test::TestRequest::get().uri("/posts/hello")).to_request();
+    let details: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!("World", details["body"]);
+    assert_eq!("draft", details["status"]);
+    assert_eq!("rust", details["tags"][0]["name"]);
+}
+
+#[actix_web::test]
+async fn creates_updates_and_deletes_posts() {
+    let mut db = common::setup();
+    let reviewer = PostRepository::new(&mut db.conn).create_author("Grace", "grace@example.com").unwrap();
+    let app = app!(db);
+
+    let request = admin(test::TestRequest::post().uri("/posts"))
+        .set_json(json!({"title": "Hello", "body": "First", "tags": ["Rust"]}))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(StatusCode::CREATED, response.status());
+    let created: Value = test::read_body_json(response).await;
+    assert_eq!("hello", created["slug"]);
+    assert_eq!("draft", created["status"]);
+    assert_eq!("rust", created["tags"][0]["name"]);
+
+    let request = admin(test::TestRequest::put().uri("/posts/1"))
+        .set_json(json!({"status": "published"}))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(StatusCode::CONFLICT, response.status());
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(
+        "Post 1 can't be published while it is a draft: send it for review and have it approved first",
+        body["error"]
+    );
+
+    let request = admin(test::TestRequest::post().uri("/posts/1/review")).to_request();
+    let pending: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!("pending", pending["review"]);
+    let request = admin(test::TestRequest::post().uri("/posts/1/approve"))
+        .set_json(json!({"reviewer": reviewer.email}))
+        .to_request();
+    let created: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!("published", created["status"]);
+    assert_eq!("approved", created["review"]);
+    assert!(created["published_at"].is_string());
+    let request = admin(test::TestRequest::post().uri("/posts/1/reject"))
+        .set_json(json!({"reviewer": reviewer.email}))
+        .to_request();
+    assert_eq!(StatusCode::CONFLICT, test::call_service(&app, request).await.status());
+
+    let request = admin(test::TestRequest::put().uri("/posts/1"))
+        .set_json(json!({"body": "Changed"}))
+        .to_request();
+    let updated: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!("Hello", updated["title"]);
+    assert_eq!("Changed", updated["body"]);
+    assert_eq!(created["published_at"], updated["published_at"]);
+
+    let request = admin(test::TestRequest::put().uri("/posts/1"))
+        .set_json(json!({"status": "scheduled"}))
+        .to_request();
+    assert_eq!(StatusCode::BAD_REQUEST, test::call_service(&app, request).await.status());
+    let request = admin(test::TestRequest::put().uri("/posts/1"))
+        .set_json(json!({"author": "nobody@example.com"}))
+        .to_request();
+    assert_eq!(StatusCode::NOT_FOUND, test::call_service(&app, request).await.status());
+
+    let request = admin(test::TestRequest::delete().uri("/posts/1")).to_request();
+    let deleted: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!("Changed", deleted["body"]);
+    let response = test::call_service(&app, admin(test::TestRequest::delete().uri("/posts/1")).to_request()).await;
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+}
+
+#[actix_web::test]
+async fn needs_the_token_to_change_posts() {
+    let db = common::setup();
+    let app = app!(db);
+
+    let attempts = [
+        test::TestRequest::post().uri("/posts").set_json(json!({"title": "Hello"})),
+        test::TestRequest::put().uri("/posts/1").set_json(json!({"title": "Hello"})),
+        test::TestRequest::delete().uri("/posts/1"),
+        test::TestRequest::delete().uri("/posts/1").insert_header((header::AUTHORIZATION, "Bearer wrong")),
+    ];
+    for request in attempts {
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+        assert_eq!("Bearer", response.headers().get(header::WWW_AUTHENTICATE).unwrap());
+    }
+
+    let request = admin(test::TestRequest::post().uri("/posts")).set_json(json!({"title": " "})).to_request();
+    assert_eq!(StatusCode::BAD_REQUEST, test::call_service(&app, request).await.status());
+}
+
+#[actix_web::test]
+async fn uploads_and_serves_attachments() {
+    let mut db = common::setup();
+    let repo = &mut PostRepository::new(&mut db.conn);
+    let post = repo.create("Hello", "![Me](attachment:me.png)").unwrap();
+    let files = tempfile::tempdir().unwrap();
+    let app = app!(db, files.path());
+
+    let request = test::TestRequest::post().uri("/posts/1/attachments/me.png").set_payload("png").to_request();
+    assert_eq!(StatusCode::UNAUTHORIZED, test::call_service(&app, request).await.status());
+    let request = admin(test::TestRequest::post().uri("/posts/1/attachments/me.png")).set_payload("png").to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(StatusCode::CREATED, response.status());
+    let attachment: Value = test::read_body_json(response).await;
+    assert_eq!("image/png", attachment["mime_type"]);
+    assert_eq!(3, attachment["size"]);
+    let url = format!("/attachments/{}", attachment["storage_key"].as_str().unwrap());
+
+    let request = admin(test::TestRequest::post().uri("/posts/1/attachments/me.png")).set_payload("gif").to_request();
+    assert_eq!(StatusCode::CONFLICT, test::call_service(&app, request).await.status());
+    let request = admin(test::TestRequest::post().uri("/posts/1/attachments/.hidden")).set_payload("").to_request();
+    assert_eq!(StatusCode::BAD_REQUEST, test::call_service(&app, request).await.status());
+
+    // A draft's files are only for admins, and kept out of shared caches.
+    let response = test::call_service(&app, test::TestRequest::get().uri(&url).to_request()).await;
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+    let response = test::call_service(&app, admin(test::TestRequest::get().uri(&url)).to_request()).await;
+    assert_eq!(StatusCode::OK, response.status());
+    assert!(response.headers().get(header::CACHE_CONTROL).unwrap().to_str().unwrap().starts_with("private"));
+    common::publish(&mut PostRepository::new(&mut db.conn), post.id);
+
+    let response = test::call_service(&app, test::TestRequest::get().uri(&url).to_request()).await;
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!("image/png", response.headers().get(header::CONTENT_TYPE).unwrap());
+    assert!(response.headers().get(header::CACHE_CONTROL).unwrap().to_str().unwrap().starts_with("public"));
+    assert!(response.headers().get(header::CONTENT_DISPOSITION).is_none());
+    assert_eq!("png", test::read_body(response).await);
+
+    let details: Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/posts/hello").to_request()).await;
+    assert_eq!(format!("![Me]({})", url), details["body"]);
+    assert_eq!("me.png", details["attachments"][0]["filename"]);
+    let page: Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/posts").to_request()).await;
+    assert_eq!(format!("![Me]({})", url), page["posts"][0]["body"]);
+}
+
+#[actix_web::test]
+async fn downloads_files_that_could_run_scripts() {
+    let mut db = common::setup();
+    let post = PostRepository::new(&mut db.conn).create("Hello", "").unwrap();
+    common::publish(&mut PostRepository::new(&mut db.conn), post.id);
+    let files = tempfile::tempdir().unwrap();
+    let app = app!(db, files.path());
+
+    let request = admin(test::TestRequest::post().uri("/posts/1/attachments/logo.svg")).set_payload("<svg/>").to_request();
+    let attachment: Value = test::call_and_read_body_json(&app, request).await;
+    let url = format!("/attachments/{}", attachment["storage_key"].as_str().unwrap());
+
+    let response = test::call_service(&app, test::TestRequest::get().uri(&url).to_request()).await;
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!("attachment", response.headers().get(header::CONTENT_DISPOSITION).unwrap());
+    assert_eq!("sandbox", response.headers().get(header::CONTENT_SECURITY_POLICY).unwrap());
+}
+
+#[actix_web::test]
+async fn deleting_a_post_deletes_its_files() {
+    let mut db = common::setup();
+    PostRepository::new(&mut db.conn).create("Hello", "").unwrap();
+    let files = tempfile::tempdir().unwrap();
+    let app = app!(db, files.path());
+
+    let request = admin(test::TestRequest::post().uri("/posts/1/attachments/me.png")).set_payload("png").to_request();
+    let attachment: Value = test::call_and_read_body_json(&app, request).await;
+    let stored = files.path().join(attachment["storage_key"].as_str().unwrap());
+    assert!(stored.exists());
+
+    let response = test::call_service(&app, admin(test::TestRequest::delete().uri("/posts/1")).to_request()).await;
+    assert_eq!(StatusCode::OK, response.status());
+    assert!(!stored.exists());
+}
@@ -0,0 +1,47 @@
+// Attaching files to posts, with the files kept in a temporary directory:
+// cargo test -p diesel-rust --test attachments
+
+mod common;
+
+use diesel_rust::attachments::{attach, detach, release};
+use diesel_rust::storage::{LocalStorage, Storage};
+use diesel_rust::{create_post, BlogError, PostRepository};
+
+#[test]
+fn stores_each_file_once_until_nothing_uses_it() {
+    let mut db = common::setup();
+    let first = create_post(&mut db.conn, "First", "");
+    let second = create_post(&mut db.conn, "Second", "");
+    let repo = &mut PostRepository::new(&mut db.conn);
+    let files = tempfile::tempdir().unwrap();
+    let storage = LocalStorage::new(files.path(), "/attachments");
+
+    let cat = attach(repo, &storage, first.id, "cat.png", b"meow").unwrap();
+    let again = attach(repo, &storage, second.id, "kitten.png", b"meow").unwrap();
+    assert_eq!(cat.storage_key, again.storage_key);
+    assert_eq!((4, "image/png"), (cat.size, cat.mime_type.as_str()));
+    let listed = repo.details(first.id).unwrap().attachments;
+    assert_eq!(vec![cat.id], listed.iter().map(|attachment| attachment.id).collect::<Vec<_>>());
+
+    assert!(matches!(
+        attach(repo, &storage, first.id, "cat.png", b"purr"),
+        Err(BlogError::AttachmentExists { .. })
+    ));
+    assert!(matches!(
+        attach(repo, &storage, first.id, "my cat.png", b"purr"),
+        Err(BlogError::InvalidAttachmentName(_))
+    ));
+    assert!(matches!(attach(repo, &storage, 99, "cat.png", b"purr"), Err(BlogError::NotFound(99))));
+    assert_eq!(1, repo.attachments(first.id).unwrap().len());
+
+    // The second post still uses the file.
+    detach(repo, &storage, cat.id).unwrap();
+    assert_eq!(b"meow".to_vec(), storage.get(&cat.storage_key).unwrap());
+    assert!(matches!(detach(repo, &storage, cat.id), Err(BlogError::AttachmentNotFound(_))));
+
+    // Deleting a post deletes its attachments, and release() their files.
+    let attached = repo.attachments_of(&[second.id]).unwrap();
+    repo.delete(second.id).unwrap();
+    release(repo, &storage, &attached).unwrap();
+    assert!(storage.get(&cat.storage_key).is_err());
+}
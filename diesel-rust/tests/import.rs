@@ -0,0 +1,85 @@
+// Importing posts over the ones already in the blog:
+// cargo test -p diesel-rust --test import
+
+mod common;
+
+use diesel_rust::import::{import, read_json, read_markdown_dir};
+use diesel_rust::models::ReviewState;
+use diesel_rust::{create_post, BlogError, PostRepository};
+use std::fs;
+
+const POSTS: &str = r#"[
+    {"title": "Hello", "body": "Hi", "tags": ["rust"], "author": "Ada <ada@example.com>",
+     "published": true, "published_at": "2024-04-01T09:00:00"},
+    {"title": "Draft", "body": "Later"}
+]"#;
+
+#[test]
+fn creates_updates_and_skips_by_slug() {
+    let mut db = common::setup();
+    let existing = create_post(&mut db.conn, "Draft", "Old");
+    let repo = &mut PostRepository::new(&mut db.conn);
+    let posts = read_json("posts.json", POSTS).unwrap();
+
+    let dry_run = import(repo, &posts, true).unwrap();
+    assert_eq!((vec!["hello".to_string()], vec!["draft".to_string()]), (dry_run.created, dry_run.updated));
+    assert_eq!(vec!["ada@example.com".to_string()], dry_run.authors);
+    assert!(matches!(repo.get_by_slug("hello"), Err(BlogError::SlugNotFound(_))));
+    assert!(repo.find_author("ada@example.com").is_err());
+
+    let report = import(repo, &posts, false).unwrap();
+    assert_eq!((1, 1, 0), (report.created.len(), report.updated.len(), report.skipped.len()));
+    let hello = repo.get_by_slug("hello").unwrap();
+    let hello = repo.details(hello.id).unwrap();
+    assert_eq!("Ada", hello.author.unwrap().name);
+    assert_eq!("rust", hello.tags[0].name);
+    assert_eq!("2024-04-01 09:00", hello.post.published_at.unwrap().format("%Y-%m-%d %H:%M").to_string());
+    assert_eq!("Later", repo.get(existing.id).unwrap().body);
+
+    let again = import(repo, &posts, false).unwrap();
+    assert_eq!(vec!["hello".to_string(), "draft".to_string()], again.skipped);
+    assert!(again.created.is_empty() && again.updated.is_empty() && again.authors.is_empty());
+    assert_eq!(ReviewState::Approved, repo.get_by_slug("hello").unwrap().review);
+}
+
+#[test]
+fn only_publishes_new_or_approved_posts() {
+    let mut db = common::setup();
+    let draft = create_post(&mut db.conn, "Draft", "Old");
+    let repo = &mut PostRepository::new(&mut db.conn);
+    let posts = read_json("posts.json", r#"[{"title": "Draft", "body": "New", "published": true}]"#).unwrap();
+
+    assert!(matches!(import(repo, &posts, false), Err(BlogError::InvalidTransition { .. })));
+    assert_eq!("Old", repo.get(draft.id).unwrap().body);
+
+    // An archived post comes back out as it was approved, but not with text
+    // nobody reviewed.
+    common::publish(repo, draft.id);
+    repo.archive(draft.id).unwrap();
+    assert!(matches!(import(repo, &posts, false), Err(BlogError::InvalidTransition { .. })));
+    let same = read_json("posts.json", r#"[{"title": "Draft", "body": "Old", "published": true}]"#).unwrap();
+    import(repo, &same, false).unwrap();
+    assert!(repo.get(draft.id).unwrap().is_published());
+}
+
+#[test]
+fn imports_markdown_folders_all_or_nothing() {
+    let mut db = common::setup();
+    let repo = &mut PostRepository::new(&mut db.conn);
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir_all(dir.path().join("2024/.drafts")).unwrap();
+    fs::write(dir.path().join("2024/hello.md"), "---\ntitle: Hello\ntags: [rust]\n---\nHi").unwrap();
+    fs::write(dir.path().join("2024/.drafts/wip.md"), "not even front matter").unwrap();
+    fs::write(dir.path().join("notes.txt"), "not a post").unwrap();
+    fs::write(dir.path().join("same.md"), "+++\ntitle = \"Other\"\nslug = \"hello\"\n+++\n").unwrap();
+
+    let posts = read_markdown_dir(dir.path()).unwrap();
+    assert_eq!(2, posts.len());
+    assert!(matches!(import(repo, &posts, false), Err(BlogError::InvalidImport { .. })));
+    assert!(repo.is_empty().unwrap());
+
+    fs::remove_file(dir.path().join("same.md")).unwrap();
+    let report = import(repo, &read_markdown_dir(dir.path()).unwrap(), false).unwrap();
+    assert_eq!(vec!["hello".to_string()], report.created);
+    assert!(repo.get_by_slug("hello").unwrap().is_published());
+}
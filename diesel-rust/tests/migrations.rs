@@ -0,0 +1,47 @@
+// The embedded migrations, run forwards and backwards on a fresh database:
+// cargo test -p diesel-rust --test migrations
+
+mod common;
+
+use diesel_rust::{migrations, BlogError, PostRepository};
+
+#[test]
+fn migrates_up_down_and_again() {
+    let mut db = common::empty();
+    let conn = &mut db.conn;
+
+    let pending = migrations::pending(conn).unwrap();
+    assert!(!pending.is_empty());
+    assert!(matches!(migrations::check(conn), Err(BlogError::PendingMigrations(names)) if names == pending));
+
+    assert_eq!(pending, migrations::run_pending(conn).unwrap());
+    migrations::check(conn).unwrap();
+    assert!(migrations::run_pending(conn).unwrap().is_empty());
+
+    let last = pending.last().cloned();
+    assert_eq!(last, migrations::revert_last(conn).unwrap());
+    let status = migrations::status(conn).unwrap();
+    assert_eq!(pending, status.iter().map(|migration| migration.name.clone()).collect::<Vec<_>>());
+    assert!(!status.last().unwrap().applied && status[..status.len() - 1].iter().all(|m| m.applied));
+    assert_eq!(last.into_iter().collect::<Vec<_>>(), migrations::run_pending(conn).unwrap());
+
+    assert_eq!(pending.last(), migrations::redo(conn).unwrap().as_ref());
+    migrations::check(conn).unwrap();
+    PostRepository::new(conn).create("Hello", "").unwrap();
+}
+
+#[test]
+fn reverts_every_migration() {
+    let mut db = common::setup();
+    let conn = &mut db.conn;
+    let mut reverted = Vec::new();
+    while let Some(name) = migrations::revert_last(conn).unwrap() {
+        reverted.push(name);
+    }
+    reverted.reverse();
+
+    assert_eq!(reverted, migrations::pending(conn).unwrap());
+    assert!(migrations::status(conn).unwrap().iter().all(|migration| !migration.applied));
+    assert_eq!(None, migrations::redo(conn).unwrap());
+    assert_eq!(reverted, migrations::run_pending(conn).unwrap());
+}
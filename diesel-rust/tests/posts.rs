@@ -0,0 +1,63 @@
+// The basic post lifecycle, on whichever database common::setup() gives:
+// cargo test -p diesel-rust --test posts
+
+mod common;
+
+use diesel_rust::models::PostStatus;
+use diesel_rust::{create_post, BlogError, PostRepository};
+
+#[test]
+fn create_post_saves_a_draft() {
+    let mut db = common::setup();
+    let post = create_post(&mut db.conn, "Hello", "First post");
+    let repo = &mut PostRepository::new(&mut db.conn);
+
+    // Every test has its own database, so ids and slugs start over.
+    assert_eq!(1, post.id);
+    assert_eq!("hello", post.slug);
+    assert_eq!(PostStatus::Draft, post.status);
+    assert!(post.published_at.is_none());
+
+    let saved = repo.get(post.id).unwrap();
+    assert_eq!(("Hello", "First post"), (saved.title.as_str(), saved.body.as_str()));
+    assert_eq!(1, repo.history(post.id).unwrap().len());
+}
+
+#[test]
+fn publish_sets_the_publication_time() {
+    let mut db = common::setup();
+    let post = create_post(&mut db.conn, "Hello", "");
+    let repo = &mut PostRepository::new(&mut db.conn);
+
+    let published = common::publish(repo, post.id);
+    assert!(published.is_published());
+    assert!(published.published_at >= Some(post.created_at));
+    assert_eq!(published.published_at, repo.get(post.id).unwrap().published_at);
+    assert!(matches!(repo.publish(post.id + 1), Err(BlogError::NotFound(_))));
+}
+
+#[test]
+fn delete_returns_the_deleted_post() {
+    let mut db = common::setup();
+    let kept = create_post(&mut db.conn, "Kept", "");
+    let post = create_post(&mut db.conn, "Hello", "");
+    let repo = &mut PostRepository::new(&mut db.conn);
+
+    assert_eq!("hello", repo.delete(post.id).unwrap().slug);
+    assert!(matches!(repo.get(post.id), Err(BlogError::NotFound(id)) if id == post.id));
+    assert!(matches!(repo.delete(post.id), Err(BlogError::NotFound(_))));
+    assert!(matches!(repo.history(post.id), Err(BlogError::NotFound(_))));
+    assert_eq!(kept.id, repo.get(kept.id).unwrap().id);
+}
+
+#[test]
+fn get_finds_posts_by_id_and_slug() {
+    let mut db = common::setup();
+    let post = create_post(&mut db.conn, "Hello", "");
+    let repo = &mut PostRepository::new(&mut db.conn);
+
+    assert_eq!(post.slug, repo.get(post.id).unwrap().slug);
+    assert_eq!(post.id, repo.get_by_slug("hello").unwrap().id);
+    assert!(matches!(repo.get(0), Err(BlogError::NotFound(0))));
+    assert!(repo.get_by_slug("nope").is_err());
+}
@@ -0,0 +1,324 @@
+// The repository against a real database, a fresh one per test. These run on
+// SQLite, so they need no database server, unless TEST_DATABASE_URL points at
+// Postgres (see common/mod.rs):
+// cargo test -p diesel-rust --test repository
+
+mod common;
+
+use chrono::NaiveDate;
+use diesel_rust::models::{NewComment, PostChanges, PostStatus, ReviewState, Transition};
+use diesel_rust::query::{Sort, Visibility};
+use diesel_rust::{seed, BlogError, PostQuery, PostRepository};
+
+#[test]
+fn creates_posts_with_free_slugs() {
+    let mut db = common::setup();
+    let repo = &mut PostRepository::new(&mut db.conn);
+
+    let first = repo.create("Hello, World!", "First").unwrap();
+    let second = repo.create("Hello world", "Second").unwrap();
+
+    assert_eq!("hello-world", first.slug);
+    assert_eq!("hello-world-2", second.slug);
+    assert_eq!(PostStatus::Draft, first.status);
+    assert_eq!(second.id, repo.get_by_slug("hello-world-2").unwrap().id);
+    assert!(matches!(repo.get(999), Err(BlogError::NotFound(999))));
+}
+
+#[test]
+fn finds_posts_by_slug_before_id() {
+    let mut db = common::setup();
+    let repo = &mut PostRepository::new(&mut db.conn);
+
+    let first = repo.create("First", "").unwrap();
+    let numbered = repo.create("1", "").unwrap();
+    assert_eq!("1", numbered.slug);
+
+    assert_eq!(numbered.id, repo.find("1").unwrap().id);
+    assert_eq!(first.id, repo.find("#1").unwrap().id);
+    assert_eq!(numbered.id, repo.find(&numbered.id.to_string()).unwrap().id);
+    assert_eq!(first.id, repo.find("first").unwrap().id);
+    assert!(matches!(repo.find("99"), Err(BlogError::NotFound(99))));
+    assert!(matches!(repo.find("#99"), Err(BlogError::NotFound(99))));
+    assert!(matches!(repo.find("nope"), Err(BlogError::SlugNotFound(_))));
+}
+
+#[test]
+fn publishes_and_lists() {
+    let mut db = common::setup();
+    let repo = &mut PostRepository::new(&mut db.conn);
+
+    let draft = repo.create("Draft", "").unwrap();
+    let post = repo.create("Post", "").unwrap();
+    let published = common::publish(repo, post.id);
+    assert!(published.is_published() && published.published_at.is_some());
+
+    let listed: Vec<i32> = repo.list(false, 10).unwrap().iter().map(|post| post.id).collect();
+    assert_eq!(vec![post.id], listed);
+    let listed: Vec<i32> = repo.list(true, 10).unwrap().iter().map(|post| post.id).collect();
+    assert_eq!(vec![draft.id, post.id], listed);
+
+    let unpublished = repo.unpublish(post.id).unwrap();
+    assert!(unpublished.status == PostStatus::Draft && unpublished.published_at.is_none());
+}
+
+#[test]
+fn publishes_scheduled_posts_when_due() {
+    let mut db = common::setup();
+    let repo = &mut PostRepository::new(&mut db.conn);
+    let at = |hour| NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(hour, 0, 0).unwrap();
+
+    let reviewer = repo.create_author("Grace", "grace@example.com").unwrap();
+    let early = repo.create("Early", "").unwrap();
+    let late = repo.create("Late", "").unwrap();
+    repo.request_review(early.id).unwrap();
+    repo.request_review(late.id).unwrap();
+    let scheduled = repo.approve(early.id, reviewer.id, Some(at(9))).unwrap();
+    assert_eq!((PostStatus::Scheduled, Some(at(9))), (scheduled.status, scheduled.publish_at));
+    repo.approve(late.id, reviewer.id, Some(at(10))).unwrap();
+    repo.schedule(late.id, at(18)).unwrap();
+
+    assert!(repo.publish_due(at(8)).unwrap().is_empty());
+    let due = repo.publish_due(at(12)).unwrap();
+    assert_eq!(vec![early.id], due.iter().map(|post| post.id).collect::<Vec<_>>());
+    assert_eq!((PostStatus::Published, Some(at(9)), None), (due[0].status, due[0].published_at, due[0].publish_at));
+    assert!(repo.publish_due(at(12)).unwrap().is_empty());
+
+    let drafts = repo.query(&PostQuery::new().visibility(Visibility::Drafts)).unwrap();
+    assert_eq!(vec![late.id], drafts.posts.iter().map(|post| post.id).collect::<Vec<_>>());
+}
+
+#[test]
+fn drafts_are_reviewed_before_they_are_published() {
+    let mut db = common::setup();
+    let repo = &mut PostRepository::new(&mut db.conn);
+    let reviewer = repo.create_author("Grace", "grace@example.com").unwrap();
+    let post = repo.create("Hello", "").unwrap();
+
+    let refused = repo.publish(post.id).err().unwrap();
+    assert!(matches!(refused, BlogError::InvalidTransition { transition: Transition::Publish, .. }));
+    assert_eq!(
+        "Post 1 can't be published while it is a draft: send it for review and have it approved first",
+        refused.to_string()
+    );
+    assert!(matches!(repo.approve(post.id, reviewer.id, None), Err(BlogError::InvalidTransition { .. })));
+
+    assert_eq!(ReviewState::Pending, repo.request_review(post.id).unwrap().review);
+    assert!(matches!(repo.request_review(post.id), Err(BlogError::InvalidTransition { .. })));
+    assert_eq!(
+        "Post 1 can't be scheduled while it is waiting for review: it has to be approved first",
+        repo.schedule(post.id, post.created_at).err().unwrap().to_string()
+    );
+    let rejected = repo.reject(post.id, reviewer.id).unwrap();
+    assert_eq!((PostStatus::Draft, ReviewState::Unreviewed), (rejected.status, rejected.review));
+
+    repo.request_review(post.id).unwrap();
+    let approved = repo.approve(post.id, reviewer.id, None).unwrap();
+    assert_eq!((PostStatus::Published, ReviewState::Approved), (approved.status, approved.review));
+    assert!(approved.published_at.is_some());
+    let reviews: Vec<(bool, i32)> = repo
+        .reviews(post.id)
+        .unwrap()
+        .iter()
+        .map(|(review, author)| (review.approved, author.id))
+        .collect();
+    assert_eq!(vec![(false, reviewer.id), (true, reviewer.id)], reviews);
+
+    // An archived post can come back out without another review, but one
+    // turned back into a draft can't.
+    repo.archive(post.id).unwrap();
+    assert!(repo.publish(post.id).unwrap().is_published());
+    assert_eq!(ReviewState::Unreviewed, repo.unpublish(post.id).unwrap().review);
+    assert!(matches!(repo.publish(post.id), Err(BlogError::InvalidTransition { .. })));
+    assert!(matches!(repo.reject(999, reviewer.id), Err(BlogError::NotFound(999))));
+}
+
+#[test]
+fn edits_before_publishing_need_another_review() {
+    let mut db = common::setup();
+    let repo = &mut PostRepository::new(&mut db.conn);
+    let at = |hour| NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(hour, 0, 0).unwrap();
+    let reviewer = repo.create_author("Grace", "grace@example.com").unwrap();
+    let post = repo.create("Hello", "Reviewed").unwrap();
+
+    repo.request_review(post.id).unwrap();
+    repo.approve(post.id, reviewer.id, Some(at(9))).unwrap();
+    let edited = repo.update(post.id, &PostChanges { title: None, body: Some("Not reviewed") }).unwrap();
+    assert_eq!(
+        (PostStatus::Draft, ReviewState::Unreviewed, None),
+        (edited.status, edited.review, edited.publish_at)
+    );
+    assert!(repo.publish_due(at(12)).unwrap().is_empty());
+    assert!(matches!(repo.publish(post.id), Err(BlogError::InvalidTransition { .. })));
+
+    // Reverting counts as an edit too, and one waiting for review is
+    // withdrawn from it.
+    repo.request_review(post.id).unwrap();
+    let reverted = repo.revert(post.id, 1).unwrap();
+    assert_eq!(("Reviewed", ReviewState::Unreviewed), (reverted.body.as_str(), reverted.review));
+
+    // A published post stays out; its edits are live.
+    repo.request_review(post.id).unwrap();
+    repo.approve(post.id, reviewer.id, None).unwrap();
+    let edited = repo.update(post.id, &PostChanges { title: Some("Hi"), body: None }).unwrap();
+    assert_eq!((PostStatus::Published, ReviewState::Approved), (edited.status, edited.review));
+}
+
+#[test]
+fn archived_posts_leave_listings() {
+    let mut db = common::setup();
+    let repo = &mut PostRepository::new(&mut db.conn);
+
+    let post = repo.create("Old news", "").unwrap();
+    common::publish(repo, post.id);
+    let archived = repo.archive(post.id).unwrap();
+    assert_eq!(PostStatus::Archived, archived.status);
+    assert!(archived.published_at.is_some());
+
+    assert!(repo.list(false, 10).unwrap().is_empty());
+    assert!(repo.search("news", Visibility::Published, 10).unwrap().is_empty());
+    let listed = repo.query(&PostQuery::new().visibility(Visibility::Archived)).unwrap();
+    assert_eq!(vec![post.id], listed.posts.iter().map(|post| post.id).collect::<Vec<_>>());
+}
+
+#[test]
+fn queries_by_tag_and_pages() {
+    let mut db = common::setup();
+    let repo = &mut PostRepository::new(&mut db.conn);
+
+    let ids: Vec<i32> = ["Charlie", "Alpha", "Bravo"]
+        .iter()
+        .map(|title| repo.create(title, "").unwrap().id)
+        .collect();
+    repo.set_tags(ids[0], &["Rust", "diesel"]).unwrap();
+    repo.set_tags(ids[1], &["rust"]).unwrap();
+    let tags: Vec<String> = repo.tags_of(ids[0]).unwrap().into_iter().map(|tag| tag.name).collect();
+    assert_eq!(vec!["diesel", "rust"], tags);
+
+    let tagged = repo
+        .query(&PostQuery::new().visibility(Visibility::All).tag("rust").sort(Sort::Title))
+        .unwrap();
+    assert_eq!(2, tagged.total);
+    assert_eq!(vec!["Alpha", "Charlie"], tagged.posts.iter().map(|post| post.title.as_str()).collect::<Vec<_>>());
+
+    let query = PostQuery::new().visibility(Visibility::All).sort(Sort::Title).page(1, 2);
+    let first = repo.query(&query).unwrap();
+    assert_eq!(Some(ids[2]), first.next_after);
+    let rest = repo.query(&query.after(first.next_after.unwrap(), 2)).unwrap();
+    assert_eq!(vec![ids[0]], rest.posts.iter().map(|post| post.id).collect::<Vec<_>>());
+}
+
+#[test]
+fn records_and_reverts_revisions() {
+    let mut db = common::setup();
+    let repo = &mut PostRepository::new(&mut db.conn);
+
+    let post = repo.create("Title", "one").unwrap();
+    let changes = PostChanges { title: None, body: Some("two") };
+    repo.update(post.id, &changes).unwrap();
+    assert_eq!(2, repo.history(post.id).unwrap().len());
+
+    let reverted = repo.revert(post.id, 1).unwrap();
+    assert_eq!("one", reverted.body);
+    assert_eq!(vec![1, 2, 3], repo.history(post.id).unwrap().iter().map(|rev| rev.revision).collect::<Vec<_>>());
+    assert!(matches!(
+        repo.revert(post.id, 9),
+        Err(BlogError::RevisionNotFound { revision: 9, .. })
+    ));
+}
+
+#[test]
+fn searches_titles_and_bodies() {
+    let mut db = common::setup();
+    let repo = &mut PostRepository::new(&mut db.conn);
+
+    let diesel = repo.create("Diesel tips", "Queries that compile").unwrap();
+    let other = repo.create("Cooking", "Recipes using diesel engines? No.").unwrap();
+    common::publish(repo, diesel.id);
+
+    let hits = repo.search("diesel", Visibility::All, 10).unwrap();
+    assert_eq!(vec![diesel.id, other.id], hits.iter().map(|hit| hit.post.id).collect::<Vec<_>>());
+    assert!(hits[1].snippet.contains("<mark>diesel</mark>"), "{}", hits[1].snippet);
+
+    let hits = repo.search("diesel", Visibility::Drafts, 10).unwrap();
+    assert_eq!(vec![other.id], hits.iter().map(|hit| hit.post.id).collect::<Vec<_>>());
+
+    repo.update(other.id, &PostChanges { title: None, body: Some("Soup") }).unwrap();
+    assert!(repo.search("engines", Visibility::All, 10).unwrap().is_empty());
+}
+
+#[test]
+fn deletes_posts_with_their_tags() {
+    let mut db = common::setup();
+    let repo = &mut PostRepository::new(&mut db.conn);
+
+    let post = repo.create("Gone", "").unwrap();
+    repo.set_tags(post.id, &["rust"]).unwrap();
+    assert_eq!(post.id, repo.delete(post.id).unwrap().id);
+    assert!(repo.search("gone", Visibility::All, 10).unwrap().is_empty());
+    assert_eq!(0, repo.delete_many(&[post.id]).unwrap());
+}
+
+#[test]
+fn threads_and_moderates_comments() {
+    let mut db = common::setup();
+    let repo = &mut PostRepository::new(&mut db.conn);
+    let post = repo.create("Post", "").unwrap();
+    let other = repo.create("Other", "").unwrap();
+    let comment = |parent_id, body| NewComment {
+        post_id: post.id,
+        parent_id,
+        author_name: "Ada",
+        author_email: "ada@example.com",
+        body,
+    };
+
+    let first = repo.add_comment(&comment(None, "First")).unwrap();
+    let reply = repo.add_comment(&comment(Some(first.id), "Reply")).unwrap();
+    assert!(!first.approved);
+    assert!(repo.comments(post.id, false).unwrap().is_empty());
+    assert_eq!(vec![first.id, reply.id], repo.pending_comments(10).unwrap().iter().map(|c| c.id).collect::<Vec<_>>());
+
+    repo.approve_comment(first.id).unwrap();
+    let threads = repo.comments(post.id, false).unwrap();
+    assert_eq!(1, threads.len());
+    assert!(threads[0].replies.is_empty());
+    let threads = repo.comments(post.id, true).unwrap();
+    assert_eq!(reply.id, threads[0].replies[0].comment.id);
+
+    let elsewhere = NewComment { post_id: other.id, ..comment(Some(first.id), "Wrong post") };
+    assert!(matches!(repo.add_comment(&elsewhere), Err(BlogError::CommentNotFound(_))));
+
+    repo.delete_comment(first.id).unwrap();
+    assert!(matches!(repo.comment(reply.id), Err(BlogError::CommentNotFound(_))));
+    assert!(matches!(repo.approve_comment(first.id), Err(BlogError::CommentNotFound(_))));
+}
+
+#[test]
+fn loads_what_it_dumps() {
+    let until = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    let blog = seed::generate(30, 3, until);
+
+    let mut db = common::setup();
+    let repo = &mut PostRepository::new(&mut db.conn);
+    assert!(repo.is_empty().unwrap());
+    repo.load(&blog).unwrap();
+    assert!(!repo.is_empty().unwrap());
+    let dumped = repo.dump().unwrap();
+    assert_eq!(blog.to_json(), dumped.to_json());
+
+    let mut copy = common::setup();
+    let copy_repo = &mut PostRepository::new(&mut copy.conn);
+    copy_repo.load(&dumped).unwrap();
+    assert_eq!(dumped.to_json(), copy_repo.dump().unwrap().to_json());
+
+    // Loaded posts are searchable, and new rows get fresh ids.
+    let title = &blog.posts[0].title;
+    let hits = copy_repo.search(title, Visibility::All, 5).unwrap();
+    assert!(hits.iter().any(|hit| hit.post.id == 1), "{}", title);
+    assert_eq!(31, copy_repo.create("New", "").unwrap().id);
+    assert_eq!(1, copy_repo.history(31).unwrap().len());
+
+    copy_repo.clear().unwrap();
+    assert!(copy_repo.is_empty().unwrap());
+}